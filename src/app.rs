@@ -1,33 +1,47 @@
-use ratatui::{crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind}, layout::{Constraint, Layout, Margin, Rect}, prelude::Backend, style::{Style, Stylize}, text::{Line, Span}, widgets::{Block, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation, Table}, Frame, Terminal};
+use ratatui::{crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, layout::{Constraint, Layout, Margin, Rect}, prelude::Backend, style::{Style, Stylize}, text::{Line, Span}, widgets::{Block, Cell, Clear, Paragraph, Row, Scrollbar, ScrollbarOrientation, Table}, Frame, Terminal};
 
-use crate::{context::{AppContext, AppMessageType, InputType, ScrollableTableState, ViewState}, registry};
+use std::{sync::Arc, time::Duration};
+
+use crate::{backend::RegistryBackend, context::{AppContext, AppMessageType, InputType, ScrollableTableState, ViewState}, hexedit::{HexPane, TextEncoding, BYTES_PER_ROW}, regfile::ChangeKind, registry, search::HitKind};
 
 pub const ITEM_HEIGHT: usize = 1;
+const HEX_PAGE_ROWS: usize = 16;
+/// How often results are collected while a search runs.
+const SEARCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct App {
     context: AppContext,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(backend: Arc<dyn RegistryBackend>) -> Self {
         Self {
-            context: AppContext::new(),
+            context: AppContext::new(backend),
         }
     }
 
     pub fn run<B: Backend>(&mut self, term: &mut Terminal<B>) -> std::io::Result<()> {
         loop {
-            if self.handle_events()? {
+            term.draw(|frame| self.draw(frame))?;
+
+            // a running search needs redraws without waiting for a key press
+            let has_event = match self.context.is_searching() {
+                true => event::poll(SEARCH_POLL_INTERVAL)?,
+                false => true,
+            };
+
+            if has_event && self.handle_events()? {
                 break;
             }
 
-            term.draw(|frame| self.draw(frame))?;
+            self.context.poll_search();
         }
 
         Ok(())
     }
 
     fn handle_input_textarea_events(&mut self, event: KeyEvent) -> std::io::Result<()> {
+        self.context.input.completions = None;
         self.context.input.textarea.input(event);
         Ok(())
     }
@@ -38,6 +52,79 @@ impl App {
         match event.code {
             KeyCode::Char('h') | KeyCode::Char('H') => self.context.prev_input_choice(),
             KeyCode::Char('l') | KeyCode::Char('L') => self.context.next_input_choice(),
+            KeyCode::Char('j') | KeyCode::Char('J') => self.context.next_preview_row(),
+            KeyCode::Char('k') | KeyCode::Char('K') => self.context.prev_preview_row(),
+
+            _ => (),
+        };
+
+        Ok(())
+    }
+
+    fn handle_input_hex_editor_events(&mut self, event: KeyEvent) -> std::io::Result<()> {
+        if event.kind != KeyEventKind::Press { return Ok(()); }
+
+        let InputType::HexEditor(ref mut editor) = self.context.input.ty else { return Ok(()); };
+        let select = event.modifiers.contains(KeyModifiers::SHIFT);
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match event.code {
+            KeyCode::Left => editor.move_left(select),
+            KeyCode::Right => editor.move_right(select),
+            KeyCode::Up => editor.move_up(1, select),
+            KeyCode::Down => editor.move_down(1, select),
+            KeyCode::PageUp => editor.move_up(HEX_PAGE_ROWS, select),
+            KeyCode::PageDown => editor.move_down(HEX_PAGE_ROWS, select),
+            KeyCode::Home if ctrl => editor.move_start(select),
+            KeyCode::End if ctrl => editor.move_end(select),
+            KeyCode::Home => editor.move_row_start(select),
+            KeyCode::End => editor.move_row_end(select),
+
+            KeyCode::Backspace => editor.backspace(),
+            KeyCode::Delete => editor.delete(),
+            KeyCode::Insert => editor.insert = !editor.insert,
+            KeyCode::Tab => editor.toggle_pane(),
+
+            KeyCode::Char('a') | KeyCode::Char('A') if ctrl => editor.select_all(),
+            KeyCode::Char('z') | KeyCode::Char('Z') if ctrl => editor.undo(),
+            KeyCode::Char('y') | KeyCode::Char('Y') if ctrl => editor.redo(),
+            KeyCode::Char('t') | KeyCode::Char('T') if ctrl => editor.toggle_encoding(),
+
+            KeyCode::Char(c) if editor.pane == HexPane::Hex => {
+                if let Some(digit) = c.to_digit(16) {
+                    editor.input_hex_digit(digit as u8);
+                }
+            }
+            KeyCode::Char(c) => editor.input_char(c),
+
+            _ => (),
+        };
+
+        Ok(())
+    }
+
+    fn handle_input_list_events(&mut self, event: KeyEvent) -> std::io::Result<()> {
+        if event.kind != KeyEventKind::Press { return Ok(()); }
+
+        let InputType::List(ref mut list) = self.context.input.ty else { return Ok(()); };
+
+        if list.is_editing() {
+            match event.code {
+                KeyCode::Enter => list.finish_edit(),
+                KeyCode::Esc => list.cancel_edit(),
+                _ => list.input(event),
+            };
+            return Ok(());
+        }
+
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down if !event.modifiers.contains(KeyModifiers::SHIFT) => list.next(),
+            KeyCode::Char('k') | KeyCode::Up if !event.modifiers.contains(KeyModifiers::SHIFT) => list.prev(),
+            KeyCode::Char('J') | KeyCode::Down => list.move_down(),
+            KeyCode::Char('K') | KeyCode::Up => list.move_up(),
+            KeyCode::Char('a') | KeyCode::Char('A') | KeyCode::Insert => list.add(),
+            KeyCode::Char('e') | KeyCode::Char('E') | KeyCode::F(2) => list.edit(),
+            KeyCode::Char('d') | KeyCode::Char('D') | KeyCode::Delete => list.remove(),
 
             _ => (),
         };
@@ -46,17 +133,35 @@ impl App {
     }
 
     fn handle_input_events(&mut self) -> std::io::Result<()> {
-        match event::read()? {
-            Event::Key(event) => match event.code {
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                // <Enter> and <Esc> finish or cancel the entry being edited
+                _ if self.context.input.ty.is_list_editing() => self.handle_input_list_events(event)?,
                 KeyCode::Esc if event.kind == KeyEventKind::Press => self.context.reset_input(),
+                // <Alt-Enter> starts a new line, e.g. for REG_MULTI_SZ strings
+                KeyCode::Enter if event.modifiers.contains(KeyModifiers::ALT) && self.context.input.ty.is_textarea() => {
+                    self.context.input.textarea.insert_newline();
+                }
                 KeyCode::Enter if event.kind == KeyEventKind::Press => self.context.confirm_input(),
+                // <Alt-…> flips options such as the ones of the find input
+                KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::ALT) && self.context.input.toggle_fn.is_some() => {
+                    if event.kind == KeyEventKind::Press {
+                        self.context.input.toggle(c);
+                    }
+                }
+                KeyCode::Tab if self.context.input.ty.is_textarea() && self.context.input.complete_fn.is_some() => {
+                    if event.kind == KeyEventKind::Press {
+                        self.context.input.complete();
+                    }
+                }
 
                 _ => match self.context.input.ty {
                     InputType::TextArea => self.handle_input_textarea_events(event)?,
                     InputType::Choice(_) => self.handle_input_choices_events(event)?,
+                    InputType::HexEditor(_) => self.handle_input_hex_editor_events(event)?,
+                    InputType::List(_) => self.handle_input_list_events(event)?,
                 }
             }
-            _ => (),
         };
 
         Ok(())
@@ -71,6 +176,48 @@ impl App {
         Ok(())
     }
 
+    fn handle_filter_events(&mut self) -> std::io::Result<()> {
+        match event::read()? {
+            Event::Key(event) if event.kind == KeyEventKind::Press => match event.code {
+                KeyCode::Esc => self.context.clear_filter(),
+                KeyCode::Enter => self.context.finish_filter(),
+                KeyCode::Tab => self.context.toggle_filter_mode(),
+                KeyCode::Backspace => self.context.filter_backspace(),
+                KeyCode::Down => self.context.next_row(),
+                KeyCode::Up => self.context.prev_row(),
+                KeyCode::Char(c) => self.context.filter_input(c),
+
+                _ => (),
+            }
+            _ => (),
+        };
+
+        Ok(())
+    }
+
+    fn handle_bookmark_events(&mut self) -> std::io::Result<()> {
+        match event::read()? {
+            Event::Key(event) if event.kind == KeyEventKind::Press => match event.code {
+                KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('B') => self.context.close_bookmarks(),
+                KeyCode::Char('j') | KeyCode::Char('J') | KeyCode::Down => self.context.next_row(),
+                KeyCode::Char('k') | KeyCode::Char('K') | KeyCode::Up => self.context.prev_row(),
+                KeyCode::Enter => self.context.jump_to_bookmark(),
+                KeyCode::Char('a') | KeyCode::Char('A') => self.context.add_bookmark(),
+                KeyCode::Char('r') | KeyCode::Char('R') => self.context.rename_bookmark(),
+                KeyCode::Char('d') | KeyCode::Char('D') => self.context.delete_bookmark(),
+
+                _ => (),
+            }
+            _ => (),
+        };
+
+        Ok(())
+    }
+
+    fn is_tree_focused(&self) -> bool {
+        self.context.tree.is_some() && self.context.view_state == ViewState::Keys
+    }
+
     fn handle_events(&mut self) -> std::io::Result<bool> {
         if self.context.view_state.is_input() {
             self.handle_input_events()?;
@@ -81,20 +228,64 @@ impl App {
             return Ok(false);
         }
 
+        if self.context.filter_editing {
+            self.handle_filter_events()?;
+            return Ok(false);
+        }
+        if self.context.view_state == ViewState::Bookmarks {
+            self.handle_bookmark_events()?;
+            return Ok(false);
+        }
+
         match event::read()? {
             Event::Key(event) if event.kind == KeyEventKind::Press => match event.code {
+                KeyCode::Esc if self.context.view_state == ViewState::Results => self.context.close_search(),
+                KeyCode::Esc if self.context.view_state == ViewState::Diff => self.context.close_diff(),
+                KeyCode::Esc if self.context.get_filter().is_some() => self.context.clear_filter(),
                 KeyCode::Esc => return Ok(true),
+
+                KeyCode::Left if event.modifiers.contains(KeyModifiers::ALT) => self.context.go_back(),
+                KeyCode::Right if event.modifiers.contains(KeyModifiers::ALT) => self.context.go_forward(),
+                KeyCode::Char('[') => self.context.go_back(),
+                KeyCode::Char(']') => self.context.go_forward(),
+
+                KeyCode::Char('/') => self.context.start_filter(),
+                KeyCode::Char('b') | KeyCode::Char('B') => self.context.open_bookmarks(),
+
+                KeyCode::Enter if self.context.view_state == ViewState::Results => self.context.jump_to_selected_hit(),
+                KeyCode::Enter if self.context.view_state == ViewState::Diff => self.context.jump_to_selected_difference(),
+                KeyCode::Char('p') | KeyCode::Char('P') if self.context.view_state == ViewState::Diff => self.context.save_patch(),
+                KeyCode::F(3) if event.modifiers.contains(KeyModifiers::SHIFT) => self.context.find_prev(),
+                KeyCode::F(3) => self.context.find_next(),
+                KeyCode::Char('f') | KeyCode::Char('F') => self.context.find(),
+
+                KeyCode::Char('j') | KeyCode::Char('J') if self.is_tree_focused() => self.context.next_tree_row(),
+                KeyCode::Char('k') | KeyCode::Char('K') if self.is_tree_focused() => self.context.prev_tree_row(),
+                KeyCode::Char('l') | KeyCode::Char('L') | KeyCode::Right if self.is_tree_focused() => self.context.expand_tree_row(),
+                KeyCode::Char('h') | KeyCode::Char('H') | KeyCode::Left if self.is_tree_focused() => self.context.collapse_tree_row(),
+                KeyCode::Enter if self.is_tree_focused() => self.context.toggle_tree_row(),
+                KeyCode::Char('t') | KeyCode::Char('T') if self.context.view_state == ViewState::Keys => self.context.toggle_tree(),
+
                 KeyCode::Char('j') | KeyCode::Char('J') => self.context.next_row(),
                 KeyCode::Char('k') | KeyCode::Char('K') => self.context.prev_row(),
                 KeyCode::Tab => self.context.swap_viewing_table(),
                 
                 KeyCode::Enter => self.context.select(),
+                KeyCode::Char('u') | KeyCode::Char('U') => self.context.undo(),
+                KeyCode::Char('r') | KeyCode::Char('R') if event.modifiers.contains(KeyModifiers::CONTROL) => self.context.redo(),
                 KeyCode::Char('n') | KeyCode::Char('N') => self.context.create(),
                 KeyCode::Char('r') | KeyCode::Char('R') => self.context.rename(),
                 KeyCode::Char('d') | KeyCode::Char('D') => self.context.delete(),
 
-                KeyCode::Char('t') | KeyCode::Char('T') => self.context.change_type(),
-                KeyCode::Char('v') | KeyCode::Char('V') => self.context.change_data(),
+                KeyCode::Char('t') | KeyCode::Char('T') if self.context.view_state == ViewState::Values => self.context.change_type(),
+                KeyCode::Char('v') | KeyCode::Char('V') if self.context.view_state == ViewState::Values => self.context.change_data(),
+
+                KeyCode::Char('g') | KeyCode::Char('G') => self.context.go_to_path(),
+                KeyCode::Char('i') | KeyCode::Char('I') => self.context.import_file(),
+                KeyCode::Char('e') | KeyCode::Char('E') if self.context.view_state == ViewState::Keys => self.context.export_key(),
+                KeyCode::Char('s') | KeyCode::Char('S') if self.context.view_state == ViewState::Keys => self.context.take_snapshot(),
+                KeyCode::Char('c') | KeyCode::Char('C') => self.context.compare_snapshot(),
+                KeyCode::Char('o') | KeyCode::Char('O') => self.context.restore_backup(),
 
                 _ => (),
             }
@@ -112,12 +303,33 @@ impl App {
         frame.render_widget(title_content, area);
     }
 
+    /// Rows the filter left, with the matched characters, or every row.
+    fn visible_rows(table: &ScrollableTableState, len: usize) -> Vec<(usize, Vec<usize>)> {
+        match table.visible.as_ref() {
+            Some(visible) => visible.iter().map(|row| (row.index, row.matched.clone())).collect(),
+            None => (0..len).map(|i| (i, Vec::new())).collect(),
+        }
+    }
+
+    fn highlight_matches(text: &str, matched: &[usize]) -> Line<'static> {
+        if matched.is_empty() {
+            return Line::from(text.to_owned());
+        }
+
+        let spans = text.chars().enumerate().map(|(i, c)| match matched.contains(&i) {
+            true => Span::from(c.to_string()).bold().underlined(),
+            false => Span::from(c.to_string()),
+        }).collect::<Vec<_>>();
+
+        Line::from(spans)
+    }
+
     fn render_subkey_table(&mut self, frame: &mut Frame, area: Rect) {
         let header = ["Key"];
 
         let subkeys = self.context.get_subkeys().clone();
-        let rows = subkeys.into_iter().map(|item| {
-            Row::new(vec![item])
+        let rows = Self::visible_rows(&self.context.key_table, subkeys.len()).into_iter().map(|(i, matched)| {
+            Row::new(vec![Self::highlight_matches(&subkeys[i], &matched)])
                 .height(ITEM_HEIGHT as u16)
         });
 
@@ -125,6 +337,24 @@ impl App {
         Self::render_table(frame, header, rows, &mut self.context.key_table, is_disabled, area);
     }
 
+    fn render_tree(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(tree) = self.context.tree.as_ref() else { return; };
+
+        let rows = tree.rows.iter().map(|row| {
+            let marker = match (row.expanded, row.has_children) {
+                (_, Some(false)) => "  ",
+                (true, _) => "▾ ",
+                (false, _) => "▸ ",
+            };
+
+            Row::new(vec![format!("{}{marker}{}", "  ".repeat(row.depth()), row.name())])
+                .height(ITEM_HEIGHT as u16)
+        }).collect::<Vec<_>>();
+
+        let is_disabled = self.context.view_state == ViewState::Keys;
+        Self::render_table(frame, ["Key"], rows, &mut self.context.tree_table, is_disabled, area);
+    }
+
     fn render_empty_values(&mut self, frame: &mut Frame, area: Rect) {
         let block = Block::bordered();
         let paragraph = Paragraph::new("No Values to Display")
@@ -144,11 +374,17 @@ impl App {
             }
         };
 
-        let rows = values.into_iter()
-            .map(|v| {
-                let name = v.name.clone();
-                let ty = registry::type_to_str(v.value.ty()).to_owned();
-                let value = registry::get_printable_value(&v.value);
+        if values.is_empty() {
+            self.render_empty_values(frame, area);
+            return;
+        }
+
+        let rows = Self::visible_rows(&self.context.value_table, values.len()).into_iter()
+            .map(|(i, matched)| {
+                let v = &values[i];
+                let name = Self::highlight_matches(&v.name, &matched);
+                let ty = Line::from(registry::type_to_str(v.value.ty()));
+                let value = Line::from(registry::get_printable_value(&v.value));
 
                 Row::new(
                     vec![name, ty, value]
@@ -156,11 +392,6 @@ impl App {
             }
         );
 
-        if rows.len() == 0 {
-            self.render_empty_values(frame, area);
-            return;
-        }
-
         let is_disabled = self.context.view_state == ViewState::Values;
         Self::render_table(frame, header, rows, &mut self.context.value_table, is_disabled, area);
     }
@@ -217,13 +448,289 @@ impl App {
         }
     }
 
+    fn render_preview(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(preview) = self.context.preview.as_mut() else { return; };
+
+        let rows = preview.changes.iter().map(|change| {
+            let style = match change.kind {
+                ChangeKind::AddKey | ChangeKind::AddValue => Style::default().green(),
+                ChangeKind::ModifyValue => Style::default().yellow(),
+                ChangeKind::DeleteKey | ChangeKind::DeleteValue => Style::default().red(),
+            };
+
+            Row::new(vec![
+                format!("{} {}", change.kind.symbol(), change.path),
+                change.value.clone().unwrap_or_default(),
+                change.detail.clone(),
+            ])
+            .style(style)
+            .height(ITEM_HEIGHT as u16)
+        }).collect::<Vec<_>>();
+
+        let title = format!("{} ({} changes)", preview.title, preview.changes.len());
+        let layout = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]);
+        let [title_area, table_area] = layout.areas(area);
+
+        frame.render_widget(Line::from(title).bold(), title_area);
+        Self::render_table(frame, ["Key", "Value", "Change"], rows, &mut preview.table, true, table_area);
+    }
+
+    fn render_search_results(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(search) = self.context.search.as_ref() else { return; };
+
+        let rows = search.hits.iter().map(|hit| {
+            let data = match hit.kind {
+                HitKind::Data => hit.text.clone(),
+                HitKind::Key | HitKind::ValueName => String::new(),
+            };
+
+            Row::new(vec![hit.path.join("\\"), hit.value.clone().unwrap_or_default(), data])
+                .height(ITEM_HEIGHT as u16)
+        }).collect::<Vec<_>>();
+
+        let hits = match search.hits.len() {
+            1 => "1 hit".to_owned(),
+            count => format!("{count} hits"),
+        };
+        let status = match search.done {
+            true => "",
+            false => ", searching...",
+        };
+        let title = format!("Results for \"{}\" ({hits}{status})", search.pattern);
+        let layout = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]);
+        let [title_area, table_area] = layout.areas(area);
+
+        frame.render_widget(Line::from(title).bold(), title_area);
+
+        let is_disabled = self.context.view_state == ViewState::Results;
+        Self::render_table(frame, ["Key", "Value", "Data"], rows, &mut self.context.search_table, is_disabled, table_area);
+    }
+
+    fn render_diff(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(diff) = self.context.diff.as_ref() else { return; };
+
+        let rows = diff.differences.iter().map(|difference| {
+            let style = match difference.kind {
+                ChangeKind::AddKey | ChangeKind::AddValue => Style::default().green(),
+                ChangeKind::ModifyValue => Style::default().yellow(),
+                ChangeKind::DeleteKey | ChangeKind::DeleteValue => Style::default().red(),
+            };
+            let value = difference.value.as_deref().map(registry::display_name).unwrap_or_default().to_owned();
+
+            Row::new(vec![format!("{} {}", difference.kind.symbol(), difference.key_path()), value, difference.detail()])
+                .style(style)
+                .height(ITEM_HEIGHT as u16)
+        }).collect::<Vec<_>>();
+
+        let title = format!("{} ({} changes)", diff.title, diff.differences.len());
+        let layout = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]);
+        let [title_area, table_area] = layout.areas(area);
+
+        frame.render_widget(Line::from(title).bold(), title_area);
+
+        let is_disabled = self.context.view_state == ViewState::Diff;
+        Self::render_table(frame, ["Key", "Value", "Change"], rows, &mut self.context.diff_table, is_disabled, table_area);
+    }
+
+    fn render_bookmarks(&mut self, frame: &mut Frame, area: Rect) {
+        let [_, area, _] = Layout::vertical([Constraint::Percentage(15), Constraint::Percentage(70), Constraint::Percentage(15)]).areas(area);
+        let [_, area, _] = Layout::horizontal([Constraint::Percentage(10), Constraint::Percentage(80), Constraint::Percentage(10)]).areas(area);
+
+        let title = match self.context.bookmarks.file() {
+            Some(file) => format!("Bookmarks ({})", file.display()),
+            None => "Bookmarks (not saved)".to_owned(),
+        };
+        frame.render_widget(Clear, area);
+
+        if self.context.bookmarks.items.is_empty() {
+            let text = Paragraph::new("No bookmarks yet, <A> bookmarks the selected key")
+                .centered()
+                .block(Block::bordered().title(title));
+            frame.render_widget(text, area);
+            return;
+        }
+
+        let rows = self.context.bookmarks.items.iter().map(|bookmark| {
+            Row::new(vec![bookmark.name.clone(), bookmark.path.clone()])
+                .height(ITEM_HEIGHT as u16)
+        }).collect::<Vec<_>>();
+
+        let is_disabled = self.context.view_state == ViewState::Bookmarks;
+        Self::render_table(frame, ["Name", "Path"], rows, &mut self.context.bookmark_table, is_disabled, area);
+
+        // the table draws its own border, the title goes on top of it
+        let title_area = Rect { x: area.x + 1, height: 1, width: area.width.saturating_sub(2), ..area };
+        frame.render_widget(Line::from(title).bold(), title_area);
+    }
+
+    fn render_hex_editor(&mut self, frame: &mut Frame, area: Rect) {
+        let InputType::HexEditor(ref mut editor) = self.context.input.ty else { return; };
+
+        let visible_rows = area.height.saturating_sub(2) as usize;
+        editor.scroll_to_cursor(visible_rows);
+
+        let data = editor.data();
+        let selection = editor.selection().unwrap_or_default();
+        let cursor_style = Style::default().black().on_white();
+        let inactive_cursor_style = Style::default().black().on_gray();
+        let selected_style = Style::default().black().on_light_cyan();
+
+        let byte_style = |offset: usize, pane: HexPane| match (selection.contains(&offset), offset == editor.cursor()) {
+            (_, true) if pane == editor.pane => cursor_style,
+            (_, true) => inactive_cursor_style,
+            (true, false) => selected_style,
+            (false, false) => Style::default(),
+        };
+
+        let lines = (editor.scroll..editor.rows().min(editor.scroll + visible_rows)).map(|row| {
+            let start = row * BYTES_PER_ROW;
+            let mut spans = vec![Span::from(format!("{start:08x}  ")).dark_gray()];
+
+            for offset in start..start + BYTES_PER_ROW {
+                let separator = match offset % 8 {
+                    7 => "  ",
+                    _ => " ",
+                };
+
+                match data.get(offset) {
+                    // the cursor highlights the nibble being edited
+                    Some(byte) if offset == editor.cursor() && editor.pane == HexPane::Hex => {
+                        let hex = format!("{byte:02x}");
+                        let (high, low) = hex.split_at(1);
+                        let (high_style, low_style) = match editor.low_nibble() {
+                            true => (selected_style, cursor_style),
+                            false => (cursor_style, selected_style),
+                        };
+
+                        spans.push(Span::styled(high.to_owned(), high_style));
+                        spans.push(Span::styled(low.to_owned(), low_style));
+                    }
+                    Some(byte) => spans.push(Span::styled(format!("{byte:02x}"), byte_style(offset, HexPane::Hex))),
+                    None if offset == data.len() => spans.push(Span::styled("__", byte_style(offset, HexPane::Hex))),
+                    None => spans.push(Span::from("  ")),
+                }
+                spans.push(Span::from(separator));
+            }
+
+            spans.push(Span::from(" "));
+            let end = (start + BYTES_PER_ROW).min(data.len());
+
+            match editor.encoding {
+                TextEncoding::Ascii => {
+                    for (offset, &byte) in (start..end).zip(&data[start..end]) {
+                        let c = match byte {
+                            byte if byte.is_ascii_graphic() || byte == b' ' => byte as char,
+                            _ => '.',
+                        };
+                        spans.push(Span::styled(c.to_string(), byte_style(offset, HexPane::Text)));
+                    }
+                }
+                TextEncoding::Utf16 => {
+                    for offset in (start..end).step_by(2) {
+                        let c = data.get(offset..offset + 2)
+                            .and_then(|pair| char::from_u32(u16::from_le_bytes([pair[0], pair[1]]).into()))
+                            .filter(|c| !c.is_control())
+                            .unwrap_or('.');
+                        let style = byte_style(offset, HexPane::Text).patch(byte_style(offset + 1, HexPane::Text));
+                        spans.push(Span::styled(format!("{c} "), style));
+                    }
+                }
+            }
+
+            if end == data.len() && data.len() < start + BYTES_PER_ROW {
+                spans.push(Span::styled("_", byte_style(data.len(), HexPane::Text)));
+            }
+
+            Line::from(spans)
+        }).collect::<Vec<_>>();
+
+        let encoding = match editor.encoding {
+            TextEncoding::Ascii => "ASCII",
+            TextEncoding::Utf16 => "UTF-16",
+        };
+        let title = format!("Hex Editor ({} bytes, {encoding})", data.len());
+
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+
+    fn render_list(&mut self, frame: &mut Frame, area: Rect) {
+        let InputType::List(ref mut list) = self.context.input.ty else { return; };
+
+        let block = Block::bordered().title(format!("Strings ({} entries)", list.items().len()));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let visible_rows = inner.height as usize;
+        list.scroll_to_selected(visible_rows);
+
+        let number_width = list.items().len().max(1).to_string().len() + 2;
+        let selected = list.selected();
+        let rows = list.items()
+            .iter()
+            .enumerate()
+            .skip(list.scroll)
+            .take(visible_rows)
+            .map(|(i, item)| (i, item.clone()))
+            .collect::<Vec<_>>();
+
+        for (row, (i, item)) in rows.into_iter().enumerate() {
+            let row_area = Rect { y: inner.y + row as u16, height: 1, ..inner };
+            let [number_area, item_area] = Layout::horizontal([Constraint::Length(number_width as u16), Constraint::Min(0)]).areas(row_area);
+
+            frame.render_widget(Line::from(format!("{:>width$} ", i + 1, width = number_width - 1)).dark_gray(), number_area);
+
+            match list.editing_mut() {
+                Some(textarea) if i == selected => {
+                    textarea.set_cursor_style(Style::default().on_white());
+                    textarea.set_cursor_line_style(Style::default().underlined());
+                    frame.render_widget(&*textarea, item_area);
+                }
+                _ if i == selected => frame.render_widget(Line::from(item).black().on_white(), item_area),
+                _ => frame.render_widget(Line::from(item), item_area),
+            }
+        }
+    }
+
     fn render_main_area(&mut self, frame: &mut Frame, area: Rect) {
         use Constraint::{Percentage, Min};
 
+        if self.context.preview.is_some() {
+            self.render_preview(frame, area);
+            return;
+        }
+        if self.context.view_state.is_input() && self.context.input.ty.is_hex_editor() {
+            self.render_hex_editor(frame, area);
+            return;
+        }
+        if self.context.view_state.is_input() && self.context.input.ty.is_list() {
+            self.render_list(frame, area);
+            return;
+        }
+
+        let area = match self.context.search {
+            Some(_) => {
+                let [area, results_area] = Layout::vertical([Min(0), Percentage(35)]).areas(area);
+                self.render_search_results(frame, results_area);
+                area
+            }
+            None => area,
+        };
+        let area = match self.context.diff {
+            Some(_) => {
+                let [area, diff_area] = Layout::vertical([Min(0), Percentage(35)]).areas(area);
+                self.render_diff(frame, diff_area);
+                area
+            }
+            None => area,
+        };
+
         let layout = Layout::horizontal([Percentage(40), Min(0)]);
         let [subkey_area, value_area] = layout.areas(area);
 
-        self.render_subkey_table(frame, subkey_area);
+        match self.context.tree {
+            Some(_) => self.render_tree(frame, subkey_area),
+            None => self.render_subkey_table(frame, subkey_area),
+        };
         self.render_value_table(frame, value_area);
     }
 
@@ -238,6 +745,32 @@ impl App {
                 " Rename ".into(),
                 " <D> ".black().on_light_cyan().bold(),
                 " Delete ".into(),
+                " <I> ".black().on_light_cyan().bold(),
+                " Import ".into(),
+                " <E> ".black().on_light_cyan().bold(),
+                " Export ".into(),
+                " <S> ".black().on_light_cyan().bold(),
+                " Snapshot ".into(),
+                " <C> ".black().on_light_cyan().bold(),
+                " Compare ".into(),
+                " <O> ".black().on_light_cyan().bold(),
+                " Restore Backup ".into(),
+                " <T> ".black().on_light_cyan().bold(),
+                " Tree ".into(),
+                " <G> ".black().on_light_cyan().bold(),
+                " Go To ".into(),
+                " <F> ".black().on_light_cyan().bold(),
+                " Find ".into(),
+                " </> ".black().on_light_cyan().bold(),
+                " Filter ".into(),
+                " <B> ".black().on_light_cyan().bold(),
+                " Bookmarks ".into(),
+                " <[/]> ".black().on_light_cyan().bold(),
+                " Back/Forward ".into(),
+                " <U> ".black().on_light_cyan().bold(),
+                " Undo ".into(),
+                " <Ctrl-R> ".black().on_light_cyan().bold(),
+                " Redo ".into(),
             ],
             ViewState::Values => vec![
                 " <N> ".black().on_light_cyan().bold(),
@@ -250,6 +783,46 @@ impl App {
                 " Change Data ".into(),
                 " <D> ".black().on_light_cyan().bold(),
                 " Delete ".into(),
+                " </> ".black().on_light_cyan().bold(),
+                " Filter ".into(),
+                " <U> ".black().on_light_cyan().bold(),
+                " Undo ".into(),
+                " <Ctrl-R> ".black().on_light_cyan().bold(),
+                " Redo ".into(),
+            ],
+            ViewState::Results => vec![
+                " <Enter> ".black().on_light_cyan().bold(),
+                " Jump ".into(),
+                " <F3> ".black().on_light_cyan().bold(),
+                " Find Next ".into(),
+                " <Shift-F3> ".black().on_light_cyan().bold(),
+                " Find Previous ".into(),
+                " <F> ".black().on_light_cyan().bold(),
+                " New Search ".into(),
+                " <Esc> ".black().on_light_cyan().bold(),
+                " Close ".into(),
+            ],
+            ViewState::Diff => vec![
+                " <Enter> ".black().on_light_cyan().bold(),
+                " Jump ".into(),
+                " <P> ".black().on_light_cyan().bold(),
+                " Save Patch ".into(),
+                " <C> ".black().on_light_cyan().bold(),
+                " Compare Again ".into(),
+                " <Esc> ".black().on_light_cyan().bold(),
+                " Close ".into(),
+            ],
+            ViewState::Bookmarks => vec![
+                " <Enter> ".black().on_light_cyan().bold(),
+                " Jump ".into(),
+                " <A> ".black().on_light_cyan().bold(),
+                " Bookmark Selected Key ".into(),
+                " <R> ".black().on_light_cyan().bold(),
+                " Rename ".into(),
+                " <D> ".black().on_light_cyan().bold(),
+                " Delete ".into(),
+                " <Esc> ".black().on_light_cyan().bold(),
+                " Close ".into(),
             ],
             _ => Vec::new(),
         }
@@ -317,6 +890,44 @@ impl App {
         frame.render_widget(label, area);
     }
 
+    fn render_hex_editor_status(&mut self, frame: &mut Frame, area: Rect) {
+        let InputType::HexEditor(ref editor) = self.context.input.ty else { unreachable!() };
+
+        let mode = match editor.insert {
+            true => "INS",
+            false => "OVR",
+        };
+
+        let line = Line::from(vec![
+            format!("{mode} offset {:#x} ", editor.cursor()).into(),
+            "(<Tab> Hex/Text, <Ins> Mode, <Ctrl-T> ASCII/UTF-16, <Ctrl-Z> Undo, <Ctrl-Y> Redo)".dark_gray(),
+        ]);
+
+        let label = Paragraph::new(line)
+            .block(Block::bordered());
+
+        frame.render_widget(label, area);
+    }
+
+    fn render_list_status(&mut self, frame: &mut Frame, area: Rect) {
+        let InputType::List(ref list) = self.context.input.ty else { unreachable!() };
+
+        let hint = match list.is_editing() {
+            true => "(<Enter> to keep the entry, <Esc> to discard the change)",
+            false => "(<A> Add, <E> Edit, <D> Delete, <Shift-J>/<Shift-K> Move, <Enter> Save)",
+        };
+
+        let line = Line::from(vec![
+            format!("{} entries ", list.items().len()).into(),
+            hint.dark_gray(),
+        ]);
+
+        let label = Paragraph::new(line)
+            .block(Block::bordered());
+
+        frame.render_widget(label, area);
+    }
+
     fn render_input(&mut self, frame: &mut Frame, area: Rect) {
         let label_padding = 2;
         let label_text = self.context.input.label.as_str();
@@ -340,15 +951,38 @@ impl App {
                 match self.context.input.ty {
                     InputType::TextArea => self.render_textarea(frame, input_area, style),
                     InputType::Choice(_) => self.render_choices(frame, input_area),
+                    InputType::HexEditor(_) => self.render_hex_editor_status(frame, input_area),
+                    InputType::List(_) => self.render_list_status(frame, input_area),
                 }
             }
             _ => self.render_textarea(frame, input_area, style),
         };
     }
 
+    fn render_filter(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(filter) = self.context.get_filter() else { return; };
+
+        let label_text = format!("Filter ({}):", filter.mode.name());
+        let layout = Layout::horizontal([Constraint::Length(label_text.len() as u16 + 2), Constraint::Min(0)]);
+        let [label_area, input_area] = layout.areas(area);
+
+        let mut spans = vec![Span::from(filter.query.clone())];
+        match self.context.filter_editing {
+            true => {
+                spans.push(" ".on_white());
+                spans.push(" (<Tab> Substring/Fuzzy, <Enter> Keep, <Esc> Clear)".dark_gray());
+            }
+            false => spans.push(" (</> Edit, <Esc> Clear)".dark_gray()),
+        };
+
+        frame.render_widget(Paragraph::new(label_text).block(Block::bordered()), label_area);
+        frame.render_widget(Paragraph::new(Line::from(spans)).block(Block::bordered()), input_area);
+    }
+
     fn render_footer(&mut self, frame: &mut Frame, area: Rect) {
         match self.context.view_state {
             ViewState::Message(_) => self.render_message(frame, area),
+            _ if self.context.get_filter().is_some() => self.render_filter(frame, area),
             _ => self.render_input(frame, area),
         };
     }
@@ -385,6 +1019,9 @@ impl App {
 
         self.render_title(frame, title_area);
         self.render_main_area(frame, main_area);
+        if self.context.is_bookmarks_open() {
+            self.render_bookmarks(frame, main_area);
+        }
         self.render_status(frame, status_area);
     }
 }
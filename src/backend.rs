@@ -0,0 +1,213 @@
+use std::{fmt, sync::Arc};
+
+use crate::registry::{self, Value};
+
+pub mod hive;
+pub mod memory;
+pub mod wine;
+#[cfg(all(windows, feature = "windows"))]
+pub mod windows;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    AlreadyExists,
+    AccessDenied,
+    InvalidInput,
+    InvalidData,
+    Unsupported,
+    Io,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn already_exists(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::AlreadyExists, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Unsupported, message)
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        let kind = match value.kind() {
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorKind::AccessDenied,
+            std::io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+            std::io::ErrorKind::InvalidData => ErrorKind::InvalidData,
+            _ => ErrorKind::Io,
+        };
+
+        Self::new(kind, value.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub fn split_path(path: &str) -> Vec<String> {
+    path.split('\\')
+        .filter(|component| !component.is_empty())
+        .map(|component| component.to_owned())
+        .collect()
+}
+
+/// Finds the root a full path like `HKLM\Software\Foo` belongs to, returning
+/// the root name and the remaining components. Offline hives are named after
+/// their file, so `HKEY_LOCAL_MACHINE\SOFTWARE\...` also matches a root
+/// called SOFTWARE.
+pub fn resolve_path(backend: &dyn RegistryBackend, path: &str) -> Result<(String, Vec<String>)> {
+    let mut components = split_path(path);
+    let roots = backend.roots();
+
+    let find_root = |name: &str| roots.iter().find(|root| root.eq_ignore_ascii_case(name)).cloned();
+
+    if let Some(first) = components.first() {
+        if let Some(root) = find_root(registry::expand_root_name(first)) {
+            components.remove(0);
+            return Ok((root, components));
+        }
+
+        if let Some(root) = components.get(1).and_then(|second| find_root(second)) {
+            components.drain(..2);
+            return Ok((root, components));
+        }
+    }
+
+    Err(Error::not_found(format!("No root key matches {path}")))
+}
+
+pub fn open_path(backend: &dyn RegistryBackend, path: &str) -> Result<Box<dyn RegistryKey>> {
+    let (root, components) = resolve_path(backend, path)?;
+    let key = backend.open_root(&root)?;
+
+    match components.is_empty() {
+        true => Ok(key),
+        false => key.open(&components.join("\\")),
+    }
+}
+
+/// Creates the key along with any missing parents.
+pub fn create_path(backend: &dyn RegistryBackend, path: &str) -> Result<()> {
+    let (root, components) = resolve_path(backend, path)?;
+
+    match components.is_empty() {
+        true => Ok(()),
+        false => backend.open_root(&root)?.create(&components.join("\\")),
+    }
+}
+
+pub fn delete_path(backend: &dyn RegistryBackend, path: &str) -> Result<()> {
+    let (root, components) = resolve_path(backend, path)?;
+
+    match components.is_empty() {
+        true => Err(Error::new(ErrorKind::InvalidInput, format!("Can't delete the root key {root}"))),
+        false => backend.open_root(&root)?.remove_tree(&components.join("\\")),
+    }
+}
+
+/// Makes the changes done by `f` one batch, see `RegistryBackend::begin_batch`.
+pub fn batch<T>(backend: &dyn RegistryBackend, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    backend.begin_batch();
+    let result = f();
+    let saved = backend.end_batch();
+
+    let result = result?;
+    saved.map(|_| result)
+}
+
+/// An open key in some registry store. Paths passed to `open` are relative
+/// to this key and use backslashes as separators.
+pub trait RegistryKey: Send + Sync {
+    fn open(&self, path: &str) -> Result<Box<dyn RegistryKey>>;
+    fn try_clone(&self) -> Result<Box<dyn RegistryKey>>;
+
+    fn subkeys(&self) -> Result<Vec<String>>;
+    fn create(&self, name: &str) -> Result<()>;
+    fn rename(&self, from: &str, to: &str) -> Result<()>;
+    fn remove_tree(&self, name: &str) -> Result<()>;
+
+    fn values(&self) -> Result<Vec<(String, Value)>>;
+    fn get_value(&self, name: &str) -> Result<Value>;
+    fn set_value(&self, name: &str, value: &Value) -> Result<()>;
+    fn remove_value(&self, name: &str) -> Result<()>;
+
+    /// When the key was last written as a FILETIME, if the store keeps track.
+    fn last_write(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// A registry store exposing a fixed set of root keys.
+pub trait RegistryBackend: Send + Sync {
+    /// Name shown at the top of the path, e.g. "Computer".
+    fn name(&self) -> &str;
+    fn roots(&self) -> Vec<String>;
+    fn open_root(&self, name: &str) -> Result<Box<dyn RegistryKey>>;
+
+    /// Tells the store apart from others of its kind across sessions, e.g. the
+    /// files it was opened from. `None` when changes don't outlive the session.
+    fn location(&self) -> Option<String> {
+        Some(self.name().to_owned())
+    }
+
+    /// Starts a batch of changes. Stores that rewrite a whole file for every
+    /// change hold off until the batch ends, batches can be nested.
+    fn begin_batch(&self) {}
+
+    /// Ends a batch, writing out what changed during it.
+    fn end_batch(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// What the user should know about the store as soon as it's opened,
+    /// e.g. that some of it can only be read.
+    fn warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(all(windows, feature = "windows"))]
+pub fn default_backend() -> Option<Arc<dyn RegistryBackend>> {
+    Some(Arc::new(windows::WindowsBackend::new()))
+}
+
+#[cfg(not(all(windows, feature = "windows")))]
+pub fn default_backend() -> Option<Arc<dyn RegistryBackend>> {
+    None
+}
@@ -0,0 +1,559 @@
+use std::{fs, io::Write, path::{Path, PathBuf}, sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}};
+
+use crate::registry::{Type, Value};
+
+use super::{split_path, Error, ErrorKind, RegistryBackend, RegistryKey, Result};
+
+mod edit;
+pub mod regf;
+
+use regf::{Hive, KeyNode, NO_OFFSET};
+
+struct HiveState {
+    hive: Hive,
+    /// Batches in progress, the file is only written once the last one ends.
+    batches: usize,
+    /// Set while the hive holds edits that aren't in the file yet.
+    unsaved: bool,
+}
+
+pub struct HiveFile {
+    path: PathBuf,
+    root_name: String,
+    state: RwLock<HiveState>,
+    /// Why the hive can't be written, if it can't: writing a hive that wasn't
+    /// cleanly closed throws away whatever is still waiting in its transaction
+    /// logs, and deleting keys relinks the security cells.
+    read_only: Option<String>,
+}
+
+impl HiveFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let hive = Hive::from_bytes(std::fs::read(&path)?)?;
+
+        let root_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| hive.key(hive.root_offset()).map(|root| root.name).unwrap_or_default());
+
+        let read_only = match hive.security_cells() {
+            _ if hive.base().is_dirty() => Some("has unapplied changes in its .LOG1/.LOG2 files".to_owned()),
+            Err(err) => Some(format!("has damaged security cells ({})", err.message())),
+            Ok(_) => None,
+        };
+
+        Ok(Self { path, root_name, state: RwLock::new(HiveState { hive, batches: 0, unsaved: false }), read_only })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn root_name(&self) -> &str {
+        &self.root_name
+    }
+
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only.is_some()
+    }
+
+    fn read_only_error(&self, reason: &str) -> Error {
+        Error::new(ErrorKind::AccessDenied, format!("{} {reason}, it's opened read-only", self.root_name))
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HiveState> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HiveState> {
+        self.state.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies an edit, rolling it back if it fails. Outside of a batch the
+    /// hive is written back to disk right away and a failed write rolls the
+    /// edit back too.
+    fn edit<T>(&self, f: impl FnOnce(&mut Hive) -> Result<T>) -> Result<T> {
+        if let Some(reason) = self.read_only.as_deref() {
+            return Err(self.read_only_error(reason));
+        }
+
+        let mut state = self.write();
+        state.hive.begin();
+
+        let result = f(&mut state.hive).and_then(|result| {
+            if state.batches == 0 {
+                self.save(&mut state.hive)?;
+            }
+            Ok(result)
+        });
+
+        match result.is_ok() {
+            true => {
+                state.hive.commit();
+                state.unsaved = state.batches > 0;
+            }
+            false => state.hive.rollback(),
+        };
+
+        result
+    }
+
+    fn begin_batch(&self) {
+        self.write().batches += 1;
+    }
+
+    /// Writes the hive once the outermost batch ends. A failed write keeps the
+    /// edits around, to be written along with the next one.
+    fn end_batch(&self) -> Result<()> {
+        let mut state = self.write();
+        state.batches = state.batches.saturating_sub(1);
+
+        if state.batches == 0 && state.unsaved {
+            self.save(&mut state.hive)?;
+            state.unsaved = false;
+        }
+
+        Ok(())
+    }
+
+    fn save(&self, hive: &mut Hive) -> Result<()> {
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".regcli-tmp");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(hive.serialize()?)?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn key_not_found(path: &[String]) -> Error {
+    Error::not_found(format!("The key {} doesn't exist", path.join("\\")))
+}
+
+pub struct HiveKey {
+    file: Arc<HiveFile>,
+    path: Vec<String>,
+}
+
+impl HiveKey {
+    fn new(file: Arc<HiveFile>, path: Vec<String>) -> Self {
+        Self { file, path }
+    }
+
+    fn with_node<T>(&self, f: impl FnOnce(&Hive, KeyNode) -> Result<T>) -> Result<T> {
+        let state = self.file.read();
+        let hive = &state.hive;
+        let node = hive.find_key(&self.path)?.ok_or_else(|| key_not_found(&self.path))?;
+
+        f(hive, node)
+    }
+
+    /// The self-relative security descriptor of the key, if it has one.
+    pub fn security_descriptor(&self) -> Result<Option<Vec<u8>>> {
+        self.with_node(|hive, node| match node.security {
+            NO_OFFSET => Ok(None),
+            offset => Ok(Some(hive.security(offset)?.descriptor)),
+        })
+    }
+}
+
+impl RegistryKey for HiveKey {
+    fn open(&self, path: &str) -> Result<Box<dyn RegistryKey>> {
+        let components = split_path(path);
+
+        // store the names as they are spelled in the hive
+        let canonical = self.with_node(|hive, mut node| {
+            let mut canonical = self.path.clone();
+
+            for name in &components {
+                node = hive.find_subkey(&node, name)?.ok_or_else(|| key_not_found(&components))?;
+                canonical.push(node.name.clone());
+            }
+
+            Ok(canonical)
+        })?;
+
+        Ok(Box::new(Self::new(self.file.clone(), canonical)))
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn RegistryKey>> {
+        Ok(Box::new(Self::new(self.file.clone(), self.path.clone())))
+    }
+
+    fn subkeys(&self) -> Result<Vec<String>> {
+        self.with_node(|hive, node| {
+            Ok(hive.subkeys(&node)?.into_iter().map(|subkey| subkey.name).collect())
+        })
+    }
+
+    fn create(&self, name: &str) -> Result<()> {
+        let components = split_path(name);
+        if components.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Name of a key can't be empty"));
+        }
+
+        self.file.edit(|hive| {
+            let mut node = hive.find_key(&self.path)?.ok_or_else(|| key_not_found(&self.path))?;
+
+            for component in &components {
+                node = hive.create_key(&node, component)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        if to.is_empty() || to.contains('\\') {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{to} isn't a valid key name")));
+        }
+
+        self.file.edit(|hive| {
+            let node = hive.find_key(&self.path)?.ok_or_else(|| key_not_found(&self.path))?;
+            hive.rename_key(&node, from, to)
+        })
+    }
+
+    fn remove_tree(&self, name: &str) -> Result<()> {
+        let mut components = split_path(name);
+        let Some(last) = components.pop() else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Can't remove the key itself"));
+        };
+
+        let mut parent_path = self.path.clone();
+        parent_path.extend(components);
+
+        self.file.edit(|hive| {
+            let parent = hive.find_key(&parent_path)?.ok_or_else(|| key_not_found(&parent_path))?;
+            hive.delete_key(&parent, &last)
+        })
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>> {
+        self.with_node(|hive, node| {
+            hive.values(&node)?
+                .into_iter()
+                .map(|value| Ok((value.name.clone(), Value::new(Type::from(value.ty), hive.value_data(&value)?))))
+                .collect()
+        })
+    }
+
+    fn get_value(&self, name: &str) -> Result<Value> {
+        self.with_node(|hive, node| {
+            let Some(value) = hive.find_value(&node, name)? else {
+                return Err(Error::not_found(format!("The value {name} doesn't exist")));
+            };
+
+            Ok(Value::new(Type::from(value.ty), hive.value_data(&value)?))
+        })
+    }
+
+    fn set_value(&self, name: &str, value: &Value) -> Result<()> {
+        self.file.edit(|hive| {
+            let node = hive.find_key(&self.path)?.ok_or_else(|| key_not_found(&self.path))?;
+            hive.set_value(node.offset, name, u32::from(value.ty()), value)
+        })
+    }
+
+    fn remove_value(&self, name: &str) -> Result<()> {
+        self.file.edit(|hive| {
+            let node = hive.find_key(&self.path)?.ok_or_else(|| key_not_found(&self.path))?;
+            hive.remove_value(node.offset, name)
+        })
+    }
+
+    fn last_write(&self) -> Result<Option<u64>> {
+        self.with_node(|_, node| Ok(Some(node.last_write)))
+    }
+}
+
+pub struct HiveBackend {
+    files: Vec<Arc<HiveFile>>,
+}
+
+impl HiveBackend {
+    pub fn open(paths: &[impl AsRef<Path>]) -> Result<Self> {
+        let mut files: Vec<Arc<HiveFile>> = Vec::new();
+
+        for path in paths {
+            let mut file = HiveFile::open(path)?;
+
+            // two hives with the same file name still need distinct roots
+            let base_name = file.root_name.clone();
+            let mut n = 1;
+            while files.iter().any(|f| f.root_name.eq_ignore_ascii_case(&file.root_name)) {
+                n += 1;
+                file.root_name = format!("{base_name} ({n})");
+            }
+
+            files.push(Arc::new(file));
+        }
+
+        Ok(Self { files })
+    }
+
+    pub fn files(&self) -> &[Arc<HiveFile>] {
+        &self.files
+    }
+}
+
+impl RegistryBackend for HiveBackend {
+    fn name(&self) -> &str {
+        "Hives"
+    }
+
+    fn roots(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.root_name.clone()).collect()
+    }
+
+    fn location(&self) -> Option<String> {
+        let mut paths = self.files.iter()
+            .map(|file| fs::canonicalize(&file.path).unwrap_or_else(|_| file.path.clone()).display().to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        Some(paths.join("\n"))
+    }
+
+    fn begin_batch(&self) {
+        self.files.iter().for_each(|file| file.begin_batch());
+    }
+
+    fn end_batch(&self) -> Result<()> {
+        self.files.iter().map(|file| file.end_batch()).fold(Ok(()), Result::and)
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.files.iter()
+            .filter_map(|file| file.read_only.as_deref().map(|reason| format!("{}.", file.read_only_error(reason))))
+            .collect()
+    }
+
+    fn open_root(&self, name: &str) -> Result<Box<dyn RegistryKey>> {
+        let Some(file) = self.files.iter().find(|file| file.root_name.eq_ignore_ascii_case(name)) else {
+            return Err(Error::not_found(format!("Unknown root key {name}")));
+        };
+
+        Ok(Box::new(HiveKey::new(file.clone(), Vec::new())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regf::{base_block_checksum, BASE_BLOCK_SIZE, HBIN_ALIGNMENT, KEY_COMP_NAME, KEY_HIVE_ENTRY};
+
+    use super::*;
+
+    const ROOT: u32 = 0x20;
+    const SECURITY: u32 = 0x80;
+    /// The rest of the first bin, one free cell.
+    const FREE: u32 = SECURITY + 0x30;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// A hive with nothing but a root key named ROOT and its security cell.
+    fn empty_hive(sequence: (u32, u32)) -> Vec<u8> {
+        let mut data = vec![0u8; BASE_BLOCK_SIZE + HBIN_ALIGNMENT];
+
+        put(&mut data, 0, b"regf");
+        put(&mut data, 0x04, &sequence.0.to_le_bytes());
+        put(&mut data, 0x08, &sequence.1.to_le_bytes());
+        put(&mut data, 0x14, &1u32.to_le_bytes());
+        put(&mut data, 0x18, &5u32.to_le_bytes());
+        put(&mut data, 0x20, &1u32.to_le_bytes());
+        put(&mut data, 0x24, &ROOT.to_le_bytes());
+        put(&mut data, 0x28, &(HBIN_ALIGNMENT as u32).to_le_bytes());
+        put(&mut data, 0x2C, &1u32.to_le_bytes());
+        let checksum = base_block_checksum(&data);
+        put(&mut data, 0x1FC, &checksum.to_le_bytes());
+
+        let bin = BASE_BLOCK_SIZE;
+        put(&mut data, bin, b"hbin");
+        put(&mut data, bin + 8, &(HBIN_ALIGNMENT as u32).to_le_bytes());
+
+        let root = bin + ROOT as usize;
+        put(&mut data, root, &(-0x60i32).to_le_bytes());
+        put(&mut data, root + 4, b"nk");
+        put(&mut data, root + 6, &(KEY_HIVE_ENTRY | KEY_COMP_NAME).to_le_bytes());
+        for field in [0x1C, 0x20, 0x28, 0x30] {
+            put(&mut data, root + 4 + field, &NO_OFFSET.to_le_bytes());
+        }
+        put(&mut data, root + 4 + 0x2C, &SECURITY.to_le_bytes());
+        put(&mut data, root + 4 + 0x48, &4u16.to_le_bytes());
+        put(&mut data, root + 4 + 0x4C, b"ROOT");
+
+        // owner and group only, as small as a descriptor gets
+        let descriptor = [1u8, 0, 0x04, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let security = bin + SECURITY as usize;
+        put(&mut data, security, &(-0x30i32).to_le_bytes());
+        put(&mut data, security + 4, b"sk");
+        put(&mut data, security + 4 + 0x04, &SECURITY.to_le_bytes());
+        put(&mut data, security + 4 + 0x08, &SECURITY.to_le_bytes());
+        put(&mut data, security + 4 + 0x0C, &1u32.to_le_bytes());
+        put(&mut data, security + 4 + 0x10, &(descriptor.len() as u32).to_le_bytes());
+        put(&mut data, security + 4 + 0x14, &descriptor);
+
+        let free = FREE as usize;
+        put(&mut data, bin + free, &((HBIN_ALIGNMENT - free) as i32).to_le_bytes());
+
+        data
+    }
+
+    fn temp_hive(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("regcli-{}-{name}.DAT", std::process::id()));
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn string(s: &str) -> Value {
+        Value::new(Type::String, crate::registry::encode_sz(s))
+    }
+
+    #[test]
+    fn failed_edits_are_rolled_back() {
+        let mut hive = Hive::from_bytes(empty_hive((1, 1))).unwrap();
+        let root = hive.key(ROOT).unwrap();
+        let before = hive.serialize().unwrap().to_vec();
+
+        hive.begin();
+        let key = hive.create_key(&root, "Key").unwrap();
+        hive.set_value(key.offset, "Big", 3, &vec![7u8; 3 * HBIN_ALIGNMENT]).unwrap();
+        hive.rollback();
+
+        assert_eq!(hive.data, before);
+        assert!(hive.find_key(&["Key".to_owned()]).unwrap().is_none());
+    }
+
+    #[test]
+    fn freed_cells_are_merged_and_reused() {
+        let mut hive = Hive::from_bytes(empty_hive((1, 1))).unwrap();
+
+        let first = hive.allocate(0x10).unwrap();
+        let second = hive.allocate(0x10).unwrap();
+        assert_eq!((first, second), (FREE, FREE + 0x18));
+        assert_eq!(hive.cell(first).unwrap(), [0; 0x14]);
+
+        hive.free(first).unwrap();
+        assert_eq!(hive.allocate(0x10).unwrap(), FREE);
+
+        // with both gone the bin is left with one free cell again
+        hive.free(first).unwrap();
+        hive.free(second).unwrap();
+        assert_eq!(hive.allocate(HBIN_ALIGNMENT - FREE as usize - 4).unwrap(), FREE);
+        assert_eq!(hive.base.hbins_size as usize, HBIN_ALIGNMENT);
+    }
+
+    #[test]
+    fn allocations_that_dont_fit_add_a_bin() {
+        let mut hive = Hive::from_bytes(empty_hive((1, 1))).unwrap();
+
+        let offset = hive.allocate(HBIN_ALIGNMENT).unwrap();
+        assert_eq!(offset as usize, HBIN_ALIGNMENT + 0x20);
+        assert_eq!(hive.base.hbins_size as usize, 3 * HBIN_ALIGNMENT);
+        assert_eq!(&hive.data[BASE_BLOCK_SIZE + HBIN_ALIGNMENT..][..4], b"hbin");
+
+        let reopened = Hive::from_bytes(hive.serialize().unwrap().to_vec()).unwrap();
+        assert_eq!(reopened.cell(offset).unwrap().len(), HBIN_ALIGNMENT + 4);
+    }
+
+    #[test]
+    fn serialize_bumps_the_sequence_and_checksum() {
+        let mut hive = Hive::from_bytes(empty_hive((7, 7))).unwrap();
+
+        let data = hive.serialize().unwrap().to_vec();
+        assert_eq!((regf::read_u32(&data, 0x04).unwrap(), regf::read_u32(&data, 0x08).unwrap()), (8, 8));
+        assert_eq!(regf::read_u32(&data, 0x1FC).unwrap(), base_block_checksum(&data));
+
+        let mut reopened = Hive::from_bytes(data).unwrap();
+        assert_eq!(reopened.base.primary_sequence, 8);
+        assert_eq!(regf::read_u32(reopened.serialize().unwrap(), 0x04).unwrap(), 9);
+
+        let mut dirty = Hive::from_bytes(empty_hive((5, 4))).unwrap();
+        assert!(dirty.serialize().is_err());
+    }
+
+    #[test]
+    fn batches_write_once_at_the_end() {
+        let path = temp_hive("batch", &empty_hive((1, 1)));
+        let backend = HiveBackend::open(&[&path]).unwrap();
+        let file = backend.files()[0].clone();
+        let root = backend.open_root(file.root_name()).unwrap();
+
+        crate::backend::batch(&backend, || {
+            root.create("Key")?;
+            root.open("Key")?.set_value("Name", &string("data"))?;
+
+            // nothing's written until the batch ends
+            assert!(Hive::from_bytes(fs::read(&path)?)?.find_key(&["Key".to_owned()])?.is_none());
+            Ok(())
+        }).unwrap();
+
+        let saved = HiveBackend::open(&[&path]).unwrap();
+        let key = saved.open_root(file.root_name()).unwrap().open("Key").unwrap();
+        assert_eq!(key.get_value("Name").unwrap(), string("data"));
+
+        root.remove_tree("Key").unwrap();
+        assert!(HiveBackend::open(&[&path]).unwrap().open_root(file.root_name()).unwrap().subkeys().unwrap().is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn dirty_hives_are_read_only() {
+        let path = temp_hive("dirty", &empty_hive((5, 4)));
+        let backend = HiveBackend::open(&[&path]).unwrap();
+
+        assert!(backend.files()[0].is_read_only());
+        assert_eq!(backend.warnings().len(), 1);
+
+        let err = backend.open_root(backend.files()[0].root_name()).unwrap().create("Key").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        assert_eq!(fs::read(&path).unwrap(), empty_hive((5, 4)));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn security_cells_are_shared_and_released() {
+        let mut hive = Hive::from_bytes(empty_hive((1, 1))).unwrap();
+        let root = hive.key(ROOT).unwrap();
+
+        let cells = hive.security_cells().unwrap();
+        assert_eq!(cells.len(), 1);
+        assert_eq!((cells[0].offset, cells[0].references), (SECURITY, 1));
+        assert_eq!(cells[0].descriptor[..4], [1, 0, 0x04, 0x80]);
+
+        let key = hive.create_key(&root, "Key").unwrap();
+        assert_eq!(key.security, SECURITY);
+        assert_eq!(hive.security(SECURITY).unwrap().references, 2);
+
+        let root = hive.key(ROOT).unwrap();
+        hive.delete_key(&root, "Key").unwrap();
+        assert_eq!(hive.security(SECURITY).unwrap().references, 1);
+    }
+
+    #[test]
+    fn broken_security_cells_make_the_hive_read_only() {
+        let mut data = empty_hive((1, 1));
+        // the only cell now points at the root key rather than itself
+        put(&mut data, BASE_BLOCK_SIZE + SECURITY as usize + 4 + 0x04, &ROOT.to_le_bytes());
+
+        let path = temp_hive("security", &data);
+        let backend = HiveBackend::open(&[&path]).unwrap();
+        let root = backend.open_root(backend.files()[0].root_name()).unwrap();
+
+        assert!(backend.files()[0].is_read_only());
+        assert!(backend.warnings()[0].contains("damaged security cells"));
+        assert!(root.subkeys().unwrap().is_empty());
+        assert_eq!(root.create("Key").err().unwrap().kind(), ErrorKind::AccessDenied);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,674 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use crate::backend::{memory::filetime_now, Error, Result};
+
+use super::regf::{
+    base_block_checksum, corrupt, name_hash, read_u32, CellMap, Hive, KeyNode, Rollback, ValueNode,
+    BASE_BLOCK_SIZE, BIG_DATA_SEGMENT_SIZE, DATA_INLINE, HBIN_ALIGNMENT, HBIN_HEADER_SIZE,
+    KEY_COMP_NAME, NO_OFFSET, VALUE_COMP_NAME,
+};
+
+const NK_LAST_WRITE: usize = 0x04;
+const NK_PARENT: usize = 0x10;
+const NK_SUBKEY_COUNT: usize = 0x14;
+const NK_SUBKEY_LIST: usize = 0x1C;
+const NK_VOLATILE_SUBKEY_LIST: usize = 0x20;
+const NK_VALUE_COUNT: usize = 0x24;
+const NK_VALUE_LIST: usize = 0x28;
+const NK_SECURITY: usize = 0x2C;
+const NK_CLASS: usize = 0x30;
+const NK_MAX_NAME: usize = 0x34;
+const NK_MAX_VALUE_NAME: usize = 0x3C;
+const NK_MAX_VALUE_DATA: usize = 0x40;
+const NK_NAME_LENGTH: usize = 0x48;
+const NK_NAME: usize = 0x4C;
+
+const VK_DATA_SIZE: usize = 0x04;
+const VK_DATA_OFFSET: usize = 0x08;
+const VK_TYPE: usize = 0x0C;
+const VK_FLAGS: usize = 0x10;
+const VK_NAME: usize = 0x14;
+
+const SK_FLINK: usize = 0x04;
+const SK_BLINK: usize = 0x08;
+const SK_REFERENCES: usize = 0x0C;
+
+/// Leaves bigger than this are split up under an ri list, like Windows does.
+const MAX_LEAF_ENTRIES: usize = 1012;
+
+const fn align(value: usize, to: usize) -> usize {
+    value.div_ceil(to) * to
+}
+
+/// Names that fit in Latin-1 are stored compressed, the rest as UTF-16.
+fn encode_name(name: &str) -> (Vec<u8>, bool) {
+    match name.chars().all(|c| (c as u32) < 0x100) {
+        true => (name.chars().map(|c| c as u8).collect(), true),
+        false => (name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect(), false),
+    }
+}
+
+fn compare_names(a: &str, b: &str) -> Ordering {
+    a.to_uppercase().encode_utf16().cmp(b.to_uppercase().encode_utf16())
+}
+
+fn wide_name_size(name: &str) -> u32 {
+    name.encode_utf16().count() as u32 * 2
+}
+
+impl Hive {
+    fn scan_cells(&self) -> Result<CellMap> {
+        let mut free = BTreeMap::new();
+        let mut bins = Vec::new();
+
+        let end = self.base.hbins_size as usize;
+        let mut bin_start = 0usize;
+
+        while bin_start < end {
+            let absolute = BASE_BLOCK_SIZE + bin_start;
+            if self.data.get(absolute..absolute + 4) != Some(b"hbin".as_slice()) {
+                return Err(corrupt(format!("missing hive bin at {bin_start:#x}")));
+            }
+
+            let bin_size = read_u32(&self.data, absolute + 8)? as usize;
+            if bin_size < HBIN_ALIGNMENT || bin_start + bin_size > end {
+                return Err(corrupt(format!("hive bin at {bin_start:#x} has an invalid size")));
+            }
+
+            let mut cell = bin_start + HBIN_HEADER_SIZE;
+            while cell < bin_start + bin_size {
+                let size = read_u32(&self.data, BASE_BLOCK_SIZE + cell)? as i32;
+                let length = size.unsigned_abs() as usize;
+
+                if length < 8 || cell + length > bin_start + bin_size {
+                    return Err(corrupt(format!("cell {cell:#x} has an invalid size")));
+                }
+                if size > 0 {
+                    free.insert(cell as u32, length as u32);
+                }
+
+                cell += length;
+            }
+
+            bins.push((bin_start as u32, (bin_start + bin_size) as u32));
+            bin_start += bin_size;
+        }
+
+        Ok(CellMap { free, bins })
+    }
+
+    fn cell_map(&mut self) -> Result<&mut CellMap> {
+        if self.cells.is_none() {
+            self.cells = Some(self.scan_cells()?);
+        }
+
+        Ok(self.cells.as_mut().unwrap())
+    }
+
+    /// Starts remembering what gets overwritten, until `commit` or `rollback`.
+    pub fn begin(&mut self) {
+        self.rollback = Some(Rollback { len: self.data.len(), hbins_size: self.base.hbins_size, writes: Vec::new() });
+    }
+
+    pub fn commit(&mut self) {
+        self.rollback = None;
+    }
+
+    /// Puts back everything written since `begin`.
+    pub fn rollback(&mut self) {
+        let Some(rollback) = self.rollback.take() else { return; };
+
+        for (start, bytes) in rollback.writes.into_iter().rev() {
+            self.data[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        self.data.truncate(rollback.len);
+        self.base.hbins_size = rollback.hbins_size;
+
+        // rescanned the next time it's needed
+        self.cells = None;
+    }
+
+    /// The bytes at an absolute offset, to be overwritten. Bins appended since
+    /// `begin` get cut off on rollback, so only what was there before is kept.
+    fn bytes_mut(&mut self, start: usize, len: usize) -> &mut [u8] {
+        if let Some(rollback) = self.rollback.as_mut()
+            && start < rollback.len
+        {
+            let end = (start + len).min(rollback.len);
+            rollback.writes.push((start, self.data[start..end].to_vec()));
+        }
+
+        &mut self.data[start..start + len]
+    }
+
+    fn write_raw(&mut self, offset: u32, bytes: &[u8]) {
+        let start = BASE_BLOCK_SIZE + offset as usize;
+        self.bytes_mut(start, bytes.len()).copy_from_slice(bytes);
+    }
+
+    fn set_cell_size(&mut self, offset: u32, size: i32) {
+        self.write_raw(offset, &size.to_le_bytes());
+    }
+
+    /// Writes into the data of an allocated cell.
+    fn write_cell(&mut self, offset: u32, position: usize, bytes: &[u8]) -> Result<()> {
+        let capacity = self.cell(offset)?.len();
+        if position + bytes.len() > capacity {
+            return Err(corrupt(format!("write past the end of cell {offset:#x}")));
+        }
+
+        self.write_raw(offset + 4 + position as u32, bytes);
+        Ok(())
+    }
+
+    fn write_cell_u32(&mut self, offset: u32, position: usize, value: u32) -> Result<()> {
+        self.write_cell(offset, position, &value.to_le_bytes())
+    }
+
+    fn read_cell_u32(&self, offset: u32, position: usize) -> Result<u32> {
+        read_u32(self.cell(offset)?, position)
+    }
+
+    fn append_bin(&mut self, needed: usize) -> Result<(u32, u32)> {
+        self.cell_map()?;
+
+        let bin_size = align(needed + HBIN_HEADER_SIZE, HBIN_ALIGNMENT);
+        let bin_start = self.base.hbins_size;
+
+        let mut header = vec![0u8; HBIN_HEADER_SIZE];
+        header[..4].copy_from_slice(b"hbin");
+        header[4..8].copy_from_slice(&bin_start.to_le_bytes());
+        header[8..12].copy_from_slice(&(bin_size as u32).to_le_bytes());
+
+        self.data.extend_from_slice(&header);
+        self.data.resize(self.data.len() + bin_size - HBIN_HEADER_SIZE, 0);
+        self.base.hbins_size += bin_size as u32;
+
+        let cell = bin_start + HBIN_HEADER_SIZE as u32;
+        let length = (bin_size - HBIN_HEADER_SIZE) as u32;
+        self.set_cell_size(cell, length as i32);
+
+        let map = self.cell_map()?;
+        map.bins.push((bin_start, bin_start + bin_size as u32));
+        map.free.insert(cell, length);
+
+        Ok((cell, length))
+    }
+
+    /// Allocates a zeroed cell able to hold `len` bytes, reusing free space first.
+    pub fn allocate(&mut self, len: usize) -> Result<u32> {
+        let needed = align(len + 4, 8);
+
+        let found = self.cell_map()?
+            .free
+            .iter()
+            .find(|&(_, &size)| size as usize >= needed)
+            .map(|(&offset, &size)| (offset, size));
+
+        let (offset, size) = match found {
+            Some(found) => found,
+            None => self.append_bin(needed)?,
+        };
+
+        let size = size as usize;
+        let rest = size - needed;
+
+        let map = self.cell_map()?;
+        map.free.remove(&offset);
+
+        let used = match rest >= 8 {
+            true => {
+                map.free.insert(offset + needed as u32, rest as u32);
+                self.set_cell_size(offset + needed as u32, rest as i32);
+                needed
+            }
+            false => size,
+        };
+
+        self.set_cell_size(offset, -(used as i32));
+        let start = BASE_BLOCK_SIZE + offset as usize + 4;
+        self.bytes_mut(start, used - 4).fill(0);
+
+        Ok(offset)
+    }
+
+    /// Releases a cell, merging it with free neighbours in the same bin.
+    pub fn free(&mut self, offset: u32) -> Result<()> {
+        let length = self.cell(offset)?.len() as u32 + 4;
+
+        let map = self.cell_map()?;
+        let Some(&(bin_start, bin_end)) = map.bins.iter().find(|(start, end)| (*start..*end).contains(&offset)) else {
+            return Err(corrupt(format!("cell {offset:#x} isn't inside a hive bin")));
+        };
+
+        let mut start = offset;
+        let mut length = length;
+
+        if offset + length < bin_end && let Some(next) = map.free.remove(&(offset + length)) {
+            length += next;
+        }
+
+        let previous = map.free.range(..offset).next_back().map(|(&o, &s)| (o, s));
+        if let Some((previous, previous_length)) = previous
+            && previous >= bin_start
+            && previous + previous_length == offset
+        {
+            map.free.remove(&previous);
+            start = previous;
+            length += previous_length;
+        }
+
+        map.free.insert(start, length);
+
+        let absolute = BASE_BLOCK_SIZE + start as usize;
+        self.bytes_mut(absolute + 4, length as usize - 4).fill(0);
+        self.set_cell_size(start, length as i32);
+
+        Ok(())
+    }
+
+    fn allocate_with(&mut self, bytes: &[u8]) -> Result<u32> {
+        let offset = self.allocate(bytes.len())?;
+        self.write_cell(offset, 0, bytes)?;
+
+        Ok(offset)
+    }
+
+    fn touch_key(&mut self, offset: u32) -> Result<()> {
+        self.write_cell(offset, NK_LAST_WRITE, &filetime_now().to_le_bytes())
+    }
+
+    fn write_leaf(&mut self, entries: &[(u32, String)]) -> Result<u32> {
+        let hashed = self.base.minor_version >= 5;
+
+        let mut leaf = Vec::with_capacity(4 + entries.len() * 8);
+        leaf.extend_from_slice(if hashed { b"lh" } else { b"lf" });
+        leaf.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+
+        for (offset, name) in entries {
+            leaf.extend_from_slice(&offset.to_le_bytes());
+
+            match hashed {
+                true => leaf.extend_from_slice(&name_hash(name).to_le_bytes()),
+                false => {
+                    let (raw, _) = encode_name(name);
+                    let mut hint = [0u8; 4];
+                    raw.iter().take(4).enumerate().for_each(|(i, &b)| hint[i] = b);
+                    leaf.extend_from_slice(&hint);
+                }
+            };
+        }
+
+        self.allocate_with(&leaf)
+    }
+
+    fn write_subkey_list(&mut self, entries: &[(u32, String)]) -> Result<u32> {
+        if entries.is_empty() {
+            return Ok(NO_OFFSET);
+        }
+        if entries.len() <= MAX_LEAF_ENTRIES {
+            return self.write_leaf(entries);
+        }
+
+        let leaves = entries
+            .chunks(MAX_LEAF_ENTRIES)
+            .map(|chunk| self.write_leaf(chunk))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut index = Vec::with_capacity(4 + leaves.len() * 4);
+        index.extend_from_slice(b"ri");
+        index.extend_from_slice(&(leaves.len() as u16).to_le_bytes());
+        leaves.iter().for_each(|leaf| index.extend_from_slice(&leaf.to_le_bytes()));
+
+        self.allocate_with(&index)
+    }
+
+    fn free_subkey_list(&mut self, offset: u32) -> Result<()> {
+        let cell = self.cell(offset)?;
+
+        if cell.get(..2) == Some(b"ri".as_slice()) {
+            let count = u16::from_le_bytes([cell[2], cell[3]]) as usize;
+            let leaves = (0..count).map(|i| read_u32(cell, 4 + i * 4)).collect::<Result<Vec<_>>>()?;
+
+            for leaf in leaves {
+                self.free(leaf)?;
+            }
+        }
+
+        self.free(offset)
+    }
+
+    /// Replaces the subkey list of a key, keeping it sorted the way Windows expects.
+    fn set_subkeys(&mut self, key_offset: u32, mut entries: Vec<(u32, String)>) -> Result<()> {
+        entries.sort_by(|(_, a), (_, b)| compare_names(a, b));
+
+        let key = self.key(key_offset)?;
+        if key.subkey_count > 0 && key.subkey_list != NO_OFFSET {
+            self.free_subkey_list(key.subkey_list)?;
+        }
+
+        let list = self.write_subkey_list(&entries)?;
+        self.write_cell_u32(key_offset, NK_SUBKEY_COUNT, entries.len() as u32)?;
+        self.write_cell_u32(key_offset, NK_SUBKEY_LIST, list)?;
+
+        let longest = entries.iter().map(|(_, name)| wide_name_size(name)).max().unwrap_or(0);
+        let max_name = self.read_cell_u32(key_offset, NK_MAX_NAME)?;
+        if longest > max_name & 0xFFFF {
+            self.write_cell_u32(key_offset, NK_MAX_NAME, (max_name & 0xFFFF_0000) | longest.min(0xFFFF))?;
+        }
+
+        self.touch_key(key_offset)
+    }
+
+    fn subkey_entries(&self, key: &KeyNode) -> Result<Vec<(u32, String)>> {
+        Ok(self.subkeys(key)?.into_iter().map(|subkey| (subkey.offset, subkey.name)).collect())
+    }
+
+    fn retain_security(&mut self, offset: u32) -> Result<()> {
+        if offset == NO_OFFSET {
+            return Ok(());
+        }
+
+        let security = self.security(offset)?;
+        self.write_cell_u32(offset, SK_REFERENCES, security.references + 1)
+    }
+
+    fn release_security(&mut self, offset: u32) -> Result<()> {
+        if offset == NO_OFFSET {
+            return Ok(());
+        }
+
+        let security = self.security(offset)?;
+        let references = security.references.saturating_sub(1);
+        if references > 0 {
+            return self.write_cell_u32(offset, SK_REFERENCES, references);
+        }
+
+        // unlink the descriptor from the list of security cells
+        if security.flink != offset {
+            self.write_cell_u32(security.blink, SK_FLINK, security.flink)?;
+            self.write_cell_u32(security.flink, SK_BLINK, security.blink)?;
+        }
+
+        self.free(offset)
+    }
+
+    pub fn create_key(&mut self, parent: &KeyNode, name: &str) -> Result<KeyNode> {
+        if let Some(existing) = self.find_subkey(parent, name)? {
+            return Ok(existing);
+        }
+
+        let (raw, compressed) = encode_name(name);
+        let mut node = vec![0u8; NK_NAME + raw.len()];
+
+        node[..2].copy_from_slice(b"nk");
+        node[2..4].copy_from_slice(&(if compressed { KEY_COMP_NAME } else { 0 }).to_le_bytes());
+        node[NK_LAST_WRITE..NK_LAST_WRITE + 8].copy_from_slice(&filetime_now().to_le_bytes());
+        node[NK_PARENT..NK_PARENT + 4].copy_from_slice(&parent.offset.to_le_bytes());
+        node[NK_SUBKEY_LIST..NK_SUBKEY_LIST + 4].copy_from_slice(&NO_OFFSET.to_le_bytes());
+        node[NK_VOLATILE_SUBKEY_LIST..NK_VOLATILE_SUBKEY_LIST + 4].copy_from_slice(&NO_OFFSET.to_le_bytes());
+        node[NK_VALUE_LIST..NK_VALUE_LIST + 4].copy_from_slice(&NO_OFFSET.to_le_bytes());
+        node[NK_SECURITY..NK_SECURITY + 4].copy_from_slice(&parent.security.to_le_bytes());
+        node[NK_CLASS..NK_CLASS + 4].copy_from_slice(&NO_OFFSET.to_le_bytes());
+        node[NK_NAME_LENGTH..NK_NAME_LENGTH + 2].copy_from_slice(&(raw.len() as u16).to_le_bytes());
+        node[NK_NAME..].copy_from_slice(&raw);
+
+        let offset = self.allocate_with(&node)?;
+        self.retain_security(parent.security)?;
+
+        let mut entries = self.subkey_entries(parent)?;
+        entries.push((offset, name.to_owned()));
+        self.set_subkeys(parent.offset, entries)?;
+
+        self.key(offset)
+    }
+
+    pub fn rename_key(&mut self, parent: &KeyNode, from: &str, to: &str) -> Result<()> {
+        let Some(key) = self.find_subkey(parent, from)? else {
+            return Err(Error::not_found(format!("The key {from} doesn't exist")));
+        };
+        if self.find_subkey(parent, to)?.is_some_and(|other| other.offset != key.offset) {
+            return Err(Error::already_exists(format!("The key {to} already exists")));
+        }
+
+        let (raw, compressed) = encode_name(to);
+        let mut node = self.cell(key.offset)?[..NK_NAME].to_vec();
+
+        let flags = match compressed {
+            true => key.flags | KEY_COMP_NAME,
+            false => key.flags & !KEY_COMP_NAME,
+        };
+        node[2..4].copy_from_slice(&flags.to_le_bytes());
+        node[NK_NAME_LENGTH..NK_NAME_LENGTH + 2].copy_from_slice(&(raw.len() as u16).to_le_bytes());
+        node.extend_from_slice(&raw);
+
+        // collect the siblings before the old node might get freed
+        let entries = self.subkey_entries(parent)?;
+
+        let offset = match self.cell(key.offset)?.len() >= node.len() {
+            true => {
+                let capacity = self.cell(key.offset)?.len();
+                node.resize(capacity, 0);
+                self.write_cell(key.offset, 0, &node)?;
+                key.offset
+            }
+            false => {
+                let offset = self.allocate_with(&node)?;
+                self.free(key.offset)?;
+
+                // children point back at their parent node
+                let moved = self.key(offset)?;
+                for child in self.subkey_offsets(&moved)? {
+                    self.write_cell_u32(child, NK_PARENT, offset)?;
+                }
+
+                offset
+            }
+        };
+        self.touch_key(offset)?;
+
+        let entries = entries
+            .into_iter()
+            .map(|(o, name)| if o == key.offset { (offset, to.to_owned()) } else { (o, name) })
+            .collect();
+
+        self.set_subkeys(parent.offset, entries)
+    }
+
+    fn free_value_data(&mut self, value: &ValueNode) -> Result<()> {
+        if value.is_inline() || value.is_empty() || value.data_offset == NO_OFFSET {
+            return Ok(());
+        }
+
+        if self.is_big_data(value) {
+            let list = self.read_cell_u32(value.data_offset, 0x04)?;
+            for segment in self.big_data_segments(value.data_offset)? {
+                self.free(segment)?;
+            }
+
+            self.free(list)?;
+        }
+
+        self.free(value.data_offset)
+    }
+
+    fn free_key_tree(&mut self, offset: u32) -> Result<()> {
+        let key = self.key(offset)?;
+
+        for subkey in self.subkey_offsets(&key)? {
+            self.free_key_tree(subkey)?;
+        }
+        if key.subkey_count > 0 && key.subkey_list != NO_OFFSET {
+            self.free_subkey_list(key.subkey_list)?;
+        }
+
+        for value in self.values(&key)? {
+            self.free_value_data(&value)?;
+            self.free(value.offset)?;
+        }
+        if key.value_count > 0 && key.value_list != NO_OFFSET {
+            self.free(key.value_list)?;
+        }
+
+        if key.class != NO_OFFSET && key.class_length > 0 {
+            self.free(key.class)?;
+        }
+
+        self.release_security(key.security)?;
+        self.free(offset)
+    }
+
+    pub fn delete_key(&mut self, parent: &KeyNode, name: &str) -> Result<()> {
+        let Some(key) = self.find_subkey(parent, name)? else {
+            return Err(Error::not_found(format!("The key {name} doesn't exist")));
+        };
+
+        let entries = self.subkey_entries(parent)?
+            .into_iter()
+            .filter(|(offset, _)| *offset != key.offset)
+            .collect();
+
+        self.free_key_tree(key.offset)?;
+        self.set_subkeys(parent.offset, entries)
+    }
+
+    /// Stores value data and returns the size and offset fields for its vk record.
+    fn store_value_data(&mut self, data: &[u8]) -> Result<(u32, u32)> {
+        if data.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..data.len()].copy_from_slice(data);
+
+            return Ok((data.len() as u32 | DATA_INLINE, u32::from_le_bytes(inline)));
+        }
+
+        if data.len() > BIG_DATA_SEGMENT_SIZE && self.uses_big_data() {
+            let segments = data
+                .chunks(BIG_DATA_SEGMENT_SIZE)
+                .map(|chunk| self.allocate_with(chunk))
+                .collect::<Result<Vec<_>>>()?;
+
+            let list: Vec<u8> = segments.iter().flat_map(|s| s.to_le_bytes()).collect();
+            let list = self.allocate_with(&list)?;
+
+            let mut record = Vec::with_capacity(8);
+            record.extend_from_slice(b"db");
+            record.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+            record.extend_from_slice(&list.to_le_bytes());
+
+            return Ok((data.len() as u32, self.allocate_with(&record)?));
+        }
+
+        Ok((data.len() as u32, self.allocate_with(data)?))
+    }
+
+    fn write_value_list(&mut self, key_offset: u32, offsets: &[u32]) -> Result<()> {
+        let key = self.key(key_offset)?;
+        let old = match key.value_count > 0 && key.value_list != NO_OFFSET {
+            true => Some(key.value_list),
+            false => None,
+        };
+
+        let list = match offsets.is_empty() {
+            true => NO_OFFSET,
+            false => {
+                let bytes: Vec<u8> = offsets.iter().flat_map(|o| o.to_le_bytes()).collect();
+                self.allocate_with(&bytes)?
+            }
+        };
+
+        if let Some(old) = old {
+            self.free(old)?;
+        }
+
+        self.write_cell_u32(key_offset, NK_VALUE_COUNT, offsets.len() as u32)?;
+        self.write_cell_u32(key_offset, NK_VALUE_LIST, list)
+    }
+
+    pub fn set_value(&mut self, key_offset: u32, name: &str, ty: u32, data: &[u8]) -> Result<()> {
+        let key = self.key(key_offset)?;
+        let existing = self.find_value(&key, name)?;
+
+        if let Some(existing) = existing.as_ref() {
+            self.free_value_data(existing)?;
+        }
+
+        let (data_size, data_offset) = self.store_value_data(data)?;
+
+        match existing {
+            Some(existing) => {
+                self.write_cell_u32(existing.offset, VK_DATA_SIZE, data_size)?;
+                self.write_cell_u32(existing.offset, VK_DATA_OFFSET, data_offset)?;
+                self.write_cell_u32(existing.offset, VK_TYPE, ty)?;
+            }
+            None => {
+                let (raw, compressed) = encode_name(name);
+                let mut record = vec![0u8; VK_NAME + raw.len()];
+
+                record[..2].copy_from_slice(b"vk");
+                record[2..4].copy_from_slice(&(raw.len() as u16).to_le_bytes());
+                record[VK_DATA_SIZE..VK_DATA_SIZE + 4].copy_from_slice(&data_size.to_le_bytes());
+                record[VK_DATA_OFFSET..VK_DATA_OFFSET + 4].copy_from_slice(&data_offset.to_le_bytes());
+                record[VK_TYPE..VK_TYPE + 4].copy_from_slice(&ty.to_le_bytes());
+                record[VK_FLAGS..VK_FLAGS + 2].copy_from_slice(&(if compressed { VALUE_COMP_NAME } else { 0 }).to_le_bytes());
+                record[VK_NAME..].copy_from_slice(&raw);
+
+                let offset = self.allocate_with(&record)?;
+
+                let mut offsets = self.value_offsets(&key)?;
+                offsets.push(offset);
+                self.write_value_list(key_offset, &offsets)?;
+
+                let max_name = self.read_cell_u32(key_offset, NK_MAX_VALUE_NAME)?;
+                self.write_cell_u32(key_offset, NK_MAX_VALUE_NAME, max_name.max(wide_name_size(name)))?;
+            }
+        };
+
+        let max_data = self.read_cell_u32(key_offset, NK_MAX_VALUE_DATA)?;
+        self.write_cell_u32(key_offset, NK_MAX_VALUE_DATA, max_data.max(data.len() as u32))?;
+
+        self.touch_key(key_offset)
+    }
+
+    pub fn remove_value(&mut self, key_offset: u32, name: &str) -> Result<()> {
+        let key = self.key(key_offset)?;
+        let Some(value) = self.find_value(&key, name)? else {
+            return Err(Error::not_found(format!("The value {name} doesn't exist")));
+        };
+
+        self.free_value_data(&value)?;
+        self.free(value.offset)?;
+
+        let offsets: Vec<u32> = self.value_offsets(&key)?
+            .into_iter()
+            .filter(|offset| *offset != value.offset)
+            .collect();
+        self.write_value_list(key_offset, &offsets)?;
+
+        self.touch_key(key_offset)
+    }
+
+    /// Bumps the sequence numbers and checksum, returning the bytes of a consistent hive file.
+    /// Only clean hives can be written, a dirty one would lose what's still in its logs.
+    pub fn serialize(&mut self) -> Result<&[u8]> {
+        if self.base.is_dirty() {
+            return Err(Error::unsupported("A hive with pending transaction log data can't be written"));
+        }
+
+        let sequence = self.base.primary_sequence.wrapping_add(1);
+        let now = filetime_now();
+
+        self.base.primary_sequence = sequence;
+        self.base.secondary_sequence = sequence;
+        self.base.last_write = now;
+
+        self.data[0x04..0x08].copy_from_slice(&sequence.to_le_bytes());
+        self.data[0x08..0x0C].copy_from_slice(&sequence.to_le_bytes());
+        self.data[0x0C..0x14].copy_from_slice(&now.to_le_bytes());
+        self.data[0x28..0x2C].copy_from_slice(&self.base.hbins_size.to_le_bytes());
+
+        let checksum = base_block_checksum(&self.data);
+        self.data[0x1FC..0x200].copy_from_slice(&checksum.to_le_bytes());
+
+        Ok(&self.data)
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::backend::{Error, ErrorKind, Result};
+
+pub const BASE_BLOCK_SIZE: usize = 4096;
+pub const HBIN_ALIGNMENT: usize = 4096;
+pub const HBIN_HEADER_SIZE: usize = 32;
+pub const NO_OFFSET: u32 = 0xFFFF_FFFF;
+
+/// Largest chunk of value data stored in a single cell, bigger data goes through a db record.
+pub const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+
+pub const KEY_HIVE_ENTRY: u16 = 0x0004;
+pub const KEY_COMP_NAME: u16 = 0x0020;
+pub const VALUE_COMP_NAME: u16 = 0x0001;
+pub const DATA_INLINE: u32 = 0x8000_0000;
+
+const MAX_LIST_DEPTH: usize = 8;
+
+/// Header of a self-relative security descriptor, anything shorter is broken.
+const SECURITY_DESCRIPTOR_SIZE: usize = 20;
+
+pub fn corrupt(message: impl AsRef<str>) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Corrupted hive: {}", message.as_ref()))
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| corrupt(format!("read past the end of a record at {offset:#x}")))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| corrupt(format!("read past the end of a record at {offset:#x}")))
+}
+
+pub fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+/// Checksum of the base block: XOR of its first 127 dwords with 0 and -1 remapped.
+pub fn base_block_checksum(data: &[u8]) -> u32 {
+    let sum = data[..508]
+        .chunks_exact(4)
+        .fold(0u32, |acc, b| acc ^ u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    match sum {
+        0 => 1,
+        0xFFFF_FFFF => 0xFFFF_FFFE,
+        sum => sum,
+    }
+}
+
+/// Hash used by lh subkey lists.
+pub fn name_hash(name: &str) -> u32 {
+    name.to_uppercase()
+        .encode_utf16()
+        .fold(0u32, |hash, c| hash.wrapping_mul(37).wrapping_add(c as u32))
+}
+
+pub fn decode_name(raw: &[u8], compressed: bool) -> String {
+    match compressed {
+        true => raw.iter().map(|&b| b as char).collect(),
+        false => {
+            let wide: Vec<u16> = raw.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BaseBlock {
+    pub primary_sequence: u32,
+    pub secondary_sequence: u32,
+    pub last_write: u64,
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub root_offset: u32,
+    pub hbins_size: u32,
+}
+
+impl BaseBlock {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < BASE_BLOCK_SIZE || &data[..4] != b"regf" {
+            return Err(corrupt("missing regf signature"));
+        }
+
+        let base = Self {
+            primary_sequence: read_u32(data, 0x04)?,
+            secondary_sequence: read_u32(data, 0x08)?,
+            last_write: read_u64(data, 0x0C)?,
+            major_version: read_u32(data, 0x14)?,
+            minor_version: read_u32(data, 0x18)?,
+            root_offset: read_u32(data, 0x24)?,
+            hbins_size: read_u32(data, 0x28)?,
+        };
+
+        if base.major_version != 1 {
+            return Err(Error::unsupported(format!("Unsupported hive version {}.{}", base.major_version, base.minor_version)));
+        }
+        if read_u32(data, 0x1C)? != 0 {
+            return Err(Error::unsupported("Transaction log files can't be opened as hives"));
+        }
+        if read_u32(data, 0x1FC)? != base_block_checksum(data) {
+            return Err(corrupt("base block checksum mismatch"));
+        }
+
+        Ok(base)
+    }
+
+    pub const fn is_dirty(&self) -> bool {
+        self.primary_sequence != self.secondary_sequence
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyNode {
+    pub offset: u32,
+    pub flags: u16,
+    pub last_write: u64,
+    pub parent: u32,
+    pub subkey_count: u32,
+    pub subkey_list: u32,
+    pub value_count: u32,
+    pub value_list: u32,
+    pub security: u32,
+    pub class: u32,
+    pub class_length: u16,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValueNode {
+    pub offset: u32,
+    pub name: String,
+    pub data_size: u32,
+    pub data_offset: u32,
+    pub ty: u32,
+}
+
+impl ValueNode {
+    pub const fn is_inline(&self) -> bool {
+        self.data_size & DATA_INLINE != 0
+    }
+
+    pub const fn len(&self) -> usize {
+        (self.data_size & !DATA_INLINE) as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A security cell, shared by every key with the same security descriptor.
+/// They're linked into a ring through `flink` and `blink`.
+#[derive(Debug, Clone)]
+pub struct SecurityNode {
+    pub offset: u32,
+    pub flink: u32,
+    pub blink: u32,
+    pub references: u32,
+    /// A self-relative SECURITY_DESCRIPTOR.
+    pub descriptor: Vec<u8>,
+}
+
+/// Free cells and hive bin bounds, built the first time the hive is edited.
+#[derive(Debug, Clone)]
+pub(super) struct CellMap {
+    pub free: BTreeMap<u32, u32>,
+    pub bins: Vec<(u32, u32)>,
+}
+
+/// What an edit overwrote, so a failed edit can be taken back without
+/// keeping a copy of the whole hive around.
+#[derive(Debug, Clone)]
+pub(super) struct Rollback {
+    pub len: usize,
+    pub hbins_size: u32,
+    /// Absolute offsets and the bytes they held, in the order they were written.
+    pub writes: Vec<(usize, Vec<u8>)>,
+}
+
+#[derive(Clone)]
+pub struct Hive {
+    pub(super) data: Vec<u8>,
+    pub(super) base: BaseBlock,
+    pub(super) cells: Option<CellMap>,
+    pub(super) rollback: Option<Rollback>,
+}
+
+impl Hive {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let base = BaseBlock::parse(&data)?;
+
+        let end = BASE_BLOCK_SIZE + base.hbins_size as usize;
+        if end > data.len() || !(base.hbins_size as usize).is_multiple_of(HBIN_ALIGNMENT) {
+            return Err(corrupt("hive bins size doesn't match the file"));
+        }
+        if data.get(BASE_BLOCK_SIZE..BASE_BLOCK_SIZE + 4) != Some(b"hbin".as_slice()) {
+            return Err(corrupt("missing first hive bin"));
+        }
+
+        let mut hive = Self { data, base, cells: None, rollback: None };
+        hive.data.truncate(end);
+
+        hive.key(hive.base.root_offset)?;
+        Ok(hive)
+    }
+
+    pub const fn base(&self) -> &BaseBlock {
+        &self.base
+    }
+
+    pub const fn root_offset(&self) -> u32 {
+        self.base.root_offset
+    }
+
+    pub const fn uses_big_data(&self) -> bool {
+        self.base.minor_version >= 4
+    }
+
+    /// Returns the data of an allocated cell, without its size field.
+    pub fn cell(&self, offset: u32) -> Result<&[u8]> {
+        let start = BASE_BLOCK_SIZE + offset as usize;
+        let size = read_u32(&self.data, start).map_err(|_| corrupt(format!("cell {offset:#x} is out of bounds")))? as i32;
+
+        if size >= 0 {
+            return Err(corrupt(format!("cell {offset:#x} isn't allocated")));
+        }
+
+        let end = start + size.unsigned_abs() as usize;
+        if size.unsigned_abs() < 4 || end > self.data.len() {
+            return Err(corrupt(format!("cell {offset:#x} has an invalid size")));
+        }
+
+        Ok(&self.data[start + 4..end])
+    }
+
+    pub fn key(&self, offset: u32) -> Result<KeyNode> {
+        let cell = self.cell(offset)?;
+        if cell.get(..2) != Some(b"nk".as_slice()) {
+            return Err(corrupt(format!("cell {offset:#x} isn't a key node")));
+        }
+
+        let flags = read_u16(cell, 0x02)?;
+        let name_length = read_u16(cell, 0x48)? as usize;
+        let raw_name = cell.get(0x4C..0x4C + name_length).ok_or_else(|| corrupt(format!("key node {offset:#x} name is truncated")))?;
+
+        Ok(KeyNode {
+            offset,
+            flags,
+            last_write: read_u64(cell, 0x04)?,
+            parent: read_u32(cell, 0x10)?,
+            subkey_count: read_u32(cell, 0x14)?,
+            subkey_list: read_u32(cell, 0x1C)?,
+            value_count: read_u32(cell, 0x24)?,
+            value_list: read_u32(cell, 0x28)?,
+            security: read_u32(cell, 0x2C)?,
+            class: read_u32(cell, 0x30)?,
+            class_length: read_u16(cell, 0x4A)?,
+            name: decode_name(raw_name, flags & KEY_COMP_NAME != 0),
+        })
+    }
+
+    pub fn security(&self, offset: u32) -> Result<SecurityNode> {
+        let cell = self.cell(offset)?;
+        if cell.get(..2) != Some(b"sk".as_slice()) {
+            return Err(corrupt(format!("cell {offset:#x} isn't a security cell")));
+        }
+
+        let size = read_u32(cell, 0x10)? as usize;
+        let descriptor = cell.get(0x14..0x14 + size).ok_or_else(|| corrupt(format!("security cell {offset:#x} descriptor is truncated")))?;
+
+        // revision 1 is the only one there is
+        if descriptor.len() < SECURITY_DESCRIPTOR_SIZE || descriptor[0] != 1 {
+            return Err(corrupt(format!("security cell {offset:#x} has an invalid descriptor")));
+        }
+
+        Ok(SecurityNode {
+            offset,
+            flink: read_u32(cell, 0x04)?,
+            blink: read_u32(cell, 0x08)?,
+            references: read_u32(cell, 0x0C)?,
+            descriptor: descriptor.to_vec(),
+        })
+    }
+
+    /// Every security cell, following the ring from the one of the root key
+    /// and checking that each link points back.
+    pub fn security_cells(&self) -> Result<Vec<SecurityNode>> {
+        let root = self.key(self.root_offset())?;
+        if root.security == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+
+        let mut cells = vec![self.security(root.security)?];
+        let mut seen = BTreeSet::from([root.security]);
+        loop {
+            let current = cells.last().unwrap();
+            if current.flink == root.security {
+                break;
+            }
+
+            let next = self.security(current.flink)?;
+            if next.blink != current.offset {
+                return Err(corrupt(format!("security cell {:#x} doesn't link back to {:#x}", next.offset, current.offset)));
+            }
+            // a ring that doesn't close on the first cell loops forever otherwise
+            if !seen.insert(next.offset) {
+                return Err(corrupt(format!("the security cells loop back to {:#x}", next.offset)));
+            }
+
+            cells.push(next);
+        }
+
+        if cells[0].blink != cells.last().unwrap().offset {
+            return Err(corrupt(format!("security cell {:#x} doesn't link back to {:#x}", cells[0].offset, cells.last().unwrap().offset)));
+        }
+
+        Ok(cells)
+    }
+
+    fn collect_list(&self, offset: u32, depth: usize, out: &mut Vec<u32>) -> Result<()> {
+        if depth > MAX_LIST_DEPTH {
+            return Err(corrupt(format!("subkey list {offset:#x} is nested too deep")));
+        }
+
+        let cell = self.cell(offset)?;
+        let count = read_u16(cell, 0x02)? as usize;
+
+        match cell.get(..2) {
+            Some(b"li") => {
+                for i in 0..count {
+                    out.push(read_u32(cell, 4 + i * 4)?);
+                }
+            }
+            Some(b"lf") | Some(b"lh") => {
+                for i in 0..count {
+                    out.push(read_u32(cell, 4 + i * 8)?);
+                }
+            }
+            Some(b"ri") => {
+                for i in 0..count {
+                    self.collect_list(read_u32(cell, 4 + i * 4)?, depth + 1, out)?;
+                }
+            }
+            _ => return Err(corrupt(format!("cell {offset:#x} isn't a subkey list"))),
+        };
+
+        Ok(())
+    }
+
+    pub fn subkey_offsets(&self, key: &KeyNode) -> Result<Vec<u32>> {
+        let mut offsets = Vec::with_capacity(key.subkey_count as usize);
+        if key.subkey_count == 0 || key.subkey_list == NO_OFFSET {
+            return Ok(offsets);
+        }
+
+        self.collect_list(key.subkey_list, 0, &mut offsets)?;
+        Ok(offsets)
+    }
+
+    pub fn subkeys(&self, key: &KeyNode) -> Result<Vec<KeyNode>> {
+        self.subkey_offsets(key)?
+            .into_iter()
+            .map(|offset| self.key(offset))
+            .collect()
+    }
+
+    pub fn find_subkey(&self, key: &KeyNode, name: &str) -> Result<Option<KeyNode>> {
+        let upper = name.to_uppercase();
+
+        for offset in self.subkey_offsets(key)? {
+            let subkey = self.key(offset)?;
+            if subkey.name.to_uppercase() == upper {
+                return Ok(Some(subkey));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn find_key(&self, path: &[String]) -> Result<Option<KeyNode>> {
+        let mut key = self.key(self.root_offset())?;
+
+        for name in path {
+            key = match self.find_subkey(&key, name)? {
+                Some(subkey) => subkey,
+                None => return Ok(None),
+            };
+        }
+
+        Ok(Some(key))
+    }
+
+    pub fn value_offsets(&self, key: &KeyNode) -> Result<Vec<u32>> {
+        if key.value_count == 0 || key.value_list == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+
+        let cell = self.cell(key.value_list)?;
+        (0..key.value_count as usize)
+            .map(|i| read_u32(cell, i * 4))
+            .collect()
+    }
+
+    pub fn value(&self, offset: u32) -> Result<ValueNode> {
+        let cell = self.cell(offset)?;
+        if cell.get(..2) != Some(b"vk".as_slice()) {
+            return Err(corrupt(format!("cell {offset:#x} isn't a value node")));
+        }
+
+        let name_length = read_u16(cell, 0x02)? as usize;
+        let flags = read_u16(cell, 0x10)?;
+        let raw_name = cell.get(0x14..0x14 + name_length).ok_or_else(|| corrupt(format!("value node {offset:#x} name is truncated")))?;
+
+        Ok(ValueNode {
+            offset,
+            name: decode_name(raw_name, flags & VALUE_COMP_NAME != 0),
+            data_size: read_u32(cell, 0x04)?,
+            data_offset: read_u32(cell, 0x08)?,
+            ty: read_u32(cell, 0x0C)?,
+        })
+    }
+
+    pub fn values(&self, key: &KeyNode) -> Result<Vec<ValueNode>> {
+        self.value_offsets(key)?
+            .into_iter()
+            .map(|offset| self.value(offset))
+            .collect()
+    }
+
+    pub fn find_value(&self, key: &KeyNode, name: &str) -> Result<Option<ValueNode>> {
+        let upper = name.to_uppercase();
+
+        for offset in self.value_offsets(key)? {
+            let value = self.value(offset)?;
+            if value.name.to_uppercase() == upper {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn is_big_data(&self, value: &ValueNode) -> bool {
+        !value.is_inline() && value.len() > BIG_DATA_SEGMENT_SIZE && self.uses_big_data()
+    }
+
+    /// Returns the offsets of the segments of a db record.
+    pub fn big_data_segments(&self, offset: u32) -> Result<Vec<u32>> {
+        let cell = self.cell(offset)?;
+        if cell.get(..2) != Some(b"db".as_slice()) {
+            return Err(corrupt(format!("cell {offset:#x} isn't a big data record")));
+        }
+
+        let count = read_u16(cell, 0x02)? as usize;
+        let list = self.cell(read_u32(cell, 0x04)?)?;
+
+        (0..count).map(|i| read_u32(list, i * 4)).collect()
+    }
+
+    pub fn value_data(&self, value: &ValueNode) -> Result<Vec<u8>> {
+        let len = value.len();
+
+        if value.is_inline() {
+            if len > 4 {
+                return Err(corrupt(format!("value node {:#x} has oversized inline data", value.offset)));
+            }
+            return Ok(value.data_offset.to_le_bytes()[..len].to_vec());
+        }
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        if self.is_big_data(value) {
+            let mut data = Vec::with_capacity(len);
+
+            for segment in self.big_data_segments(value.data_offset)? {
+                let remaining = len - data.len();
+                let cell = self.cell(segment)?;
+                data.extend_from_slice(&cell[..cell.len().min(remaining).min(BIG_DATA_SEGMENT_SIZE)]);
+            }
+
+            if data.len() != len {
+                return Err(corrupt(format!("value node {:#x} big data is truncated", value.offset)));
+            }
+            return Ok(data);
+        }
+
+        let cell = self.cell(value.data_offset)?;
+        cell.get(..len)
+            .map(|data| data.to_vec())
+            .ok_or_else(|| corrupt(format!("value node {:#x} data is truncated", value.offset)))
+    }
+}
@@ -0,0 +1,496 @@
+use std::{path::Path, sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::registry::{self, Type, Value};
+
+use super::{split_path, Error, ErrorKind, RegistryBackend, RegistryKey, Result};
+
+/// Number of 100ns intervals between 1601-01-01 and 1970-01-01.
+pub const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+pub fn filetime_now() -> u64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    FILETIME_UNIX_EPOCH + (since_epoch.as_nanos() / 100) as u64
+}
+
+pub fn names_equal(a: &str, b: &str) -> bool {
+    a.to_uppercase() == b.to_uppercase()
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryNode {
+    pub name: String,
+    pub subkeys: Vec<MemoryNode>,
+    pub values: Vec<(String, Value)>,
+    pub last_write: u64,
+    pub class: Option<String>,
+    pub link: bool,
+    /// `#` lines of a Wine registry file that aren't understood, written back as they were.
+    pub annotations: Vec<String>,
+}
+
+impl MemoryNode {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), subkeys: Vec::new(), values: Vec::new(), last_write: filetime_now(), class: None, link: false, annotations: Vec::new() }
+    }
+
+    pub fn touch(&mut self) {
+        self.last_write = filetime_now();
+    }
+
+    /// Subkeys are kept sorted by `insert_subkey`, so lookups can use a binary search.
+    fn subkey_index(&self, name: &str) -> std::result::Result<usize, usize> {
+        let upper = name.to_uppercase();
+
+        self.subkeys.binary_search_by(|s| s.name.to_uppercase().cmp(&upper))
+    }
+
+    pub fn subkey(&self, name: &str) -> Option<&MemoryNode> {
+        self.subkey_index(name).ok().map(|index| &self.subkeys[index])
+    }
+
+    pub fn subkey_mut(&mut self, name: &str) -> Option<&mut MemoryNode> {
+        self.subkey_index(name).ok().map(|index| &mut self.subkeys[index])
+    }
+
+    pub fn find(&self, path: &[String]) -> Option<&MemoryNode> {
+        path.iter().try_fold(self, |node, name| node.subkey(name))
+    }
+
+    pub fn find_mut(&mut self, path: &[String]) -> Option<&mut MemoryNode> {
+        path.iter().try_fold(self, |node, name| node.subkey_mut(name))
+    }
+
+    /// Inserts a subkey keeping the list ordered the way Windows enumerates keys.
+    pub fn insert_subkey(&mut self, node: MemoryNode) -> &mut MemoryNode {
+        let index = self.subkey_index(&node.name).unwrap_or_else(|i| i);
+
+        self.subkeys.insert(index, node);
+        &mut self.subkeys[index]
+    }
+
+    pub fn remove_subkey(&mut self, name: &str) -> Option<MemoryNode> {
+        let index = self.subkey_index(name).ok()?;
+
+        Some(self.subkeys.remove(index))
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.values.iter().find(|(n, _)| names_equal(n, name)).map(|(_, value)| value)
+    }
+
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        match self.values.iter_mut().find(|(n, _)| names_equal(n, name)) {
+            Some((_, existing)) => *existing = value,
+            None => self.values.push((name.to_owned(), value)),
+        };
+
+        self.touch();
+    }
+
+    pub fn remove_value(&mut self, name: &str) -> Option<Value> {
+        let index = self.values.iter().position(|(n, _)| names_equal(n, name))?;
+        self.touch();
+
+        Some(self.values.remove(index).1)
+    }
+}
+
+pub type SharedRoots = Arc<RwLock<Vec<MemoryNode>>>;
+
+/// Called after every change with the updated roots and the path of the
+/// changed key. Returning an error rolls the change back.
+pub type PersistFn = dyn Fn(&[MemoryNode], &[String]) -> Result<()> + Send + Sync;
+
+fn read_roots(roots: &SharedRoots) -> RwLockReadGuard<'_, Vec<MemoryNode>> {
+    roots.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_roots(roots: &SharedRoots) -> RwLockWriteGuard<'_, Vec<MemoryNode>> {
+    roots.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn find_node<'a>(roots: &'a [MemoryNode], path: &[String]) -> Option<&'a MemoryNode> {
+    let (root, rest) = path.split_first()?;
+
+    roots.iter().find(|node| names_equal(&node.name, root))?.find(rest)
+}
+
+fn find_node_mut<'a>(roots: &'a mut [MemoryNode], path: &[String]) -> Option<&'a mut MemoryNode> {
+    let (root, rest) = path.split_first()?;
+
+    roots.iter_mut().find(|node| names_equal(&node.name, root))?.find_mut(rest)
+}
+
+/// Resolves `path` case-insensitively, returning the names as they are spelled in the tree.
+fn canonical_path(roots: &[MemoryNode], path: &[String]) -> Option<Vec<String>> {
+    let (root, rest) = path.split_first()?;
+
+    let mut node = roots.iter().find(|node| names_equal(&node.name, root))?;
+    let mut canonical = vec![node.name.clone()];
+
+    for name in rest {
+        node = node.subkey(name)?;
+        canonical.push(node.name.clone());
+    }
+
+    Some(canonical)
+}
+
+fn key_not_found(path: &[String]) -> Error {
+    Error::not_found(format!("The key {} doesn't exist", path.join("\\")))
+}
+
+pub struct MemoryKey {
+    roots: SharedRoots,
+    path: Vec<String>,
+    persist: Option<Arc<PersistFn>>,
+}
+
+impl MemoryKey {
+    pub fn new(roots: SharedRoots, path: Vec<String>) -> Self {
+        Self { roots, path, persist: None }
+    }
+
+    pub fn with_persist(roots: SharedRoots, path: Vec<String>, persist: Arc<PersistFn>) -> Self {
+        Self { roots, path, persist: Some(persist) }
+    }
+
+    fn child(&self, path: Vec<String>) -> Self {
+        Self { roots: self.roots.clone(), path, persist: self.persist.clone() }
+    }
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    fn with_node<T>(&self, f: impl FnOnce(&MemoryNode) -> Result<T>) -> Result<T> {
+        let roots = read_roots(&self.roots);
+        let node = find_node(&roots, &self.path).ok_or_else(|| key_not_found(&self.path))?;
+
+        f(node)
+    }
+
+    fn with_node_mut<T>(&self, f: impl FnOnce(&mut MemoryNode) -> Result<T>) -> Result<T> {
+        let mut roots = write_roots(&self.roots);
+
+        // only the root that's being changed needs a copy to roll back to
+        let backup = self.persist.as_ref()
+            .and_then(|_| roots.iter().position(|node| names_equal(&node.name, &self.path[0])))
+            .map(|index| (index, roots[index].clone()));
+
+        let node = find_node_mut(&mut roots, &self.path).ok_or_else(|| key_not_found(&self.path))?;
+        let result = f(node)?;
+
+        if let Some(persist) = self.persist.as_ref()
+            && let Err(err) = persist(&roots, &self.path)
+        {
+            if let Some((index, node)) = backup {
+                roots[index] = node;
+            }
+            return Err(err);
+        }
+
+        Ok(result)
+    }
+}
+
+impl RegistryKey for MemoryKey {
+    fn open(&self, path: &str) -> Result<Box<dyn RegistryKey>> {
+        let mut full_path = self.path.clone();
+        full_path.extend(split_path(path));
+
+        let roots = read_roots(&self.roots);
+        let canonical = canonical_path(&roots, &full_path).ok_or_else(|| key_not_found(&full_path))?;
+
+        Ok(Box::new(self.child(canonical)))
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn RegistryKey>> {
+        Ok(Box::new(self.child(self.path.clone())))
+    }
+
+    fn subkeys(&self) -> Result<Vec<String>> {
+        self.with_node(|node| Ok(node.subkeys.iter().map(|s| s.name.clone()).collect()))
+    }
+
+    fn create(&self, name: &str) -> Result<()> {
+        let components = split_path(name);
+        if components.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Name of a key can't be empty"));
+        }
+
+        self.with_node_mut(|mut node| {
+            for component in components {
+                if node.subkey(&component).is_none() {
+                    node.touch();
+                    node.insert_subkey(MemoryNode::new(component.as_str()));
+                }
+
+                node = node.subkey_mut(&component).unwrap();
+            }
+
+            Ok(())
+        })
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        if to.is_empty() || to.contains('\\') {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{to} isn't a valid key name")));
+        }
+
+        self.with_node_mut(|node| {
+            if node.subkey(to).is_some_and(|s| !names_equal(&s.name, from)) {
+                return Err(Error::already_exists(format!("The key {to} already exists")));
+            }
+
+            let Some(mut subkey) = node.remove_subkey(from) else {
+                return Err(Error::not_found(format!("The key {from} doesn't exist")));
+            };
+
+            subkey.name = to.to_owned();
+            subkey.touch();
+
+            node.insert_subkey(subkey);
+            node.touch();
+
+            Ok(())
+        })
+    }
+
+    fn remove_tree(&self, name: &str) -> Result<()> {
+        self.with_node_mut(|node| {
+            let mut components = split_path(name);
+            let Some(last) = components.pop() else {
+                return Err(Error::new(ErrorKind::InvalidInput, "Can't remove the key itself"));
+            };
+
+            let parent = node.find_mut(&components).ok_or_else(|| key_not_found(&components))?;
+            if parent.remove_subkey(&last).is_none() {
+                return Err(Error::not_found(format!("The key {last} doesn't exist")));
+            }
+            parent.touch();
+
+            Ok(())
+        })
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>> {
+        self.with_node(|node| Ok(node.values.clone()))
+    }
+
+    fn get_value(&self, name: &str) -> Result<Value> {
+        self.with_node(|node| {
+            node.value(name)
+                .cloned()
+                .ok_or_else(|| Error::not_found(format!("The value {name} doesn't exist")))
+        })
+    }
+
+    fn set_value(&self, name: &str, value: &Value) -> Result<()> {
+        self.with_node_mut(|node| {
+            node.set_value(name, value.clone());
+            Ok(())
+        })
+    }
+
+    fn remove_value(&self, name: &str) -> Result<()> {
+        self.with_node_mut(|node| {
+            node.remove_value(name)
+                .map(|_| ())
+                .ok_or_else(|| Error::not_found(format!("The value {name} doesn't exist")))
+        })
+    }
+
+    fn last_write(&self) -> Result<Option<u64>> {
+        self.with_node(|node| Ok(Some(node.last_write)))
+    }
+}
+
+pub struct MemoryBackend {
+    name: String,
+    roots: SharedRoots,
+}
+
+impl MemoryBackend {
+    pub fn new(name: impl Into<String>, roots: Vec<MemoryNode>) -> Self {
+        Self { name: name.into(), roots: Arc::new(RwLock::new(roots)) }
+    }
+
+    pub fn shared_roots(&self) -> SharedRoots {
+        self.roots.clone()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let json: serde_json::Value = serde_json::from_str(text)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("Invalid fixture: {err}")))?;
+
+        let Some(object) = json.as_object() else {
+            return Err(invalid_fixture("", "expected an object of root keys"));
+        };
+        if object.is_empty() {
+            return Err(invalid_fixture("", "no root keys defined"));
+        }
+
+        let roots = object
+            .iter()
+            .map(|(name, node)| parse_fixture_node(name, name, node))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::new("Memory", roots))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        Self::from_json(&text)
+    }
+}
+
+impl RegistryBackend for MemoryBackend {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn location(&self) -> Option<String> {
+        None
+    }
+
+    fn roots(&self) -> Vec<String> {
+        read_roots(&self.roots).iter().map(|node| node.name.clone()).collect()
+    }
+
+    fn open_root(&self, name: &str) -> Result<Box<dyn RegistryKey>> {
+        let roots = read_roots(&self.roots);
+        let Some(root) = roots.iter().find(|node| names_equal(&node.name, name)) else {
+            return Err(Error::not_found(format!("Unknown root key {name}")));
+        };
+
+        Ok(Box::new(MemoryKey::new(self.roots.clone(), vec![root.name.clone()])))
+    }
+}
+
+fn invalid_fixture(path: &str, message: &str) -> Error {
+    match path.is_empty() {
+        true => Error::new(ErrorKind::InvalidData, format!("Invalid fixture: {message}")),
+        false => Error::new(ErrorKind::InvalidData, format!("Invalid fixture at {path}: {message}")),
+    }
+}
+
+fn parse_fixture_node(name: &str, path: &str, json: &serde_json::Value) -> Result<MemoryNode> {
+    let Some(object) = json.as_object() else {
+        return Err(invalid_fixture(path, "a key must be an object"));
+    };
+
+    let mut node = MemoryNode::new(name);
+
+    if let Some(timestamp) = object.get("timestamp") {
+        node.last_write = timestamp.as_u64().ok_or_else(|| invalid_fixture(path, "timestamp must be a FILETIME number"))?;
+    }
+
+    if let Some(values) = object.get("values") {
+        let values = values.as_object().ok_or_else(|| invalid_fixture(path, "values must be an object"))?;
+
+        for (value_name, value) in values {
+            let value_path = format!("{path}\\@{value_name}");
+            node.values.push((value_name.clone(), parse_fixture_value(&value_path, value)?));
+        }
+    }
+
+    if let Some(keys) = object.get("keys") {
+        let keys = keys.as_object().ok_or_else(|| invalid_fixture(path, "keys must be an object"))?;
+
+        for (subkey_name, subkey) in keys {
+            if subkey_name.is_empty() || subkey_name.contains('\\') {
+                return Err(invalid_fixture(path, "key names can't be empty or contain backslashes"));
+            }
+            if node.subkey(subkey_name).is_some() {
+                return Err(invalid_fixture(path, &format!("duplicate key {subkey_name}")));
+            }
+
+            let subkey_path = format!("{path}\\{subkey_name}");
+            node.insert_subkey(parse_fixture_node(subkey_name, &subkey_path, subkey)?);
+        }
+    }
+
+    Ok(node)
+}
+
+fn parse_fixture_value(path: &str, json: &serde_json::Value) -> Result<Value> {
+    // shorthands: plain strings are REG_SZ and plain numbers are REG_DWORD
+    if let Some(s) = json.as_str() {
+        return Ok(Value::new(Type::String, registry::encode_sz(s)));
+    }
+    if let Some(n) = json.as_u64() {
+        let n = u32::try_from(n).map_err(|_| invalid_fixture(path, "number doesn't fit in a REG_DWORD"))?;
+        return Ok(Value::new(Type::U32, n.to_le_bytes().to_vec()));
+    }
+
+    let Some(object) = json.as_object() else {
+        return Err(invalid_fixture(path, "a value must be a string, a number or an object"));
+    };
+
+    let ty = match object.get("type").and_then(|ty| ty.as_str()) {
+        Some(ty) if registry::TYPE_STRINGS.contains(&ty) => registry::str_to_type(ty),
+        Some(ty) => return Err(invalid_fixture(path, &format!("unknown type {ty}"))),
+        None => return Err(invalid_fixture(path, "a value must have a type")),
+    };
+
+    if let Some(hex) = object.get("hex") {
+        let hex = hex.as_str().ok_or_else(|| invalid_fixture(path, "hex must be a string"))?;
+        let bytes = decode_hex(hex).ok_or_else(|| invalid_fixture(path, "hex must contain pairs of hex digits"))?;
+
+        return Ok(Value::new(ty, bytes));
+    }
+
+    let Some(data) = object.get("data") else {
+        return Err(invalid_fixture(path, "a value must have data or hex"));
+    };
+
+    let bytes = match ty {
+        Type::String | Type::ExpandString => {
+            let s = data.as_str().ok_or_else(|| invalid_fixture(path, "data must be a string"))?;
+            registry::encode_sz(s)
+        }
+        Type::MultiString => {
+            let items = data.as_array().ok_or_else(|| invalid_fixture(path, "data must be an array of strings"))?;
+            let strings = items
+                .iter()
+                .map(|item| item.as_str().map(|s| s.to_owned()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid_fixture(path, "data must be an array of strings"))?;
+
+            registry::encode_multi_sz(&strings)
+        }
+        Type::U32 | Type::U32BigEndian => {
+            let n = data.as_u64().and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(|| invalid_fixture(path, "data must be a 32-bit unsigned number"))?;
+            match ty {
+                Type::U32BigEndian => n.to_be_bytes().to_vec(),
+                _ => n.to_le_bytes().to_vec(),
+            }
+        }
+        Type::U64 => {
+            let n = data.as_u64().ok_or_else(|| invalid_fixture(path, "data must be a 64-bit unsigned number"))?;
+            n.to_le_bytes().to_vec()
+        }
+        _ => {
+            let hex = data.as_str().ok_or_else(|| invalid_fixture(path, "data must be a hex string"))?;
+            decode_hex(hex).ok_or_else(|| invalid_fixture(path, "data must contain pairs of hex digits"))?
+        }
+    };
+
+    Ok(Value::new(ty, bytes))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace() && *b != b',').collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .chunks_exact(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,130 @@
+use windows_registry::Key;
+
+use crate::registry::{Type, Value};
+
+use super::{Error, ErrorKind, RegistryBackend, RegistryKey, Result};
+
+pub const DEFAULT_KEYS: [(&'static Key, &'static str); 5] = [
+    (windows_registry::CLASSES_ROOT, "HKEY_CLASSES_ROOT"),
+    (windows_registry::CURRENT_USER, "HKEY_CURRENT_USER"),
+    (windows_registry::LOCAL_MACHINE, "HKEY_LOCAL_MACHINE"),
+    (windows_registry::USERS, "HKEY_USERS"),
+    (windows_registry::CURRENT_CONFIG, "HKEY_CURRENT_CONFIG")
+];
+
+const E_FILE_NOT_FOUND: u32 = 0x8007_0002;
+const E_ACCESS_DENIED: u32 = 0x8007_0005;
+const E_ALREADY_EXISTS: u32 = 0x8007_00B7;
+
+fn convert<T>(result: windows_registry::Result<T>) -> Result<T> {
+    result.map_err(|err| {
+        let kind = match err.code().0 as u32 {
+            E_FILE_NOT_FOUND => ErrorKind::NotFound,
+            E_ACCESS_DENIED => ErrorKind::AccessDenied,
+            E_ALREADY_EXISTS => ErrorKind::AlreadyExists,
+            _ => ErrorKind::Other,
+        };
+
+        Error::new(kind, err.message())
+    })
+}
+
+fn to_native_type(ty: Type) -> windows_registry::Type {
+    windows_registry::Type::from(u32::from(ty))
+}
+
+fn from_native_type(ty: windows_registry::Type) -> Type {
+    Type::from(u32::from(ty))
+}
+
+pub struct WindowsKey(Key);
+
+// SAFETY: registry handles may be used from any thread, the Win32 registry
+// API serializes access to the underlying key internally.
+unsafe impl Send for WindowsKey {}
+unsafe impl Sync for WindowsKey {}
+
+impl WindowsKey {
+    fn wrap(key: Key) -> Box<dyn RegistryKey> {
+        Box::new(Self(key))
+    }
+}
+
+impl RegistryKey for WindowsKey {
+    fn open(&self, path: &str) -> Result<Box<dyn RegistryKey>> {
+        // fall back to a read-only handle for keys we aren't allowed to modify
+        let key = self.0.options().read().write().open(path)
+            .or_else(|_| self.0.options().read().open(path));
+
+        convert(key).map(Self::wrap)
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn RegistryKey>> {
+        self.open("")
+    }
+
+    fn subkeys(&self) -> Result<Vec<String>> {
+        convert(self.0.keys().map(|keys| keys.collect()))
+    }
+
+    fn create(&self, name: &str) -> Result<()> {
+        convert(self.0.create(name).map(|_| ()))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        convert(self.0.rename(from, to))
+    }
+
+    fn remove_tree(&self, name: &str) -> Result<()> {
+        convert(self.0.remove_tree(name))
+    }
+
+    fn values(&self) -> Result<Vec<(String, Value)>> {
+        let values = convert(self.0.values())?;
+
+        Ok(values
+            .map(|(name, value)| (name, Value::new(from_native_type(value.ty()), value.to_vec())))
+            .collect())
+    }
+
+    fn get_value(&self, name: &str) -> Result<Value> {
+        let value = convert(self.0.get_value(name))?;
+
+        Ok(Value::new(from_native_type(value.ty()), value.to_vec()))
+    }
+
+    fn set_value(&self, name: &str, value: &Value) -> Result<()> {
+        convert(self.0.set_bytes(name, to_native_type(value.ty()), value))
+    }
+
+    fn remove_value(&self, name: &str) -> Result<()> {
+        convert(self.0.remove_value(name))
+    }
+}
+
+#[derive(Default)]
+pub struct WindowsBackend { }
+
+impl WindowsBackend {
+    pub fn new() -> Self {
+        Self { }
+    }
+}
+
+impl RegistryBackend for WindowsBackend {
+    fn name(&self) -> &str {
+        "Computer"
+    }
+
+    fn roots(&self) -> Vec<String> {
+        DEFAULT_KEYS.iter().map(|(_, name)| name.to_string()).collect()
+    }
+
+    fn open_root(&self, name: &str) -> Result<Box<dyn RegistryKey>> {
+        let Some((key, _)) = DEFAULT_KEYS.iter().find(|(_, s)| *s == name) else {
+            return Err(Error::not_found(format!("Unknown root key {name}")));
+        };
+
+        convert(key.open("")).map(WindowsKey::wrap)
+    }
+}
@@ -0,0 +1,612 @@
+use std::{fmt::Write as _, fs, io::Write, path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock}};
+
+use crate::registry::{Type, Value};
+
+use super::{memory::{names_equal, MemoryKey, MemoryNode, SharedRoots, FILETIME_UNIX_EPOCH}, Error, ErrorKind, RegistryBackend, RegistryKey, Result};
+
+pub const WINE_HEADER: &str = "WINE REGISTRY Version 2";
+
+const TICKS_PER_SECOND: u64 = 10_000_000;
+
+/// Wine wraps hex data once a line gets longer than this.
+const MAX_HEX_LINE: usize = 76;
+
+/// C escapes Wine uses for control characters, '.' means there isn't one.
+const CONTROL_ESCAPES: &[u8; 32] = b".......abtnvfr.............e....";
+
+fn invalid(path: &Path, line: usize, message: impl AsRef<str>) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line, message.as_ref()))
+}
+
+/// Reads an escaped string up to the unescaped `end` character, returning the
+/// UTF-16 units and whatever follows the terminator.
+fn unescape(s: &str, end: char) -> Option<(Vec<u16>, &str)> {
+    let mut result = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c == end {
+            return Some((result, &s[i + c.len_utf8()..]));
+        }
+        if c != '\\' {
+            let mut units = [0; 2];
+            result.extend_from_slice(c.encode_utf16(&mut units));
+            continue;
+        }
+
+        let (_, escaped) = chars.next()?;
+        match escaped {
+            'x' => {
+                let mut n = 0;
+                for _ in 0..4 {
+                    match chars.peek().and_then(|&(_, c)| c.to_digit(16)) {
+                        Some(digit) => n = n * 16 + digit,
+                        None => break,
+                    };
+                    chars.next();
+                }
+                result.push(n as u16);
+            }
+            '0'..='7' => {
+                let mut n = escaped.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|&(_, c)| c.to_digit(8)) {
+                        Some(digit) => n = n * 8 + digit,
+                        None => break,
+                    };
+                    chars.next();
+                }
+                result.push(n as u16);
+            }
+            c => {
+                let unit = match CONTROL_ESCAPES.iter().position(|&e| e != b'.' && e as char == c) {
+                    Some(control) => control as u16,
+                    None => c as u16,
+                };
+                result.push(unit);
+            }
+        };
+    }
+
+    None
+}
+
+fn escape(units: &[u16], quotes: [u16; 2], out: &mut String) {
+    for (i, &unit) in units.iter().enumerate() {
+        let next = units.get(i + 1).copied();
+
+        if unit > 127 {
+            // a following hex digit would be read as part of the escape
+            match next.is_some_and(|n| n < 128 && (n as u8).is_ascii_hexdigit()) {
+                true => write!(out, "\\x{unit:04x}").unwrap(),
+                false => write!(out, "\\x{unit:x}").unwrap(),
+            };
+        } else if unit < 32 {
+            match CONTROL_ESCAPES[unit as usize] {
+                b'.' if next.is_some_and(|n| (b'0' as u16..=b'7' as u16).contains(&n)) => write!(out, "\\{unit:03o}").unwrap(),
+                b'.' => write!(out, "\\{unit:o}").unwrap(),
+                c => write!(out, "\\{}", c as char).unwrap(),
+            };
+        } else {
+            if unit == b'\\' as u16 || quotes.contains(&unit) {
+                out.push('\\');
+            }
+            out.push(unit as u8 as char);
+        }
+    }
+}
+
+fn escape_str(s: &str, quotes: [u16; 2]) -> String {
+    let mut out = String::new();
+    escape(&s.encode_utf16().collect::<Vec<_>>(), quotes, &mut out);
+
+    out
+}
+
+fn parse_value_data(data: &str) -> Option<Value> {
+    if let Some(quoted) = data.strip_prefix('"') {
+        let (mut units, _) = unescape(quoted, '"')?;
+        units.push(0);
+
+        return Some(Value::new(Type::String, units.into_iter().flat_map(|u| u.to_le_bytes()).collect()));
+    }
+
+    if let Some(rest) = data.strip_prefix("str(") {
+        let (ty, quoted) = rest.split_once("):\"")?;
+        let (mut units, _) = unescape(quoted, '"')?;
+        units.push(0);
+
+        let ty = u32::from_str_radix(ty, 16).ok()?;
+        return Some(Value::new(Type::from(ty), units.into_iter().flat_map(|u| u.to_le_bytes()).collect()));
+    }
+
+    if let Some(digits) = data.strip_prefix("dword:") {
+        let n = u32::from_str_radix(digits.trim(), 16).ok()?;
+        return Some(Value::new(Type::U32, n.to_le_bytes().to_vec()));
+    }
+
+    let (ty, hex) = match data.strip_prefix("hex(") {
+        Some(rest) => {
+            let (ty, hex) = rest.split_once("):")?;
+            (Type::from(u32::from_str_radix(ty, 16).ok()?), hex)
+        }
+        None => (Type::Bytes, data.strip_prefix("hex:")?),
+    };
+
+    let bytes = hex.split(',')
+        .map(|byte| byte.trim())
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(Value::new(ty, bytes))
+}
+
+fn units_to_string(units: &[u16]) -> String {
+    String::from_utf16_lossy(units)
+}
+
+/// Parses the contents of a Wine registry file into a tree under `root`,
+/// returning the header lines so they can be written back unchanged.
+fn parse(path: &Path, text: &str, root: &mut MemoryNode) -> Result<Vec<String>> {
+    let mut lines = text.lines().enumerate();
+
+    match lines.next() {
+        Some((_, line)) if line.trim_end() == WINE_HEADER => (),
+        _ => return Err(invalid(path, 1, format!("missing '{WINE_HEADER}' header"))),
+    };
+
+    let mut header = vec![WINE_HEADER.to_owned()];
+    let mut current: Option<Vec<String>> = None;
+
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let line = line.trim_end();
+
+        if let Some(section) = line.strip_prefix('[') {
+            let (units, rest) = unescape(section, ']').ok_or_else(|| invalid(path, line_number, "unterminated key name"))?;
+            let components = super::split_path(&units_to_string(&units));
+
+            let mut node = &mut *root;
+            for name in &components {
+                if node.subkey(name).is_none() {
+                    node.insert_subkey(MemoryNode::new(name.as_str()));
+                }
+                node = node.subkey_mut(name).unwrap();
+            }
+
+            if let Ok(seconds) = rest.trim().parse::<u64>() {
+                node.last_write = FILETIME_UNIX_EPOCH + seconds * TICKS_PER_SECOND;
+            }
+
+            current = Some(components);
+            continue;
+        }
+
+        let Some(components) = current.as_ref() else {
+            // everything before the first key is kept as is
+            header.push(line.to_owned());
+            continue;
+        };
+        let node = root.find_mut(components).unwrap();
+
+        if let Some(time) = line.strip_prefix("#time=") {
+            node.last_write = u64::from_str_radix(time.trim(), 16).map_err(|_| invalid(path, line_number, "invalid #time"))?;
+        } else if line == "#link" {
+            node.link = true;
+        } else if let Some(class) = line.strip_prefix("#class=\"") {
+            let (units, _) = unescape(class, '"').ok_or_else(|| invalid(path, line_number, "unterminated class name"))?;
+            node.class = Some(units_to_string(&units));
+        } else if line.starts_with('#') {
+            node.annotations.push(line.to_owned());
+        } else if line.starts_with('"') || line.starts_with('@') {
+            let (name, rest) = match line.strip_prefix('@') {
+                Some(rest) => (String::new(), rest),
+                None => {
+                    let (units, rest) = unescape(&line[1..], '"').ok_or_else(|| invalid(path, line_number, "unterminated value name"))?;
+                    (units_to_string(&units), rest)
+                }
+            };
+
+            let Some(data) = rest.strip_prefix('=') else {
+                return Err(invalid(path, line_number, "expected '=' after the value name"));
+            };
+
+            let mut data = data.to_owned();
+            while let Some(stripped) = data.strip_suffix('\\') {
+                data = stripped.to_owned();
+                match lines.next() {
+                    Some((_, next)) => data.push_str(next.trim()),
+                    None => return Err(invalid(path, line_number, "the file ends in the middle of hex data")),
+                };
+            }
+
+            let value = parse_value_data(&data).ok_or_else(|| invalid(path, line_number, format!("invalid value data '{data}'")))?;
+            node.values.push((name, value));
+        }
+    }
+
+    while header.last().is_some_and(|line| line.is_empty()) {
+        header.pop();
+    }
+
+    Ok(header)
+}
+
+fn is_terminated_string(value: &Value) -> bool {
+    value.len() >= 2 && value.len().is_multiple_of(2) && value.as_wide().last() == Some(&0)
+}
+
+fn write_value(out: &mut String, name: &str, value: &Value) {
+    let start = out.len();
+    match name.is_empty() {
+        true => out.push_str("@="),
+        false => write!(out, "\"{}\"=", escape_str(name, [b'"' as u16; 2])).unwrap(),
+    };
+
+    match value.ty() {
+        Type::String | Type::ExpandString | Type::MultiString if is_terminated_string(value) => {
+            if value.ty() != Type::String {
+                write!(out, "str({:x}):", u32::from(value.ty())).unwrap();
+            }
+
+            let wide = value.as_wide();
+            out.push('"');
+            escape(&wide[..wide.len() - 1], [b'"' as u16; 2], out);
+            out.push_str("\"\n");
+            return;
+        }
+        Type::U32 if value.len() == 4 => {
+            writeln!(out, "dword:{:08x}", u32::from_le_bytes(value[..4].try_into().unwrap())).unwrap();
+            return;
+        }
+        Type::Bytes => out.push_str("hex:"),
+        ty => write!(out, "hex({:x}):", u32::from(ty)).unwrap(),
+    };
+
+    let mut count = out.len() - start;
+    for (i, byte) in value.iter().enumerate() {
+        write!(out, "{byte:02x}").unwrap();
+        count += 2;
+
+        if i + 1 < value.len() {
+            out.push(',');
+            count += 1;
+
+            if count > MAX_HEX_LINE {
+                out.push_str("\\\n  ");
+                count = 2;
+            }
+        }
+    }
+    out.push('\n');
+}
+
+fn write_key(out: &mut String, node: &MemoryNode, path: &str) {
+    // keys that only hold other keys are implied by their children
+    if !node.values.is_empty() || node.subkeys.is_empty() || node.class.is_some() || node.link || !node.annotations.is_empty() {
+        let seconds = node.last_write.saturating_sub(FILETIME_UNIX_EPOCH) / TICKS_PER_SECOND;
+
+        writeln!(out, "\n[{path}] {seconds}").unwrap();
+        writeln!(out, "#time={:x}{:08x}", node.last_write >> 32, node.last_write & 0xFFFF_FFFF).unwrap();
+
+        if let Some(class) = node.class.as_ref() {
+            writeln!(out, "#class=\"{}\"", escape_str(class, [b'"' as u16; 2])).unwrap();
+        }
+        if node.link {
+            out.push_str("#link\n");
+        }
+        for annotation in &node.annotations {
+            writeln!(out, "{annotation}").unwrap();
+        }
+
+        for (name, value) in &node.values {
+            write_value(out, name, value);
+        }
+    }
+
+    for subkey in &node.subkeys {
+        let name = escape_str(&subkey.name, [b'[' as u16, b']' as u16]);
+        let path = match path.is_empty() {
+            true => name,
+            false => format!("{path}\\\\{name}"),
+        };
+
+        write_key(out, subkey, &path);
+    }
+}
+
+fn serialize(header: &[String], root: &MemoryNode) -> String {
+    let mut out = header.join("\n");
+    out.push('\n');
+
+    for subkey in &root.subkeys {
+        write_key(&mut out, subkey, &escape_str(&subkey.name, [b'[' as u16, b']' as u16]));
+    }
+
+    out
+}
+
+/// One of the registry files in a prefix and where its keys show up.
+pub struct WineFile {
+    path: PathBuf,
+    header: Vec<String>,
+    mount: Vec<String>,
+}
+
+impl WineFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn save(&self, root: &MemoryNode) -> Result<()> {
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".regcli-tmp");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(serialize(&self.header, root).as_bytes())?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn find_mounted<'a>(roots: &'a [MemoryNode], mount: &[String]) -> Option<&'a MemoryNode> {
+    let (root, rest) = mount.split_first()?;
+
+    roots.iter().find(|node| names_equal(&node.name, root))?.find(rest)
+}
+
+fn is_under(path: &[String], mount: &[String]) -> bool {
+    path.len() >= mount.len() && path.iter().zip(mount).all(|(a, b)| names_equal(a, b))
+}
+
+/// A Wine prefix, with `system.reg` as HKEY_LOCAL_MACHINE, `user.reg` as
+/// HKEY_CURRENT_USER and `userdef.reg` as HKEY_USERS\.Default.
+pub struct WineBackend {
+    prefix: PathBuf,
+    roots: SharedRoots,
+    files: Arc<Vec<WineFile>>,
+    batch: Arc<Mutex<WineBatch>>,
+}
+
+/// How deep the open batches go and which files have changes that weren't written yet.
+#[derive(Default)]
+struct WineBatch {
+    batches: usize,
+    unsaved: Vec<usize>,
+}
+
+fn lock_batch(batch: &Mutex<WineBatch>) -> MutexGuard<'_, WineBatch> {
+    batch.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn save_file(file: &WineFile, roots: &[MemoryNode]) -> Result<()> {
+    let node = find_mounted(roots, &file.mount).ok_or_else(|| Error::other("The registry file's root key is missing"))?;
+
+    file.save(node)
+}
+
+impl WineBackend {
+    pub fn open(prefix: impl AsRef<Path>) -> Result<Self> {
+        let prefix = prefix.as_ref();
+        let layout = [
+            ("system.reg", &["HKEY_LOCAL_MACHINE"][..]),
+            ("user.reg", &["HKEY_CURRENT_USER"][..]),
+            ("userdef.reg", &["HKEY_USERS", ".Default"][..]),
+        ];
+
+        let mut roots: Vec<MemoryNode> = Vec::new();
+        let mut files = Vec::new();
+
+        for (file_name, mount) in layout {
+            let path = prefix.join(file_name);
+            if !path.is_file() {
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&fs::read(&path)?).into_owned();
+            let mut node = MemoryNode::new(*mount.last().unwrap());
+            let header = parse(&path, &text, &mut node)?;
+
+            match mount {
+                [_] => roots.push(node),
+                [root, _] => {
+                    let index = match roots.iter().position(|r| r.name == *root) {
+                        Some(index) => index,
+                        None => {
+                            roots.push(MemoryNode::new(*root));
+                            roots.len() - 1
+                        }
+                    };
+                    roots[index].insert_subkey(node);
+                }
+                _ => unreachable!(),
+            };
+
+            files.push(WineFile { path, header, mount: mount.iter().map(|s| s.to_string()).collect() });
+        }
+
+        if files.is_empty() {
+            return Err(Error::not_found(format!("{} doesn't contain any Wine registry files", prefix.display())));
+        }
+
+        let prefix = fs::canonicalize(prefix).unwrap_or_else(|_| prefix.to_path_buf());
+        Ok(Self { prefix, roots: Arc::new(RwLock::new(roots)), files: Arc::new(files), batch: Arc::default() })
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    pub fn files(&self) -> &[WineFile] {
+        &self.files
+    }
+}
+
+impl RegistryBackend for WineBackend {
+    fn name(&self) -> &str {
+        "Wine"
+    }
+
+    fn location(&self) -> Option<String> {
+        Some(self.prefix.display().to_string())
+    }
+
+    fn roots(&self) -> Vec<String> {
+        self.roots.read().unwrap_or_else(PoisonError::into_inner).iter().map(|node| node.name.clone()).collect()
+    }
+
+    fn begin_batch(&self) {
+        lock_batch(&self.batch).batches += 1;
+    }
+
+    /// Writes the changed files once the outermost batch ends. A failed write
+    /// keeps the file marked, to be written along with the next change.
+    fn end_batch(&self) -> Result<()> {
+        // the roots are locked before the batch, the same order as the persist hook
+        let roots = self.roots.read().unwrap_or_else(PoisonError::into_inner);
+        let mut batch = lock_batch(&self.batch);
+        batch.batches = batch.batches.saturating_sub(1);
+
+        if batch.batches > 0 {
+            return Ok(());
+        }
+
+        let mut result = Ok(());
+        batch.unsaved.retain(|&index| match save_file(&self.files[index], &roots) {
+            Ok(()) => false,
+            Err(err) => {
+                // reports the first failure, like folding with Result::and
+                if result.is_ok() {
+                    result = Err(err);
+                }
+                true
+            }
+        });
+
+        result
+    }
+
+    fn open_root(&self, name: &str) -> Result<Box<dyn RegistryKey>> {
+        let Some(root) = self.roots().into_iter().find(|root| names_equal(root, name)) else {
+            return Err(Error::not_found(format!("Unknown root key {name}")));
+        };
+
+        let files = self.files.clone();
+        let batch = self.batch.clone();
+        let persist = move |roots: &[MemoryNode], path: &[String]| {
+            let Some(index) = files.iter().position(|file| is_under(path, &file.mount)) else {
+                return Err(Error::new(ErrorKind::AccessDenied, "Only keys stored in one of the prefix's registry files can be changed"));
+            };
+
+            // inside a batch the file is written once, when the batch ends
+            let mut batch = lock_batch(&batch);
+            if batch.batches > 0 {
+                if !batch.unsaved.contains(&index) {
+                    batch.unsaved.push(index);
+                }
+                return Ok(());
+            }
+
+            save_file(&files[index], roots)
+        };
+
+        Ok(Box::new(MemoryKey::with_persist(self.roots.clone(), vec![root], Arc::new(persist))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::split_path;
+
+    use super::*;
+
+    const SAMPLE: &str = concat!(
+        "WINE REGISTRY Version 2\n",
+        ";; All keys relative to \\\\User\\\\S-1-5-21-0-0-0-1000\n",
+        "\n",
+        "#arch=win64\n",
+        "\n",
+        "[Software\\\\Classes\\\\Link] 1699108133\n",
+        "#time=1da0f2b3c4d5e6f\n",
+        "#class=\"Cl\\\"ass\"\n",
+        "#link\n",
+        "#unknown=kept\n",
+        "@=\"default\"\n",
+        "\"SymbolicLinkValue\"=hex(6):5c,00,52,00\n",
+        "\n",
+        "[Software\\\\Wine] 1699108133\n",
+        "#time=1da0f2b3c4d5e70\n",
+        "\"Escapes\"=\"tab\\there \\\"q\\\" \\\\ \\x263a\\xe9z\"\n",
+        "\"Expand\"=str(2):\"%SystemRoot%\\\\system32\"\n",
+        "\"Multi\"=str(7):\"a\\0b\\0\"\n",
+        "\"Number\"=dword:0000002a\n",
+        "\"Binary\"=hex:00,01,02,03,04,05,06,07,08,09,0a,0b,0c,0d,0e,0f,10,11,12,13,14,15,\\\n",
+        "  16,17,18,19,1a,1b,1c,1d,1e,1f\n",
+    );
+
+    #[test]
+    fn save_writes_back_what_was_read() {
+        let mut root = MemoryNode::new("HKEY_CURRENT_USER");
+        let header = parse(Path::new("user.reg"), SAMPLE, &mut root).unwrap();
+
+        assert_eq!(serialize(&header, &root), SAMPLE);
+    }
+
+    #[test]
+    fn annotations_are_parsed() {
+        let mut root = MemoryNode::new("HKEY_CURRENT_USER");
+        parse(Path::new("user.reg"), SAMPLE, &mut root).unwrap();
+
+        let path = split_path("Software\\Classes\\Link");
+        let node = root.find(&path).unwrap();
+        assert_eq!(node.class.as_deref(), Some("Cl\"ass"));
+        assert!(node.link);
+        assert_eq!(node.annotations, vec!["#unknown=kept"]);
+        assert_eq!(node.last_write, 0x1da0f2b3c4d5e6f);
+
+        let wine = root.find(&split_path("Software\\Wine")).unwrap();
+        assert_eq!(wine.value("Escapes").unwrap().as_wide(), "tab\there \"q\" \\ \u{263a}\u{e9}z\0".encode_utf16().collect::<Vec<_>>());
+        assert_eq!(wine.value("Multi").unwrap().ty(), Type::MultiString);
+    }
+
+    #[test]
+    fn batches_write_once_at_the_end() {
+        let prefix = std::env::temp_dir().join(format!("regcli-{}-wine-batch", std::process::id()));
+        fs::create_dir_all(&prefix).unwrap();
+        fs::write(prefix.join("user.reg"), SAMPLE).unwrap();
+
+        let backend = WineBackend::open(&prefix).unwrap();
+        let root = backend.open_root("HKEY_CURRENT_USER").unwrap();
+
+        crate::backend::batch(&backend, || {
+            root.create("Key")?;
+            root.open("Key")?.set_value("Number", &Value::new(Type::U32, 7u32.to_le_bytes().to_vec()))?;
+
+            // nothing's written until the batch ends
+            assert_eq!(fs::read_to_string(prefix.join("user.reg"))?, SAMPLE);
+            Ok(())
+        }).unwrap();
+
+        let saved = WineBackend::open(&prefix).unwrap();
+        let key = saved.open_root("HKEY_CURRENT_USER").unwrap().open("Key").unwrap();
+        assert_eq!(&*key.get_value("Number").unwrap(), 7u32.to_le_bytes());
+
+        // outside a batch every change is written right away
+        root.remove_tree("Key").unwrap();
+        assert_eq!(fs::read_to_string(prefix.join("user.reg")).unwrap(), SAMPLE);
+
+        fs::remove_dir_all(prefix).unwrap();
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let mut root = MemoryNode::new("HKEY_CURRENT_USER");
+        let err = parse(Path::new("user.reg"), "WINE REGISTRY Version 2\n\n[Software] 0\n\"A\"=dword:xyz\n", &mut root).err().unwrap();
+
+        assert_eq!(err.message(), "user.reg:4: invalid value data 'dword:xyz'");
+    }
+}
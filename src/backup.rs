@@ -0,0 +1,298 @@
+use std::{io::Write, path::{Path, PathBuf}};
+
+use crate::{backend::{self, memory::{self, MemoryNode}, Error, RegistryBackend, Result}, config, regfile::{self, RegFile, RegOperation}, registry::Value, treefile::{self, ImportMode, TreeFile, TreeFormat}};
+
+const TICKS_PER_SECOND: u64 = 10_000_000;
+/// Length of the `YYYYMMDD-HHMMSS` the file names start with.
+const STAMP_LEN: usize = 15;
+
+/// A copy of a key subtree, or of a single value, written just before it was
+/// deleted or overwritten. Backups are JSON exports named after the UTC time
+/// they were taken and the key, e.g. `20261016-192530_Software.json`, so they
+/// can also be imported like any other export.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub file: PathBuf,
+    pub tree: TreeFile,
+}
+
+impl Backup {
+    pub fn load(file: impl Into<PathBuf>) -> Result<Self> {
+        let file = file.into();
+        let bytes = std::fs::read(&file)?;
+        let tree = treefile::parse(&regfile::decode_text(&bytes), TreeFormat::Json)?;
+
+        Ok(Self { file, tree })
+    }
+
+    pub fn file_name(&self) -> String {
+        self.file.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// When the backup was taken, read back from the file name.
+    pub fn taken(&self) -> String {
+        let name = self.file_name();
+        let stamp = name.get(..STAMP_LEN).unwrap_or_default();
+
+        match stamp.len() == STAMP_LEN && stamp.chars().enumerate().all(|(i, c)| (i == 8 && c == '-') || (i != 8 && c.is_ascii_digit())) {
+            true => format!("{}-{}-{} {}:{}:{} UTC", &stamp[..4], &stamp[4..6], &stamp[6..8], &stamp[9..11], &stamp[11..13], &stamp[13..]),
+            false => "Unknown time".to_owned(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.tree.recursive, self.tree.root.values.as_slice()) {
+            (false, [(name, _)]) if name.is_empty() => format!("value (Default) of {}", self.tree.path),
+            (false, [(name, _)]) => format!("value {name} of {}", self.tree.path),
+            _ => format!("key {}", self.tree.path),
+        }
+    }
+
+    /// What restoring takes, restoring adds back what's missing and leaves
+    /// anything added since alone.
+    pub fn operations(&self, backend: &dyn RegistryBackend) -> Result<RegFile> {
+        treefile::operations(backend, &self.tree, ImportMode::Merge)
+    }
+}
+
+/// Days since 1970-01-01 to a date, valid for the whole proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn stamp(filetime: u64) -> String {
+    let seconds = filetime.saturating_sub(memory::FILETIME_UNIX_EPOCH) / TICKS_PER_SECOND;
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+
+    format!("{year:04}{month:02}{day:02}-{:02}{:02}{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+fn file_label(name: &str) -> String {
+    name.chars()
+        .take(40)
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn save(tree: TreeFile, label: &str) -> Result<PathBuf> {
+    let Some(dir) = config::backup_dir() else {
+        return Err(Error::unsupported("There's no directory to keep backups in"));
+    };
+    std::fs::create_dir_all(&dir)?;
+
+    let (text, _) = treefile::format(&tree, true, TreeFormat::Json)?;
+    let base = format!("{}_{}", stamp(memory::filetime_now()), file_label(label));
+
+    // more than one backup a second gets numbered
+    for attempt in 1.. {
+        let file = match attempt {
+            1 => dir.join(format!("{base}.json")),
+            n => dir.join(format!("{base}-{n}.json")),
+        };
+
+        match std::fs::File::create_new(&file) {
+            Ok(mut out) => {
+                out.write_all(text.as_bytes())?;
+                return Ok(file);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    unreachable!()
+}
+
+/// Backs up a key with everything below it, `path` being its full path.
+pub fn save_key(path: &str, tree: &MemoryNode) -> Result<PathBuf> {
+    let file = TreeFile { path: path.to_owned(), recursive: true, root: tree.clone() };
+
+    save(file, &tree.name)
+}
+
+/// Backs up a single value of the key at `path`.
+pub fn save_value(path: &str, name: &str, value: &Value) -> Result<PathBuf> {
+    let key_name = backend::split_path(path).pop().unwrap_or_default();
+
+    let mut root = MemoryNode::new(key_name.clone());
+    root.last_write = 0;
+    root.values.push((name.to_owned(), value.clone()));
+
+    let file = TreeFile { path: path.to_owned(), recursive: false, root };
+    let label = match name.is_empty() {
+        true => format!("{key_name}_Default"),
+        false => format!("{key_name}_{name}"),
+    };
+
+    save(file, &label)
+}
+
+/// Something applying a file would delete or overwrite.
+enum Lost {
+    Key(MemoryNode),
+    Value(String, Value),
+}
+
+fn empty_node(name: &str) -> MemoryNode {
+    let mut node = MemoryNode::new(name);
+    node.last_write = 0;
+    node
+}
+
+/// Adds what's missing from `node` out of `from`, what's there already stays.
+fn merge(node: &mut MemoryNode, from: MemoryNode) {
+    if node.last_write == 0 {
+        node.last_write = from.last_write;
+    }
+
+    for (name, value) in from.values {
+        if node.value(&name).is_none() {
+            node.values.push((name, value));
+        }
+    }
+
+    for subkey in from.subkeys {
+        match node.subkey_mut(&subkey.name) {
+            Some(existing) => merge(existing, subkey),
+            None => {
+                node.insert_subkey(subkey);
+            }
+        }
+    }
+}
+
+/// Backs up everything in `lost`, all of it under the same root key, in one
+/// file rooted at the deepest key they share.
+fn save_lost(lost: Vec<(Vec<String>, Lost)>) -> Result<PathBuf> {
+    let mut common = lost[0].0.clone();
+    for (path, _) in &lost {
+        let len = common.iter().zip(path).take_while(|(a, b)| memory::names_equal(a, b)).count();
+        common.truncate(len);
+    }
+
+    let label = match lost.as_slice() {
+        [(path, Lost::Value(name, _))] if name.is_empty() => format!("{}_Default", path[path.len() - 1]),
+        [(path, Lost::Value(name, _))] => format!("{}_{name}", path[path.len() - 1]),
+        _ => common[common.len() - 1].clone(),
+    };
+    let recursive = lost.iter().any(|(path, lost)| matches!(lost, Lost::Key(_)) || path.len() > common.len());
+
+    let mut root = empty_node(&common[common.len() - 1]);
+    for (path, lost) in lost {
+        let mut node = &mut root;
+        for name in &path[common.len()..] {
+            if node.subkey(name).is_none() {
+                node.insert_subkey(empty_node(name));
+            }
+            let Some(child) = node.subkey_mut(name) else { unreachable!() };
+            node = child;
+        }
+
+        match lost {
+            Lost::Key(tree) => merge(node, tree),
+            Lost::Value(name, value) => {
+                if node.value(&name).is_none() {
+                    node.values.push((name, value));
+                }
+            }
+        }
+    }
+
+    save(TreeFile { path: common.join("\\"), recursive, root }, &label)
+}
+
+/// Backs up whatever applying `file` would delete or overwrite, as it is
+/// before anything is applied. What's under different root keys goes to
+/// separate backups, and nothing is saved when nothing would be lost.
+pub fn save_changes(backend: &dyn RegistryBackend, file: &RegFile) -> Result<Vec<PathBuf>> {
+    // full paths split into components, the root key first
+    let mut lost: Vec<(Vec<String>, Lost)> = Vec::new();
+
+    for operation in &file.operations {
+        let (path, name, value) = match operation {
+            RegOperation::CreateKey { .. } => continue,
+            RegOperation::DeleteKey { path } => (path, None, None),
+            RegOperation::SetValue { path, name, value } => (path, Some(name), Some(value)),
+            RegOperation::DeleteValue { path, name } => (path, Some(name), None),
+        };
+        let Ok(key) = backend::open_path(backend, path) else { continue; };
+        let (root, components) = backend::resolve_path(backend, path)?;
+        let full = std::iter::once(root).chain(components).collect::<Vec<_>>();
+
+        match name {
+            // a key that can't be read can't be backed up, so it's an error
+            None => {
+                let tree = treefile::read_tree(key.as_ref(), &full[full.len() - 1], true)?;
+                lost.push((full, Lost::Key(tree)));
+            }
+            Some(name) => match key.get_value(name) {
+                Ok(old) if value != Some(&old) => lost.push((full, Lost::Value(name.clone(), old))),
+                _ => {},
+            },
+        }
+    }
+
+    let mut files = Vec::new();
+    while let Some((first, _)) = lost.first() {
+        let root = first[0].clone();
+        let (same_root, rest) = lost.into_iter().partition(|(path, _)| path[0] == root);
+        lost = rest;
+        files.push(save_lost(same_root)?);
+    }
+
+    Ok(files)
+}
+
+/// Every backup in the backup directory, newest first. Files that aren't
+/// backups are skipped.
+pub fn list() -> Result<Vec<Backup>> {
+    let Some(dir) = config::backup_dir() else { return Ok(Vec::new()); };
+
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    // backups taken within the same second are told apart by the file time
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+            && let Ok(backup) = Backup::load(&path)
+        {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok();
+            backups.push((backup.file_name().get(..STAMP_LEN).map(str::to_owned), modified, backup));
+        }
+    }
+
+    backups.sort_by(|a, b| (&b.0, b.1).cmp(&(&a.0, a.1)));
+    Ok(backups.into_iter().map(|(_, _, backup)| backup).collect())
+}
+
+/// A backup by its path, or by its file name in the backup directory.
+pub fn find(name: &str) -> Result<Backup> {
+    let path = Path::new(name);
+
+    match path.components().count() > 1 || path.exists() {
+        true => Backup::load(path),
+        false => match config::backup_dir() {
+            Some(dir) => Backup::load(dir.join(name)),
+            None => Backup::load(path),
+        },
+    }
+}
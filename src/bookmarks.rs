@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::{backend::{Error, ErrorKind, Result}, config};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub name: String,
+    /// Full path of the key, e.g. `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft`.
+    pub path: String,
+}
+
+/// Named key paths, stored as JSON so the file can be shared and edited by hand:
+///
+/// ```json
+/// { "bookmarks": [ { "name": "Run", "path": "HKLM\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run" } ] }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Bookmarks {
+    /// `None` when there's no config directory, or the file couldn't be read.
+    file: Option<PathBuf>,
+    pub items: Vec<Bookmark>,
+}
+
+fn invalid_file(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid bookmarks file: {message}"))
+}
+
+impl Bookmarks {
+    pub fn default_file() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join("bookmarks.json"))
+    }
+
+    /// Reads the bookmarks, a missing file meaning there are none yet.
+    pub fn load(file: Option<PathBuf>) -> Result<Self> {
+        let Some(path) = file.as_deref() else { return Ok(Self::default()); };
+
+        let items = match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self { file, items })
+    }
+
+    fn parse(text: &str) -> Result<Vec<Bookmark>> {
+        let json: serde_json::Value = serde_json::from_str(text).map_err(|err| invalid_file(&err.to_string()))?;
+        let Some(entries) = json.get("bookmarks").and_then(|entries| entries.as_array()) else {
+            return Err(invalid_file("expected a \"bookmarks\" array"));
+        };
+
+        entries.iter().map(|entry| {
+            let name = entry.get("name").and_then(|name| name.as_str());
+            let path = entry.get("path").and_then(|path| path.as_str());
+
+            match (name, path) {
+                (Some(name), Some(path)) => Ok(Bookmark { name: name.to_owned(), path: path.to_owned() }),
+                _ => Err(invalid_file("every bookmark needs a name and a path")),
+            }
+        }).collect()
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.file.as_deref() else {
+            return Err(Error::unsupported("There's no bookmarks file to save to"));
+        };
+
+        let entries = self.items.iter().map(|bookmark| json!({ "name": bookmark.name, "path": bookmark.path })).collect::<Vec<_>>();
+        let text = serde_json::to_string_pretty(&json!({ "bookmarks": entries })).map_err(|err| Error::other(err.to_string()))?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text + "\n")?;
+
+        Ok(())
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.items.iter().position(|bookmark| bookmark.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds the bookmark, replacing one with the same name. Returns its index.
+    pub fn add(&mut self, bookmark: Bookmark) -> usize {
+        match self.position(&bookmark.name) {
+            Some(index) => {
+                self.items[index] = bookmark;
+                index
+            }
+            None => {
+                self.items.push(bookmark);
+                self.items.len() - 1
+            }
+        }
+    }
+}
@@ -0,0 +1,1187 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{backend::{self, ErrorKind, RegistryBackend, RegistryKey}, backup, journal::{self, Journal}, regfile::{self, RegFile, RegFileVersion, RegOperation}, registry::{self, RegValue, Type, Value}, snapshot::{self, Snapshot}, treefile::{self, ImportMode, TreeFormat}};
+
+pub const COMMANDS: [&str; 10] = ["query", "add", "delete", "copy", "export", "import", "compare", "snapshot", "diff", "restore"];
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+/// Returned by `compare` and `diff` when the keys differ.
+pub const EXIT_DIFFERENT: i32 = 2;
+
+const NOT_FOUND: &str = "The system was unable to find the specified registry key or value.";
+const COMPLETED: &str = "The operation completed successfully.";
+
+const QUERY_USAGE: &str = "\
+regcli query <key> [/v <name> | /ve] [/s] [/f <data>] [/k] [/d] [/c] [/e] [/t <types>] [/se <separator>]
+
+  /v <name>   Query a single value.
+  /ve         Query the default value.
+  /s          Query all subkeys and their values.
+  /f <data>   Search key names, value names and data for a pattern, * matches everything.
+              With /v and no name, only value names are searched.
+  /k          Search key names only.
+  /d          Search data only.
+  /c          Make the search case-sensitive.
+  /e          Only return exact matches.
+  /t <types>  Comma-separated value types to return, e.g. REG_SZ,REG_DWORD.
+  /se <sep>   Separator for REG_MULTI_SZ entries in the output, \\0 by default.";
+
+const ADD_USAGE: &str = "\
+regcli add <key> [/v <name> | /ve] [/t <type>] [/s <separator>] [/d <data>] [/f]
+
+  /v <name>   Name of the value to add.
+  /ve         Add the default value.
+  /t <type>   Type of the value, REG_SZ by default.
+  /s <sep>    Separator between REG_MULTI_SZ entries in /d, \\0 by default.
+  /d <data>   Data of the value. REG_BINARY takes hex bytes, numbers can be decimal or 0x hex.
+  /f          Overwrite an existing value without asking.
+
+Without /v or /ve only the key is created.";
+
+const DELETE_USAGE: &str = "\
+regcli delete <key> [/v <name> | /ve | /va] [/f]
+
+  /v <name>   Delete a single value.
+  /ve         Delete the default value.
+  /va         Delete all values of the key.
+  /f          Delete without asking.
+
+Without /v, /ve or /va the key is deleted with everything below it.";
+
+const COPY_USAGE: &str = "\
+regcli copy <source> <destination> [/s] [/f]
+
+  /s          Copy all subkeys and their values too.
+  /f          Overwrite existing values without asking.";
+
+const EXPORT_USAGE: &str = "\
+regcli export <key> <file> [/y] [/ts]
+
+  /y          Overwrite an existing file without asking.
+  /ts         Include the last write time of every key, JSON and YAML only.
+
+The key is written with everything below it, as JSON or YAML when the file
+name ends in .json, .yaml or .yml and as a REGEDIT5 file otherwise.";
+
+const IMPORT_USAGE: &str = "\
+regcli import <file> [/replace]
+
+  /replace    Remove the values and subkeys that aren't in the file, JSON and YAML only.
+
+Applies a REGEDIT4 or REGEDIT5 file, or a JSON or YAML export which is merged
+into the key it was exported from.";
+
+const COMPARE_USAGE: &str = "\
+regcli compare <key1> <key2> [/v <name> | /ve] [/s] [/oa | /od | /os | /on]
+
+  /v <name>   Compare a single value.
+  /ve         Compare the default value.
+  /s          Compare all subkeys and their values too.
+  /oa         Output all differences and matches.
+  /od         Output only differences, the default.
+  /os         Output only matches.
+  /on         Output nothing.
+
+Exits with 0 when the keys are identical and 2 when they differ.";
+
+const SNAPSHOT_USAGE: &str = "\
+regcli snapshot <key> <file> [/y]
+
+  /y          Overwrite an existing file without asking.
+
+Saves the key with everything below it, to be compared later with diff.";
+
+const DIFF_USAGE: &str = "\
+regcli diff <snapshot> [<snapshot>] [/reg <file>]
+
+  /reg <file> Write a REGEDIT5 file that makes the same changes.
+
+Lists what changed from the first snapshot to the second one, or to the key
+as it is now. Exits with 0 when nothing changed and 2 otherwise.";
+
+const RESTORE_USAGE: &str = "\
+regcli restore [<backup>] [/f]
+
+  /f          Restore without asking.
+
+Without a backup, lists the backups taken before keys were deleted and values
+overwritten, newest first. A backup is picked by its number in that list, its
+file name or its path. Restoring adds back what's missing and leaves anything
+added since alone.
+
+Backups are kept in REGCLI_BACKUP_DIR, or the backups directory of the config.";
+
+pub fn is_command(name: &str) -> bool {
+    COMMANDS.iter().any(|command| command.eq_ignore_ascii_case(name))
+}
+
+#[derive(Debug)]
+enum CliError {
+    /// The arguments don't make sense, printed along with a pointer to the usage.
+    Syntax(String),
+    Failed(String),
+}
+
+impl From<backend::Error> for CliError {
+    fn from(err: backend::Error) -> Self {
+        match err.kind() {
+            ErrorKind::NotFound => Self::Failed(NOT_FOUND.into()),
+            ErrorKind::AccessDenied => Self::Failed("Access is denied.".into()),
+            _ => Self::Failed(err.message().to_owned()),
+        }
+    }
+}
+
+type CliResult = Result<i32, CliError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    Flag,
+    Value,
+    /// Takes the next argument unless it's another switch.
+    OptionalValue,
+}
+
+/// Arguments in reg.exe style. Switches start with `/` or `-` and are matched
+/// ignoring case, anything that isn't a known switch is positional, so paths
+/// like `/tmp/out.reg` still work.
+struct Args {
+    positional: Vec<String>,
+    switches: Vec<(&'static str, Option<String>)>,
+}
+
+impl Args {
+    fn find_switch(specs: &[(&'static str, Arg)], arg: &str) -> Option<(&'static str, Arg)> {
+        let name = arg.strip_prefix('/').or_else(|| arg.strip_prefix('-'))?;
+
+        specs.iter().find(|(spec, _)| spec.eq_ignore_ascii_case(name)).copied()
+    }
+
+    fn parse(args: &[String], specs: &[(&'static str, Arg)]) -> Result<Self, CliError> {
+        let mut result = Self { positional: Vec::new(), switches: Vec::new() };
+        let mut args = args.iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let Some((name, kind)) = Self::find_switch(specs, arg) else {
+                result.positional.push(arg.clone());
+                continue;
+            };
+
+            if result.has(name) {
+                return Err(CliError::Syntax(format!("/{name} is given more than once")));
+            }
+
+            let value = match kind {
+                Arg::Flag => None,
+                Arg::Value => Some(args.next().ok_or_else(|| CliError::Syntax(format!("/{name} expects a value")))?.clone()),
+                Arg::OptionalValue => args.next_if(|next| Self::find_switch(specs, next).is_none()).cloned(),
+            };
+
+            result.switches.push((name, value));
+        }
+
+        Ok(result)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.switches.iter().any(|(switch, _)| *switch == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.switches.iter().find(|(switch, _)| *switch == name).and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Fails when more than one of the switches is given.
+    fn exclusive(&self, names: &[&str]) -> Result<(), CliError> {
+        let given: Vec<_> = names.iter().filter(|name| self.has(name)).map(|name| format!("/{name}")).collect();
+
+        match given.len() {
+            0 | 1 => Ok(()),
+            _ => Err(CliError::Syntax(format!("{} can't be used together", given.join(" and ")))),
+        }
+    }
+
+    fn positional(&self, count: usize, names: &str) -> Result<&[String], CliError> {
+        match self.positional.len() == count {
+            true => Ok(&self.positional),
+            false => Err(CliError::Syntax(format!("expected {names}"))),
+        }
+    }
+
+    /// The value name picked with `/v <name>` or `/ve`.
+    fn value_name(&self) -> Result<Option<String>, CliError> {
+        self.exclusive(&["v", "ve"])?;
+
+        match (self.has("v"), self.value("v")) {
+            (true, Some(name)) => Ok(Some(name.to_owned())),
+            (true, None) => Err(CliError::Syntax("/v expects a value name".into())),
+            (false, _) => Ok(self.has("ve").then(String::new)),
+        }
+    }
+}
+
+fn parse_type(s: &str) -> Result<Type, CliError> {
+    s.trim().to_ascii_uppercase().parse().map_err(|_| CliError::Syntax(format!("{s} isn't a registry value type")))
+}
+
+/// Formats data the way reg.exe prints it.
+fn format_data(value: &Value, separator: &str) -> String {
+    match RegValue::from(value) {
+        RegValue::Sz(s) | RegValue::ExpandSz(s) | RegValue::Link(s) => s,
+        RegValue::MultiSz(strings) => strings.join(separator),
+        RegValue::Dword(n) | RegValue::DwordBE(n) => format!("{n:#x}"),
+        RegValue::Qword(n) => format!("{n:#x}"),
+        RegValue::Unknown { ty: Type::String | Type::ExpandString, .. } => registry::decode_sz(value),
+        RegValue::Unknown { ty: Type::MultiString, .. } => registry::decode_multi_sz(value).join(separator),
+        _ => value.iter().map(|byte| format!("{byte:02X}")).collect(),
+    }
+}
+
+fn format_value(name: &str, value: &Value, separator: &str) -> String {
+    format!("{}    {}    {}", registry::display_name(name), registry::type_to_str(value.ty()), format_data(value, separator))
+}
+
+fn print(text: impl AsRef<str>) {
+    // a closed pipe isn't worth a panic
+    let _ = writeln!(io::stdout().lock(), "{}", text.as_ref());
+}
+
+fn ask(question: &str) -> String {
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "{question} ");
+    let _ = stdout.flush();
+
+    // nothing to read counts as no, on a line of its own
+    let mut answer = String::new();
+    if let Ok(0) | Err(_) = io::stdin().lock().read_line(&mut answer) {
+        let _ = writeln!(stdout);
+    }
+
+    answer.trim().to_ascii_lowercase()
+}
+
+fn confirm(question: &str) -> bool {
+    matches!(ask(question).as_str(), "y" | "yes")
+}
+
+fn cancelled() -> CliError {
+    CliError::Failed("The operation was cancelled.".into())
+}
+
+/// Resolves a path given on the command line to the full path of the key,
+/// with the root spelled out, and its components below the root.
+fn resolve(backend: &dyn RegistryBackend, path: &str) -> Result<(String, String, Vec<String>), CliError> {
+    let (root, components) = backend::resolve_path(backend, path)?;
+
+    let full = std::iter::once(root.as_str()).chain(components.iter().map(|c| c.as_str())).collect::<Vec<_>>().join("\\");
+    Ok((full, root, components))
+}
+
+fn open(backend: &dyn RegistryBackend, path: &str) -> Result<(String, Box<dyn RegistryKey>), CliError> {
+    let (full, root, components) = resolve(backend, path)?;
+    let key = backend.open_root(&root)?;
+
+    match components.is_empty() {
+        true => Ok((full, key)),
+        false => Ok((full, registry::read_key(key.as_ref(), &components.join("\\"))?)),
+    }
+}
+
+fn is_same_or_below(path: &str, parent: &str) -> bool {
+    let path = backend::split_path(path);
+    let parent = backend::split_path(parent);
+
+    path.len() >= parent.len() && path.iter().zip(&parent).all(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+struct Pattern {
+    text: String,
+    case_sensitive: bool,
+    exact: bool,
+}
+
+impl Pattern {
+    fn matches(&self, s: &str) -> bool {
+        if self.text == "*" && !self.exact {
+            return true;
+        }
+
+        let (text, s) = match self.case_sensitive {
+            true => (self.text.clone(), s.to_owned()),
+            false => (self.text.to_lowercase(), s.to_lowercase()),
+        };
+
+        match self.exact {
+            true => s == text,
+            false => s.contains(&text),
+        }
+    }
+}
+
+struct Query {
+    value: Option<String>,
+    types: Option<Vec<Type>>,
+    pattern: Option<Pattern>,
+    search_keys: bool,
+    search_names: bool,
+    search_data: bool,
+    recursive: bool,
+    separator: String,
+    matches: usize,
+}
+
+impl Query {
+    fn is_filtered(&self) -> bool {
+        self.value.is_some() || self.types.is_some() || self.pattern.is_some()
+    }
+
+    fn value_matches(&self, name: &str, value: &Value) -> bool {
+        if self.value.as_ref().is_some_and(|wanted| !wanted.eq_ignore_ascii_case(name)) {
+            return false;
+        }
+        if self.types.as_ref().is_some_and(|types| !types.contains(&value.ty())) {
+            return false;
+        }
+
+        match &self.pattern {
+            Some(pattern) => (self.search_names && pattern.matches(name)) || (self.search_data && pattern.matches(&format_data(value, &self.separator))),
+            None => true,
+        }
+    }
+
+    fn key_matches(&self, name: &str) -> bool {
+        self.search_keys && self.value.is_none() && self.pattern.as_ref().is_some_and(|pattern| pattern.matches(name))
+    }
+
+    fn print_key(&mut self, key: &dyn RegistryKey, path: &str, name_matches: bool) -> Result<(), CliError> {
+        let values: Vec<_> = registry::read_values(key)?
+            .into_iter()
+            .filter(|(name, value)| self.value_matches(name, value))
+            .collect();
+
+        if values.is_empty() && !name_matches && self.is_filtered() {
+            return Ok(());
+        }
+
+        print("");
+        print(path);
+        for (name, value) in &values {
+            print(format!("    {}", format_value(name, value, &self.separator)));
+        }
+
+        self.matches += values.len() + usize::from(name_matches);
+        Ok(())
+    }
+
+    fn walk(&mut self, key: &dyn RegistryKey, path: &str, name_matches: bool) -> Result<(), CliError> {
+        self.print_key(key, path, name_matches)?;
+
+        for name in registry::read_subkeys(key)? {
+            // keys that can't be read are skipped, like the search in the browser does
+            let Ok(subkey) = registry::read_key(key, &name) else { continue; };
+
+            let name_matches = self.key_matches(&name);
+            if !self.is_filtered() {
+                self.matches += 1;
+            }
+
+            self.walk(subkey.as_ref(), &format!("{path}\\{name}"), name_matches)?;
+        }
+
+        Ok(())
+    }
+
+    fn list(&mut self, key: &dyn RegistryKey, path: &str) -> Result<(), CliError> {
+        self.print_key(key, path, false)?;
+
+        if self.value.is_some() {
+            return Ok(());
+        }
+
+        let subkeys = registry::read_subkeys(key)?;
+
+        if self.pattern.is_none() {
+            if !subkeys.is_empty() {
+                print("");
+            }
+            for name in subkeys {
+                print(format!("{path}\\{name}"));
+            }
+            return Ok(());
+        }
+
+        for name in subkeys {
+            if !self.key_matches(&name) {
+                continue;
+            }
+
+            print("");
+            print(format!("{path}\\{name}"));
+            self.matches += 1;
+        }
+
+        Ok(())
+    }
+}
+
+fn query(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let specs = [
+        ("v", Arg::OptionalValue), ("ve", Arg::Flag), ("s", Arg::Flag), ("f", Arg::Value), ("k", Arg::Flag),
+        ("d", Arg::Flag), ("c", Arg::Flag), ("e", Arg::Flag), ("t", Arg::Value), ("se", Arg::Value),
+    ];
+    let args = Args::parse(args, &specs)?;
+    let [path] = args.positional(1, "a key")? else { unreachable!() };
+
+    // /f with a bare /v searches value names
+    let value = match args.has("f") && args.has("v") && args.value("v").is_none() {
+        true => None,
+        false => args.value_name()?,
+    };
+
+    let pattern = args.value("f").map(|text| Pattern { text: text.to_owned(), case_sensitive: args.has("c"), exact: args.has("e") });
+    if pattern.is_none() && ["k", "d", "c", "e"].iter().any(|name| args.has(name)) {
+        return Err(CliError::Syntax("/k, /d, /c and /e can only be used with /f".into()));
+    }
+
+    let types = match args.value("t") {
+        Some(types) => Some(types.split(',').map(parse_type).collect::<Result<Vec<_>, _>>()?),
+        None => None,
+    };
+
+    // without any of /k, /v or /d everything is searched
+    let everywhere = !args.has("k") && !args.has("v") && !args.has("d");
+    let mut query = Query {
+        value,
+        types,
+        pattern,
+        search_keys: everywhere || args.has("k"),
+        search_names: everywhere || args.has("v"),
+        search_data: everywhere || args.has("d"),
+        recursive: args.has("s"),
+        separator: args.value("se").unwrap_or("\\0").to_owned(),
+        matches: 0,
+    };
+
+    let (path, key) = open(backend, path)?;
+    match query.recursive {
+        true => query.walk(key.as_ref(), &path, false)?,
+        false => query.list(key.as_ref(), &path)?,
+    };
+
+    let summary = query.recursive || query.pattern.is_some();
+    if summary {
+        print("");
+        print(format!("End of search: {} match(es) found.", query.matches));
+    }
+
+    match (query.matches, query.value.is_some() || query.pattern.is_some()) {
+        (0, true) if summary => Ok(EXIT_FAILURE),
+        (0, true) => Err(CliError::Failed(NOT_FOUND.into())),
+        _ => Ok(EXIT_SUCCESS),
+    }
+}
+
+fn add(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let specs = [("v", Arg::Value), ("ve", Arg::Flag), ("t", Arg::Value), ("s", Arg::Value), ("d", Arg::Value), ("f", Arg::Flag)];
+    let args = Args::parse(args, &specs)?;
+    let [path] = args.positional(1, "a key")? else { unreachable!() };
+
+    let name = args.value_name()?;
+    let ty = args.value("t").map(parse_type).transpose()?.unwrap_or(Type::String);
+
+    if name.is_none() && ["t", "s", "d"].iter().any(|switch| args.has(switch)) {
+        return Err(CliError::Syntax("/t, /s and /d need a value picked with /v or /ve".into()));
+    }
+    if args.has("s") && ty != Type::MultiString {
+        return Err(CliError::Syntax("/s can only be used with REG_MULTI_SZ".into()));
+    }
+
+    let separator = args.value("s").unwrap_or("\\0");
+    if separator.is_empty() {
+        return Err(CliError::Syntax("/s expects a separator".into()));
+    }
+
+    // the value editor takes one REG_MULTI_SZ entry per line
+    let data = match (ty, args.value("d")) {
+        (Type::MultiString, Some(data)) => data.split(separator).collect::<Vec<_>>().join("\n"),
+        (Type::U32 | Type::U32BigEndian | Type::U64, None) => "0".to_owned(),
+        (_, data) => data.unwrap_or_default().to_owned(),
+    };
+    let value = registry::encode_value(ty, &data).map_err(|err| CliError::Failed(registry::parser_error_message(err)))?;
+    let (full, _, _) = resolve(backend, path)?;
+    let mut operations = vec![RegOperation::CreateKey { path: full.clone() }];
+    let mut description = format!("add the key {full}");
+
+    if let Some(name) = name {
+        let exists = backend::open_path(backend, &full).and_then(|key| key.get_value(&name)).is_ok();
+        if exists && !args.has("f") && !confirm(&format!("Value {} exists, overwrite (Yes/No)?", registry::display_name(&name))) {
+            return Err(cancelled());
+        }
+
+        description = format!("add the value {} in {full}", registry::display_name(&name));
+        operations.push(RegOperation::SetValue { path: full, name, value });
+    }
+
+    apply(backend, operations, description)?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+/// Backs up what the operations delete or overwrite, reporting where the
+/// backups went, then applies them. Nothing is changed when the backup fails.
+/// What changed goes in the undo journal, to be undone in the editor.
+fn apply(backend: &dyn RegistryBackend, operations: Vec<RegOperation>, description: String) -> Result<(), CliError> {
+    let file = RegFile { version: RegFileVersion::Regedit5, operations };
+    let backups = backup::save_changes(backend, &file).map_err(|err| CliError::Failed(format!("Can't back up before the change: {}", err.message())))?;
+
+    for backup in backups {
+        print(format!("Backup saved to {}.", backup.display()));
+    }
+
+    let (operation, result) = journal::apply(backend, &file, description);
+    if let Some(operation) = operation
+        && let Err(err) = Journal::load(Journal::default_file(backend)).and_then(|mut journal| journal.record(operation))
+    {
+        eprintln!("WARNING: Can't update the undo journal: {}", err.message());
+    }
+
+    result?;
+    Ok(())
+}
+
+fn delete(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let specs = [("v", Arg::Value), ("ve", Arg::Flag), ("va", Arg::Flag), ("f", Arg::Flag)];
+    let args = Args::parse(args, &specs)?;
+    let [path] = args.positional(1, "a key")? else { unreachable!() };
+
+    args.exclusive(&["v", "ve", "va"])?;
+    let name = args.value_name()?;
+    let force = args.has("f");
+
+    let (full, key) = open(backend, path)?;
+
+    if let Some(name) = name {
+        key.get_value(&name)?;
+
+        if !force && !confirm(&format!("Delete the registry value {} (Yes/No)?", registry::display_name(&name))) {
+            return Err(cancelled());
+        }
+
+        let description = format!("delete the value {} in {full}", registry::display_name(&name));
+        apply(backend, vec![RegOperation::DeleteValue { path: full, name }], description)?;
+    } else if args.has("va") {
+        if !force && !confirm(&format!("Delete all values under the registry key {full} (Yes/No)?")) {
+            return Err(cancelled());
+        }
+
+        let operations = registry::read_values(key.as_ref())?.into_iter()
+            .map(|(name, _)| RegOperation::DeleteValue { path: full.clone(), name })
+            .collect();
+        apply(backend, operations, format!("delete the values in {full}"))?;
+    } else {
+        let (_, root, components) = resolve(backend, path)?;
+        if components.is_empty() {
+            return Err(CliError::Failed(format!("Can't delete the root key {root}.")));
+        }
+
+        if !force && !confirm(&format!("Permanently delete the registry key {full} (Yes/No)?")) {
+            return Err(cancelled());
+        }
+
+        apply(backend, vec![RegOperation::DeleteKey { path: full.clone() }], format!("delete the key {full}"))?;
+    }
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+fn copy(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let specs = [("s", Arg::Flag), ("f", Arg::Flag)];
+    let args = Args::parse(args, &specs)?;
+    let [source, target] = args.positional(2, "a source and a destination key")? else { unreachable!() };
+
+    let recursive = args.has("s");
+    let (source_path, source) = open(backend, source)?;
+    let (target_path, _, _) = resolve(backend, target)?;
+
+    if source_path.eq_ignore_ascii_case(&target_path) {
+        return Err(CliError::Failed("The source and destination keys are the same.".into()));
+    }
+    if recursive && is_same_or_below(&target_path, &source_path) {
+        return Err(CliError::Failed("Can't copy a key into one of its own subkeys.".into()));
+    }
+
+    let target = backend::open_path(backend, &target_path).ok();
+
+    let force = args.has("f");
+    let mut all = false;
+    let mut overwrite = |path: &str, name: &str| {
+        if force || all {
+            return true;
+        }
+
+        let key = match path.is_empty() {
+            true => target_path.clone(),
+            false => format!("{target_path}\\{path}"),
+        };
+
+        match ask(&format!("Value {} exists in {key}, overwrite (Yes/No/All)?", registry::display_name(name))).as_str() {
+            "y" | "yes" => true,
+            "a" | "all" => {
+                all = true;
+                true
+            }
+            _ => false,
+        }
+    };
+
+    let operations = regfile::copy_operations(source.as_ref(), target.as_deref(), &target_path, recursive, &mut overwrite)?;
+    apply(backend, operations, format!("copy {source_path} to {target_path}"))?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+fn export(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let args = Args::parse(args, &[("y", Arg::Flag), ("ts", Arg::Flag)])?;
+    let [path, file] = args.positional(2, "a key and a file")? else { unreachable!() };
+
+    let format = TreeFormat::from_path(file);
+    if format.is_none() && args.has("ts") {
+        return Err(CliError::Syntax("/ts only applies to JSON and YAML files".into()));
+    }
+
+    let (path, key) = open(backend, path)?;
+
+    if !args.has("y") && std::path::Path::new(file).exists() && !confirm(&format!("File {file} already exists. Overwrite (Yes/No)?")) {
+        return Err(cancelled());
+    }
+
+    let bytes = match format {
+        Some(format) => treefile::export(key.as_ref(), &path, true, args.has("ts"), format)?.0.into_bytes(),
+        None => regfile::encode_text(&regfile::export(key.as_ref(), &path, true)?.0),
+    };
+    std::fs::write(file, bytes).map_err(|err| CliError::Failed(format!("Can't write {file}: {err}")))?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+fn import(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let args = Args::parse(args, &[("replace", Arg::Flag)])?;
+    let [file] = args.positional(1, "a file")? else { unreachable!() };
+
+    let format = TreeFormat::from_path(file);
+    if format.is_none() && args.has("replace") {
+        return Err(CliError::Syntax("/replace only applies to JSON and YAML files".into()));
+    }
+
+    let bytes = std::fs::read(file).map_err(|err| CliError::Failed(format!("Can't read {file}: {err}")))?;
+    let parsed = match format {
+        Some(format) => {
+            let tree = treefile::parse(&regfile::decode_text(&bytes), format).map_err(|err| CliError::Failed(format!("{file}, {}", err.message())))?;
+            let mode = match args.has("replace") {
+                true => ImportMode::Replace,
+                false => ImportMode::Merge,
+            };
+
+            treefile::operations(backend, &tree, mode)?
+        }
+        None => regfile::parse(&bytes).map_err(|err| CliError::Failed(format!("{file}, {err}")))?,
+    };
+
+    apply(backend, parsed.operations, format!("import {file}"))?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOutput {
+    All,
+    Differences,
+    Same,
+    Nothing,
+}
+
+struct Compare {
+    value: Option<String>,
+    recursive: bool,
+    output: CompareOutput,
+    different: bool,
+}
+
+impl Compare {
+    fn report(&mut self, same: bool, line: String) {
+        let show = match self.output {
+            CompareOutput::All => true,
+            CompareOutput::Differences => !same,
+            CompareOutput::Same => same,
+            CompareOutput::Nothing => false,
+        };
+
+        if show {
+            print(line);
+        }
+        self.different |= !same;
+    }
+
+    fn report_value(&mut self, marker: &str, path: &str, name: &str, value: &Value) {
+        let line = format!("{marker} Value: {path}  {}  {}  {}", registry::display_name(name), registry::type_to_str(value.ty()), format_data(value, "\\0"));
+        self.report(marker == "=", line);
+    }
+
+    fn compare_values(&mut self, first: &dyn RegistryKey, first_path: &str, second: &dyn RegistryKey, second_path: &str) -> Result<(), CliError> {
+        let wanted = |name: &str| self.value.as_ref().is_none_or(|wanted| wanted.eq_ignore_ascii_case(name));
+
+        let first_values: Vec<_> = registry::read_values(first)?.into_iter().filter(|(name, _)| wanted(name)).collect();
+        let second_values: Vec<_> = registry::read_values(second)?.into_iter().filter(|(name, _)| wanted(name)).collect();
+
+        if let Some(name) = &self.value
+            && first_values.is_empty()
+            && second_values.is_empty()
+        {
+            return Err(CliError::Failed(format!("{NOT_FOUND} ({})", registry::display_name(name))));
+        }
+
+        for (name, value) in &first_values {
+            match second_values.iter().find(|(other, _)| other.eq_ignore_ascii_case(name)) {
+                Some((_, other)) if other == value => self.report_value("=", first_path, name, value),
+                Some((other_name, other)) => {
+                    self.report_value("<", first_path, name, value);
+                    self.report_value(">", second_path, other_name, other);
+                }
+                None => self.report_value("<", first_path, name, value),
+            };
+        }
+
+        for (name, value) in &second_values {
+            if !first_values.iter().any(|(other, _)| other.eq_ignore_ascii_case(name)) {
+                self.report_value(">", second_path, name, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compare_keys(&mut self, first: &dyn RegistryKey, first_path: &str, second: &dyn RegistryKey, second_path: &str) -> Result<(), CliError> {
+        self.compare_values(first, first_path, second, second_path)?;
+
+        if self.value.is_some() {
+            return Ok(());
+        }
+
+        let first_subkeys = registry::read_subkeys(first)?;
+        let second_subkeys = registry::read_subkeys(second)?;
+
+        for name in &first_subkeys {
+            let Some(other) = second_subkeys.iter().find(|other| other.eq_ignore_ascii_case(name)) else {
+                self.report(false, format!("< Key: {first_path}\\{name}"));
+                continue;
+            };
+
+            self.report(true, format!("= Key: {first_path}\\{name}"));
+
+            if self.recursive {
+                let first = registry::read_key(first, name)?;
+                let second = registry::read_key(second, other)?;
+
+                self.compare_keys(first.as_ref(), &format!("{first_path}\\{name}"), second.as_ref(), &format!("{second_path}\\{other}"))?;
+            }
+        }
+
+        for name in &second_subkeys {
+            if !first_subkeys.iter().any(|other| other.eq_ignore_ascii_case(name)) {
+                self.report(false, format!("> Key: {second_path}\\{name}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn compare(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let specs = [("v", Arg::Value), ("ve", Arg::Flag), ("s", Arg::Flag), ("oa", Arg::Flag), ("od", Arg::Flag), ("os", Arg::Flag), ("on", Arg::Flag)];
+    let args = Args::parse(args, &specs)?;
+    let [first, second] = args.positional(2, "two keys")? else { unreachable!() };
+
+    args.exclusive(&["oa", "od", "os", "on"])?;
+    let output = if args.has("oa") {
+        CompareOutput::All
+    } else if args.has("os") {
+        CompareOutput::Same
+    } else if args.has("on") {
+        CompareOutput::Nothing
+    } else {
+        CompareOutput::Differences
+    };
+
+    let mut compare = Compare { value: args.value_name()?, recursive: args.has("s"), output, different: false };
+
+    let (first_path, first) = open(backend, first)?;
+    let (second_path, second) = open(backend, second)?;
+    compare.compare_keys(first.as_ref(), &first_path, second.as_ref(), &second_path)?;
+
+    print("");
+    match compare.different {
+        true => print("Result Compared: Different"),
+        false => print("Result Compared: Identical"),
+    };
+    print(COMPLETED);
+
+    match compare.different {
+        true => Ok(EXIT_DIFFERENT),
+        false => Ok(EXIT_SUCCESS),
+    }
+}
+
+fn take_snapshot(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let args = Args::parse(args, &[("y", Arg::Flag)])?;
+    let [path, file] = args.positional(2, "a key and a file")? else { unreachable!() };
+
+    let snapshot = Snapshot::take(backend, path)?;
+
+    if !args.has("y") && std::path::Path::new(file).exists() && !confirm(&format!("File {file} already exists. Overwrite (Yes/No)?")) {
+        return Err(cancelled());
+    }
+    snapshot.save(file).map_err(|err| CliError::Failed(format!("Can't write {file}: {err}")))?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+fn load_snapshot(file: &str) -> Result<Snapshot, CliError> {
+    Snapshot::load(file).map_err(|err| CliError::Failed(format!("Can't read {file}: {err}")))
+}
+
+fn diff(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let args = Args::parse(args, &[("reg", Arg::Value)])?;
+
+    let (old, new) = match args.positional.as_slice() {
+        [old] => {
+            let old = load_snapshot(old)?;
+            let new = Snapshot::take(backend, &old.path)?;
+            (old, new)
+        }
+        [old, new] => (load_snapshot(old)?, load_snapshot(new)?),
+        _ => return Err(CliError::Syntax("expected one or two snapshot files".into())),
+    };
+
+    let describe = |value: &Value| format!("{}  {}", registry::type_to_str(value.ty()), format_data(value, "\\0"));
+
+    let differences = snapshot::diff(&old, &new);
+    for difference in &differences {
+        let symbol = difference.kind.symbol();
+        let path = difference.key_path();
+
+        match (&difference.value, &difference.old, &difference.new) {
+            (None, _, _) => print(format!("{symbol} Key: {path}")),
+            (Some(name), Some(old), Some(new)) => print(format!("{symbol} Value: {path}  {}  {} -> {}", registry::display_name(name), describe(old), describe(new))),
+            (Some(name), Some(value), None) | (Some(name), None, Some(value)) => print(format!("{symbol} Value: {path}  {}  {}", registry::display_name(name), describe(value))),
+            (Some(_), None, None) => {}
+        }
+    }
+
+    if let Some(file) = args.value("reg") {
+        std::fs::write(file, regfile::encode_text(&snapshot::patch(&differences))).map_err(|err| CliError::Failed(format!("Can't write {file}: {err}")))?;
+    }
+
+    print("");
+    print(format!("Result Compared: {} difference(s) found.", differences.len()));
+    print(COMPLETED);
+
+    match differences.is_empty() {
+        true => Ok(EXIT_SUCCESS),
+        false => Ok(EXIT_DIFFERENT),
+    }
+}
+
+fn restore(backend: &dyn RegistryBackend, args: &[String]) -> CliResult {
+    let args = Args::parse(args, &[("f", Arg::Flag)])?;
+
+    let backups = backup::list().map_err(|err| CliError::Failed(format!("Can't list the backups: {}", err.message())))?;
+    let chosen = match args.positional.as_slice() {
+        [] => {
+            for (i, backup) in backups.iter().enumerate() {
+                print(format!("{:>4}  {}  {}  ({})", i + 1, backup.taken(), backup.describe(), backup.file_name()));
+            }
+
+            print("");
+            print(format!("{} backup(s) found.", backups.len()));
+            return Ok(EXIT_SUCCESS);
+        }
+        [name] => match name.parse::<usize>() {
+            Ok(number) => backups.into_iter().nth(number.wrapping_sub(1)).ok_or_else(|| CliError::Failed(format!("There's no backup {number}.")))?,
+            Err(_) => backup::find(name).map_err(|err| CliError::Failed(format!("Can't read {name}: {}", err.message())))?,
+        },
+        _ => return Err(CliError::Syntax("expected at most one backup".into())),
+    };
+
+    if !args.has("f") && !confirm(&format!("Restore the {} from {} (Yes/No)?", chosen.describe(), chosen.taken())) {
+        return Err(cancelled());
+    }
+
+    apply(backend, chosen.operations(backend)?.operations, format!("restore the {}", chosen.describe()))?;
+
+    print(COMPLETED);
+    Ok(EXIT_SUCCESS)
+}
+
+fn usage(command: &str) -> &'static str {
+    match command {
+        "query" => QUERY_USAGE,
+        "add" => ADD_USAGE,
+        "delete" => DELETE_USAGE,
+        "copy" => COPY_USAGE,
+        "export" => EXPORT_USAGE,
+        "import" => IMPORT_USAGE,
+        "snapshot" => SNAPSHOT_USAGE,
+        "diff" => DIFF_USAGE,
+        "restore" => RESTORE_USAGE,
+        _ => COMPARE_USAGE,
+    }
+}
+
+/// Runs one of the `COMMANDS` with reg.exe compatible arguments, printing
+/// results on stdout and errors on stderr. Returns the exit code.
+pub fn run(backend: &dyn RegistryBackend, command: &str, args: &[String]) -> i32 {
+    let command = command.to_ascii_lowercase();
+
+    if args.iter().any(|arg| arg == "/?" || arg == "-?") {
+        print(usage(&command));
+        return EXIT_SUCCESS;
+    }
+
+    for warning in backend.warnings() {
+        eprintln!("WARNING: {warning}");
+    }
+
+    let result = match command.as_str() {
+        "query" => query(backend, args),
+        "add" => add(backend, args),
+        "delete" => delete(backend, args),
+        "copy" => copy(backend, args),
+        "export" => export(backend, args),
+        "import" => import(backend, args),
+        "compare" => compare(backend, args),
+        "snapshot" => take_snapshot(backend, args),
+        "diff" => diff(backend, args),
+        "restore" => restore(backend, args),
+        _ => Err(CliError::Syntax(format!("unknown command '{command}'"))),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(CliError::Syntax(message)) => {
+            eprintln!("ERROR: Invalid syntax, {message}.");
+            eprintln!("Type \"regcli {command} /?\" for usage.");
+            EXIT_FAILURE
+        }
+        Err(CliError::Failed(message)) => {
+            eprintln!("ERROR: {message}");
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::memory::MemoryBackend, config::TestConfigDir};
+
+    const FIXTURE: &str = r#"{
+        "HKEY_CURRENT_USER": {
+            "keys": {
+                "App": {
+                    "values": { "": "default", "Name": "regcli", "Count": 2 },
+                    "keys": { "Plugins": { "values": { "Path": "C:\\plugins" } } }
+                },
+                "Copy": {
+                    "values": { "Name": "regcli", "Count": 3 }
+                }
+            }
+        }
+    }"#;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn run_with(backend: &MemoryBackend, command: &str, args: &[&str]) -> i32 {
+        run(backend, command, &strings(args))
+    }
+
+    fn value(backend: &MemoryBackend, path: &str, name: &str) -> Option<Value> {
+        backend::open_path(backend, path).and_then(|key| key.get_value(name)).ok()
+    }
+
+    fn subkeys(backend: &MemoryBackend, path: &str) -> Vec<String> {
+        registry::read_subkeys(backend::open_path(backend, path).unwrap().as_ref()).unwrap()
+    }
+
+    #[test]
+    fn optional_values_stop_at_the_next_switch() {
+        let specs = [("v", Arg::OptionalValue), ("f", Arg::Value), ("s", Arg::Flag)];
+
+        let args = Args::parse(&strings(&["HKCU\\App", "/v", "/s"]), &specs).unwrap();
+        assert!(args.has("v") && args.has("s"));
+        assert_eq!(args.value("v"), None);
+
+        let args = Args::parse(&strings(&["HKCU\\App", "-V", "Name", "/F", "*"]), &specs).unwrap();
+        assert_eq!(args.value("v"), Some("Name"));
+        assert_eq!(args.value("f"), Some("*"));
+        assert_eq!(args.positional, ["HKCU\\App"]);
+    }
+
+    #[test]
+    fn switches_are_only_given_once() {
+        let specs = [("s", Arg::Flag), ("f", Arg::Value)];
+
+        assert!(matches!(Args::parse(&strings(&["HKCU", "/s", "/S"]), &specs), Err(CliError::Syntax(_))));
+        assert!(matches!(Args::parse(&strings(&["HKCU", "/f"]), &specs), Err(CliError::Syntax(_))));
+    }
+
+    #[test]
+    fn unknown_switches_are_positional() {
+        let args = Args::parse(&strings(&["HKCU\\App", "/tmp/x.reg", "/y"]), &[("y", Arg::Flag)]).unwrap();
+
+        assert_eq!(args.positional, ["HKCU\\App", "/tmp/x.reg"]);
+        assert!(args.has("y"));
+    }
+
+    #[test]
+    fn value_names_are_exclusive() {
+        let specs = [("v", Arg::Value), ("ve", Arg::Flag)];
+        let value_name = |args: &[&str]| Args::parse(&strings(args), &specs).unwrap().value_name();
+
+        assert_eq!(value_name(&["/v", "Name"]).ok(), Some(Some("Name".to_owned())));
+        assert_eq!(value_name(&["/ve"]).ok(), Some(Some(String::new())));
+        assert_eq!(value_name(&[]).ok(), Some(None));
+        assert!(matches!(value_name(&["/v", "Name", "/ve"]), Err(CliError::Syntax(_))));
+    }
+
+    #[test]
+    fn patterns_can_be_exact_and_case_sensitive() {
+        let pattern = |text: &str, case_sensitive, exact| Pattern { text: text.to_owned(), case_sensitive, exact };
+
+        assert!(pattern("*", false, false).matches("anything"));
+        assert!(!pattern("*", false, true).matches("anything"));
+        assert!(pattern("LI", false, false).matches("regcli"));
+        assert!(!pattern("LI", true, false).matches("regcli"));
+        assert!(!pattern("reg", false, true).matches("regcli"));
+        assert!(pattern("REGCLI", false, true).matches("regcli"));
+        assert!(!pattern("REGCLI", true, true).matches("regcli"));
+    }
+
+    #[test]
+    fn query_reports_what_was_found() {
+        let _config = TestConfigDir::create();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        assert_eq!(run_with(&backend, "query", &["HKCU\\App"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "query", &["HKCU\\App", "/v", "Name"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "query", &["HKCU\\App", "/v", "Missing"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "query", &["HKCU\\Missing"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "query", &["HKCU", "/f", "PLUGINS", "/s", "/d"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "query", &["HKCU", "/f", "PLUGINS", "/s", "/d", "/c"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "query", &["HKCU", "/k"]), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn add_creates_keys_and_overwrites_with_force() {
+        let _config = TestConfigDir::create();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        assert_eq!(run_with(&backend, "add", &["HKCU\\New\\Deep"]), EXIT_SUCCESS);
+        assert_eq!(subkeys(&backend, "HKCU\\New"), ["Deep"]);
+
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/v", "Count", "/t", "REG_DWORD", "/d", "0x10", "/f"]), EXIT_SUCCESS);
+        assert_eq!(value(&backend, "HKCU\\App", "Count"), Some(Value::new(Type::U32, 16u32.to_le_bytes().to_vec())));
+
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/v", "List", "/t", "REG_MULTI_SZ", "/s", ",", "/d", "a,b"]), EXIT_SUCCESS);
+        assert_eq!(registry::decode_multi_sz(&value(&backend, "HKCU\\App", "List").unwrap()), ["a", "b"]);
+
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/v", "Name", "/ve"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/d", "data"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/v", "Bad", "/t", "REG_DWORD", "/d", "x"]), EXIT_FAILURE);
+        assert_eq!(value(&backend, "HKCU\\App", "Bad"), None);
+    }
+
+    #[test]
+    fn delete_with_force_skips_the_prompt() {
+        let config = TestConfigDir::create();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        assert_eq!(run_with(&backend, "delete", &["HKCU\\App", "/ve", "/f"]), EXIT_SUCCESS);
+        assert_eq!(value(&backend, "HKCU\\App", ""), None);
+
+        assert_eq!(run_with(&backend, "delete", &["HKCU\\App", "/v", "Missing", "/f"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "delete", &["HKCU\\App", "/v", "Name", "/va", "/f"]), EXIT_FAILURE);
+
+        assert_eq!(run_with(&backend, "delete", &["HKCU\\App", "/va", "/f"]), EXIT_SUCCESS);
+        assert!(registry::read_values(backend::open_path(&backend, "HKCU\\App").unwrap().as_ref()).unwrap().is_empty());
+        assert_eq!(subkeys(&backend, "HKCU\\App"), ["Plugins"]);
+
+        assert_eq!(run_with(&backend, "delete", &["HKCU", "/f"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "delete", &["HKCU\\App", "/f"]), EXIT_SUCCESS);
+        assert_eq!(subkeys(&backend, "HKCU"), ["Copy"]);
+
+        // everything deleted was backed up first
+        assert!(config.path().join("backups").read_dir().unwrap().next().is_some());
+    }
+
+    #[test]
+    fn copy_merges_into_the_destination() {
+        let _config = TestConfigDir::create();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        assert_eq!(run_with(&backend, "copy", &["HKCU\\App", "HKCU\\App\\Plugins", "/s", "/f"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "copy", &["HKCU\\App", "hkcu\\app", "/f"]), EXIT_FAILURE);
+
+        assert_eq!(run_with(&backend, "copy", &["HKCU\\App", "HKCU\\Copy", "/s", "/f"]), EXIT_SUCCESS);
+        assert_eq!(value(&backend, "HKCU\\Copy", "Count"), value(&backend, "HKCU\\App", "Count"));
+        assert!(value(&backend, "HKCU\\Copy\\Plugins", "Path").is_some());
+
+        assert_eq!(run_with(&backend, "copy", &["HKCU\\App", "HKCU\\Flat", "/f"]), EXIT_SUCCESS);
+        assert!(value(&backend, "HKCU\\Flat", "Name").is_some());
+        assert!(subkeys(&backend, "HKCU\\Flat").is_empty());
+    }
+
+    #[test]
+    fn compare_exits_with_different() {
+        let _config = TestConfigDir::create();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\Copy"]), EXIT_DIFFERENT);
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\Copy", "/v", "Name"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\Copy", "/v", "Count", "/on"]), EXIT_DIFFERENT);
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\Copy", "/v", "Missing"]), EXIT_FAILURE);
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\App", "/s"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "compare", &["HKCU\\App", "HKCU\\Copy", "/oa", "/os"]), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn diff_exits_with_different() {
+        let config = TestConfigDir::create();
+        std::fs::create_dir_all(config.path()).unwrap();
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+
+        let before = config.path().join("before.snapshot").display().to_string();
+        let after = config.path().join("after.snapshot").display().to_string();
+        let reg = config.path().join("changes.reg").display().to_string();
+
+        assert_eq!(run_with(&backend, "snapshot", &["HKCU\\App", &before]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "diff", &[&before]), EXIT_SUCCESS);
+
+        assert_eq!(run_with(&backend, "add", &["HKCU\\App", "/v", "Name", "/d", "changed", "/f"]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "snapshot", &["HKCU\\App", &after]), EXIT_SUCCESS);
+        assert_eq!(run_with(&backend, "diff", &[&before, &after, "/reg", &reg]), EXIT_DIFFERENT);
+        assert_eq!(run_with(&backend, "diff", &[&before]), EXIT_DIFFERENT);
+
+        // the .reg file makes the same change again
+        let file = regfile::parse(&std::fs::read(&reg).unwrap()).unwrap();
+        assert!(matches!(file.operations.as_slice(), [RegOperation::CreateKey { .. }, RegOperation::SetValue { name, .. }] if name == "Name"));
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+/// Directory holding the files regcli keeps between sessions, e.g.
+/// `~/.config/regcli`. `REGCLI_CONFIG_DIR` points it somewhere else, such as
+/// a checkout shared by a team.
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_DIR.with_borrow(Clone::clone) {
+        return Some(dir);
+    }
+
+    if let Some(dir) = std::env::var_os("REGCLI_CONFIG_DIR") {
+        return Some(dir.into());
+    }
+
+    let base = match cfg!(windows) {
+        true => std::env::var_os("APPDATA").map(PathBuf::from),
+        false => std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))),
+    };
+
+    base.map(|base| base.join("regcli"))
+}
+
+/// Directory the automatic backups are written to, `REGCLI_BACKUP_DIR` or
+/// `backups` in the config directory.
+pub fn backup_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_DIR.with_borrow(Clone::clone) {
+        return Some(dir.join("backups"));
+    }
+
+    match std::env::var_os("REGCLI_BACKUP_DIR") {
+        Some(dir) => Some(dir.into()),
+        None => config_dir().map(|dir| dir.join("backups")),
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static TEST_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Points the config and backup directories of the current test's thread at
+/// a temporary directory, removed again when this is dropped.
+#[cfg(test)]
+pub struct TestConfigDir(PathBuf);
+
+#[cfg(test)]
+impl TestConfigDir {
+    pub fn create() -> Self {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let id = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("regcli-config-{}-{id}", std::process::id()));
+        TEST_DIR.set(Some(dir.clone()));
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestConfigDir {
+    fn drop(&mut self) {
+        TEST_DIR.set(None);
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,12 +1,16 @@
-use std::{collections::HashMap, usize};
+use std::{cell::Cell, collections::HashMap, rc::Rc, sync::Arc};
 
 use ratatui::widgets::{ScrollbarState, TableState};
 use tui_textarea::TextArea;
 
-use crate::{app::ITEM_HEIGHT, registry::{self, ValueParserError}};
+use crate::{app::ITEM_HEIGHT, backend::{self, memory::{self, MemoryNode}, RegistryBackend, RegistryKey}, backup::{self, Backup}, bookmarks::{Bookmark, Bookmarks}, filter::{Filter, FilterMode}, hexedit::HexEditor, history::{History, Location}, journal::{self, Journal, Operation}, listedit::ListEditor, regfile::{self, Change, ChangeKind, RegFile}, registry, search::{Search, SearchOptions}, snapshot::{self, Difference, Snapshot}, tree::KeyTree, treefile::{self, ImportMode, TreeFile, TreeFormat}};
 
 pub type InputValidateFn = dyn Fn(&str) -> Result<(), String>;
 pub type InputConfirmFn = dyn Fn(String) -> (Option<AppMessage>, PostAction);
+/// Returns every text the current input could be completed to.
+pub type InputCompleteFn = dyn Fn(&str) -> Vec<String>;
+/// Flips the option bound to `<Alt-c>`, returning the new label when there is one.
+pub type InputToggleFn = dyn Fn(char) -> Option<String>;
 
 pub struct InputChoices {
     pub items: Vec<String>,
@@ -15,7 +19,7 @@ pub struct InputChoices {
 
 impl InputChoices {
     pub fn new(items: Vec<impl Into<String>>) -> Self {
-        assert!(items.len() > 0);
+        assert!(!items.is_empty());
 
         Self {
             items: items.into_iter().map(|s| s.into()).collect(),
@@ -27,18 +31,30 @@ impl InputChoices {
 pub enum InputType {
     TextArea,
     Choice(InputChoices),
+    HexEditor(HexEditor),
+    List(ListEditor),
 }
 
 impl InputType {
     pub const fn is_textarea(&self) -> bool {
-        match self {
-            Self::TextArea => true,
-            _ => false,
-        }
+        matches!(self, Self::TextArea)
     }
 
     pub const fn is_choice(&self) -> bool {
-        !self.is_textarea()
+        matches!(self, Self::Choice(_))
+    }
+
+    pub const fn is_hex_editor(&self) -> bool {
+        matches!(self, Self::HexEditor(_))
+    }
+
+    pub const fn is_list(&self) -> bool {
+        matches!(self, Self::List(_))
+    }
+
+    /// Whether a list entry is being edited, which takes over <Enter> and <Esc>.
+    pub const fn is_list_editing(&self) -> bool {
+        matches!(self, Self::List(list) if list.is_editing())
     }
 }
 
@@ -48,13 +64,17 @@ pub struct InputState {
 
     pub validate_fn: Option<Box<InputValidateFn>>,
     pub confirm_fn: Option<Box<InputConfirmFn>>,
+    pub complete_fn: Option<Box<InputCompleteFn>>,
+    pub toggle_fn: Option<Box<InputToggleFn>>,
+    /// Candidates cycled through by repeated <Tab> presses.
+    pub completions: Option<(Vec<String>, usize)>,
 
     pub ty: InputType,
 }
 
 impl InputState {
     fn new() -> Self {
-        Self { label: String::from("No Input Required"), textarea: TextArea::default(), validate_fn: None, confirm_fn: None, ty: InputType::TextArea }
+        Self { label: String::from("No Input Required"), textarea: TextArea::default(), validate_fn: None, confirm_fn: None, complete_fn: None, toggle_fn: None, completions: None, ty: InputType::TextArea }
     }
 
     pub fn text(&self) -> String {
@@ -64,9 +84,53 @@ impl InputState {
     pub fn validate(&self) -> Option<Result<(), String>> {
         let text = self.text();
 
-        match self.validate_fn {
-            Some(ref validate_fn) => Some((validate_fn)(text.as_str())),
-            None => None,
+        self.validate_fn.as_ref().map(|validate_fn| (validate_fn)(text.as_str()))
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.textarea.select_all();
+        self.textarea.cut();
+        self.textarea.insert_str(text);
+    }
+
+    pub fn toggle(&mut self, c: char) {
+        let Some(toggle_fn) = self.toggle_fn.as_ref() else { return; };
+
+        if let Some(label) = (toggle_fn)(c) {
+            self.label = label;
+        }
+    }
+
+    /// Completes the text as far as all candidates agree, then cycles through them.
+    pub fn complete(&mut self) {
+        if let Some((candidates, index)) = self.completions.as_mut() {
+            *index = (*index + 1) % candidates.len();
+            let text = candidates[*index].clone();
+            self.set_text(&text);
+            return;
+        }
+
+        let Some(complete_fn) = self.complete_fn.as_ref() else { return; };
+        let text = self.text();
+        let candidates = (complete_fn)(&text);
+
+        let Some(first) = candidates.first() else { return; };
+        let common = candidates.iter().skip(1).fold(first.as_str(), |common, candidate| {
+            let len = common.chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+                .map(|(a, _)| a.len_utf8())
+                .sum();
+            &common[..len]
+        }).to_owned();
+
+        match candidates.len() {
+            1 => self.set_text(first),
+            _ if common.chars().count() > text.chars().count() => self.set_text(&common),
+            _ => {
+                self.set_text(first);
+                self.completions = Some((candidates, 0));
+            }
         }
     }
 }
@@ -105,22 +169,123 @@ pub struct ActionRenameSubkey {
     pub new: String,
 }
 
+pub struct ActionImport {
+    /// What the import changed, for the undo journal.
+    pub operation: Option<Operation>,
+}
+
 pub struct ActionDeleteSubkey {
     pub name: String,
+    /// Everything that was deleted, for the undo journal.
+    pub tree: MemoryNode,
+}
+
+pub struct ActionAddValue {
+    pub subkey: String,
+    pub value: NamedValue,
+    /// The value that was overwritten, if any.
+    pub previous: Option<registry::Value>,
+}
+
+pub struct ActionRenameValue {
+    pub subkey: String,
+    pub original: String,
+    pub new: String,
+}
+
+pub struct ActionDeleteValue {
+    pub subkey: String,
+    pub name: String,
+    pub value: registry::Value,
+}
+
+pub struct ActionGoTo {
+    pub path: Vec<String>,
+}
+
+pub struct ActionFind {
+    pub pattern: String,
+}
+
+pub struct ActionShowDiff {
+    pub diff: SnapshotDiff,
+}
+
+pub struct ActionAddBookmark {
+    pub bookmark: Bookmark,
+}
+
+pub struct ActionRenameBookmark {
+    pub index: usize,
+    pub name: String,
+}
+
+pub struct ActionDeleteBookmark {
+    pub index: usize,
 }
 
 pub struct StageNewValueType {
+    pub key: Box<dyn RegistryKey>,
+    pub subkey: String,
     pub name: String,
 }
 
 pub struct StageNewValueData {
+    pub key: Box<dyn RegistryKey>,
+    pub subkey: String,
+    pub name: String,
+    pub ty: registry::Type,
+}
+
+pub struct StageImportPreview {
+    pub path: String,
+    pub file: RegFile,
+    pub changes: Vec<Change>,
+}
+
+pub struct StageExportScope {
+    pub key: Box<dyn RegistryKey>,
+    pub path: String,
+}
+
+pub struct StageExportFile {
+    pub key: Box<dyn RegistryKey>,
+    pub path: String,
+    pub recursive: bool,
+}
+
+pub struct StageImportMode {
+    pub path: String,
+    pub tree: TreeFile,
+}
+
+pub struct StageExportTimestamps {
+    pub key: Box<dyn RegistryKey>,
+    pub path: String,
+    pub recursive: bool,
+    pub file: String,
+    pub format: TreeFormat,
+}
+
+pub struct StageConfirmConversion {
+    pub key: Box<dyn RegistryKey>,
+    /// Full path of the key, for the backup.
+    pub path: String,
+    pub subkey: String,
     pub name: String,
-    pub ty: windows_registry::Type,
+    pub value: registry::Value,
+    pub warning: String,
 }
 
 pub enum InputStageType {
     NewValueType(StageNewValueType),
     NewValueData(StageNewValueData),
+    ConfirmConversion(StageConfirmConversion),
+    ImportPreview(StageImportPreview),
+    ExportScope(StageExportScope),
+    ExportFile(StageExportFile),
+    ImportMode(StageImportMode),
+    ExportTimestamps(StageExportTimestamps),
 }
 
 pub struct ActionStage {
@@ -131,17 +296,44 @@ pub enum PostAction {
     AddSubkey(ActionAddSubkey),
     RenameSubkey(ActionRenameSubkey),
     DeleteSubkey(ActionDeleteSubkey),
+    AddValue(ActionAddValue),
+    RenameValue(ActionRenameValue),
+    DeleteValue(ActionDeleteValue),
 
     Stage(ActionStage),
+    GoTo(ActionGoTo),
+    Find(ActionFind),
+    ShowDiff(ActionShowDiff),
+    AddBookmark(ActionAddBookmark),
+    RenameBookmark(ActionRenameBookmark),
+    DeleteBookmark(ActionDeleteBookmark),
+
+    /// A file was imported or a backup restored, reload everything.
+    Import(ActionImport),
+    /// Something outside of the current key changed, reload everything.
+    Refresh,
 
     None,
 }
 
+/// A row left by the filter.
+pub struct VisibleRow {
+    /// Index into the full list.
+    pub index: usize,
+    /// Positions of the characters matched by the filter.
+    pub matched: Vec<usize>,
+}
+
+/// Selection and scroll position of a table. With a filter, `state` and
+/// `scroll` refer to the rows that are left, everything else to the full list.
 pub struct ScrollableTableState {
     pub state: TableState,
     pub scroll: ScrollbarState,
 
     pub content_length: usize,
+
+    pub filter: Option<Filter>,
+    pub visible: Option<Vec<VisibleRow>>,
 }
 
 impl ScrollableTableState {
@@ -151,15 +343,89 @@ impl ScrollableTableState {
             scroll: ScrollbarState::new(content_length),
 
             content_length,
+
+            filter: None,
+            visible: None,
         }
     }
 
+    /// The list has been replaced, which also drops the filter.
     fn resize(&mut self, content_length: usize) {
+        self.filter = None;
+        self.visible = None;
+
         self.state.select(Some(0));
         self.scroll = self.scroll.content_length(content_length);
 
         self.content_length = content_length;
     }
+
+    /// Index into the full list of the selected row.
+    pub fn selected(&self) -> Option<usize> {
+        let row = self.state.selected()?;
+
+        self.row_index(row)
+    }
+
+    fn row_index(&self, row: usize) -> Option<usize> {
+        match self.visible.as_ref() {
+            Some(visible) => visible.get(row).map(|row| row.index),
+            None => Some(row),
+        }
+    }
+
+    /// Selects the item at `index` in the full list, or the next row left
+    /// by the filter when that one is hidden.
+    fn select(&mut self, index: usize) {
+        let row = match self.visible.as_ref() {
+            Some(visible) if visible.is_empty() => None,
+            Some(visible) => Some(visible.iter().position(|row| row.index >= index).unwrap_or(visible.len() - 1)),
+            None => Some(index),
+        };
+
+        self.state.select(row);
+        self.scroll = self.scroll.position(row.unwrap_or(0) * ITEM_HEIGHT);
+    }
+
+    /// Runs the filter over `names` again, keeping the selection when it's still visible.
+    /// The item at `pinned`, like "..", stays visible regardless.
+    fn apply_filter<'a>(&mut self, names: impl Iterator<Item = &'a str>, pinned: Option<usize>) {
+        let selected = self.selected();
+
+        self.visible = self.filter.as_ref().map(|filter| {
+            names.enumerate()
+                .filter_map(|(index, name)| match pinned == Some(index) {
+                    true => Some(VisibleRow { index, matched: Vec::new() }),
+                    false => filter.find(name).map(|matched| VisibleRow { index, matched }),
+                })
+                .collect()
+        });
+
+        let length = self.visible.as_ref().map_or(self.content_length, |visible| visible.len() * ITEM_HEIGHT);
+        self.scroll = self.scroll.content_length(length);
+
+        self.select(selected.unwrap_or(0));
+    }
+}
+
+pub struct ChangePreview {
+    pub title: String,
+    pub changes: Vec<Change>,
+    pub table: ScrollableTableState,
+}
+
+impl ChangePreview {
+    fn new(title: impl Into<String>, changes: Vec<Change>) -> Self {
+        let table = ScrollableTableState::new(changes.len() * ITEM_HEIGHT);
+
+        Self { title: title.into(), changes, table }
+    }
+}
+
+/// What changed in a key since a snapshot of it was taken.
+pub struct SnapshotDiff {
+    pub title: String,
+    pub differences: Vec<Difference>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -172,6 +438,9 @@ pub enum KeyViewState {
 pub enum LastSelected {
     Keys,
     Values,
+    Results,
+    Diff,
+    Bookmarks,
     None,
 }
 
@@ -179,6 +448,12 @@ pub enum LastSelected {
 pub enum ViewState {
     Keys,
     Values,
+    /// The search result list.
+    Results,
+    /// The differences to a snapshot.
+    Diff,
+    /// The bookmark popup.
+    Bookmarks,
     Input(LastSelected),
     Message(LastSelected),
 }
@@ -188,6 +463,9 @@ impl From<ViewState> for LastSelected {
         match value {
             ViewState::Keys => LastSelected::Keys,
             ViewState::Values => LastSelected::Values,
+            ViewState::Results => LastSelected::Results,
+            ViewState::Diff => LastSelected::Diff,
+            ViewState::Bookmarks => LastSelected::Bookmarks,
             _ => unreachable!(),
         }
     }
@@ -198,6 +476,9 @@ impl From<LastSelected> for ViewState {
         match value {
             LastSelected::Keys => ViewState::Keys,
             LastSelected::Values => ViewState::Values,
+            LastSelected::Results => ViewState::Results,
+            LastSelected::Diff => ViewState::Diff,
+            LastSelected::Bookmarks => ViewState::Bookmarks,
             _ => unreachable!(),
         }
     }
@@ -205,75 +486,131 @@ impl From<LastSelected> for ViewState {
 
 impl ViewState {
     pub const fn is_input(&self) -> bool {
-        match self {
-            Self::Input(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Input(_))
     }
 
     pub const fn is_message(&self) -> bool {
-        match self {
-            Self::Message(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Message(_))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct NamedValue {
     pub name: String,
-    pub value: windows_registry::Value,
+    pub value: registry::Value,
 }
 
 impl NamedValue {
-    pub const fn new(name: String, value: windows_registry::Value) -> Self {
+    pub const fn new(name: String, value: registry::Value) -> Self {
         Self { name, value }
     }
 }
 
 struct KeyState {
-    key: windows_registry::Key,
+    key: Box<dyn RegistryKey>,
     subkeys: Vec<String>,
 
+    /// Full registry path of the key, e.g. `HKEY_CURRENT_USER\Software`.
+    key_path: String,
+
     cached_path: String,
     cached_values: HashMap<String, Vec<NamedValue>>,
 }
 
 impl KeyState {
-    fn new(key: windows_registry::Key, name: String, subkeys: Vec<String>, last_path: String) -> Self {
+    fn new(key: Box<dyn RegistryKey>, name: String, subkeys: Vec<String>, last_path: String, parent_key_path: Option<&str>) -> Self {
         let new_path = format!("{last_path} -> {name}");
+        let key_path = match parent_key_path {
+            Some(parent) => format!("{parent}\\{name}"),
+            None => name,
+        };
 
-        Self { key, subkeys, cached_path: new_path, cached_values: HashMap::new() }
+        Self { key, subkeys, key_path, cached_path: new_path, cached_values: HashMap::new() }
     }
 }
 
 pub struct AppContext {
     pub key_table: ScrollableTableState,
     pub value_table: ScrollableTableState,
+    pub tree_table: ScrollableTableState,
+    /// Set while the tree pane replaces the subkey table.
+    pub tree: Option<KeyTree>,
+    pub search_table: ScrollableTableState,
+    /// The last search, kept for find-next until it's closed.
+    pub search: Option<Search>,
+    pub diff_table: ScrollableTableState,
+    /// The last snapshot comparison, kept until it's closed.
+    pub diff: Option<SnapshotDiff>,
+    pub bookmark_table: ScrollableTableState,
+    pub bookmarks: Bookmarks,
+    pub journal: Journal,
     pub input: InputState,
     pub message: Option<AppMessage>,
+    pub preview: Option<ChangePreview>,
 
     pub view_state: ViewState,
+    /// Set while typing into the filter of the current table.
+    pub filter_editing: bool,
 
+    backend: Arc<dyn RegistryBackend>,
     base_subkeys: Vec<String>,
-    base_path: &'static str,
+    base_path: String,
+    // shared with the validate and toggle functions of the find input
+    search_options: Rc<Cell<SearchOptions>>,
+    filter_mode: FilterMode,
+    history: History,
 
     key_states: Vec<KeyState>,
 }
 
 impl AppContext {
-    pub fn new() -> Self {
-        let base_subkeys: Vec<String> = Vec::from(registry::DEFAULT_KEYS.map(|(_, name)| name.into()));
+    pub fn new(backend: Arc<dyn RegistryBackend>) -> Self {
+        let base_subkeys = backend.roots();
+        let base_path = backend.name().to_owned();
+
+        // e.g. a hive opened read-only, shown before anything else goes wrong
+        let warnings = backend.warnings();
+        let message = (!warnings.is_empty()).then(|| AppMessage::error(warnings.join(" ")));
+
+        // a broken file is left alone, nothing gets saved over it
+        let (bookmarks, message) = match Bookmarks::load(Bookmarks::default_file()) {
+            Ok(bookmarks) => (bookmarks, message),
+            Err(err) => (Bookmarks::default(), message.or(Some(AppMessage::error(format!("Error when loading the bookmarks: {}", err.message()))))),
+        };
+        // same for the journal, it's kept for the session only
+        let (journal, message) = match Journal::load(Journal::default_file(backend.as_ref())) {
+            Ok(journal) => (journal, message),
+            Err(err) => (Journal::default(), message.or(Some(AppMessage::error(format!("Error when loading the undo journal: {}", err.message()))))),
+        };
+        let view_state = match message {
+            Some(_) => ViewState::Message(LastSelected::Keys),
+            None => ViewState::Keys,
+        };
 
         Self {
             key_table: ScrollableTableState::new(base_subkeys.len() * ITEM_HEIGHT),
             value_table: ScrollableTableState::new(100 * ITEM_HEIGHT),
+            tree_table: ScrollableTableState::new(0),
+            tree: None,
+            search_table: ScrollableTableState::new(0),
+            search: None,
+            diff_table: ScrollableTableState::new(0),
+            diff: None,
+            bookmark_table: ScrollableTableState::new(bookmarks.items.len() * ITEM_HEIGHT),
+            bookmarks,
+            journal,
             input: InputState::new(),
-            message: None,
-            view_state: ViewState::Keys,
+            message,
+            preview: None,
+            view_state,
+            filter_editing: false,
 
+            backend,
             base_subkeys,
-            base_path: "Computer",
+            base_path,
+            search_options: Rc::new(Cell::new(SearchOptions::default())),
+            filter_mode: FilterMode::Substring,
+            history: History::default(),
 
             key_states: Vec::new(),
         }
@@ -283,6 +620,9 @@ impl AppContext {
         match view {
             ViewState::Keys => Some(&mut self.key_table),
             ViewState::Values => Some(&mut self.value_table),
+            ViewState::Results => Some(&mut self.search_table),
+            ViewState::Diff => Some(&mut self.diff_table),
+            ViewState::Bookmarks => Some(&mut self.bookmark_table),
             _ => None,
         }
     }
@@ -294,13 +634,15 @@ impl AppContext {
     pub fn swap_viewing_table(&mut self) {
         self.view_state = match self.view_state {
             ViewState::Keys => ViewState::Values,
-            ViewState::Values => ViewState::Keys,
+            ViewState::Values if self.search.is_some() => ViewState::Results,
+            ViewState::Values | ViewState::Results if self.diff.is_some() => ViewState::Diff,
+            ViewState::Values | ViewState::Results | ViewState::Diff => ViewState::Keys,
             _ => return,
         };
     }
 
     fn update_values(&mut self) {
-        let i = match self.key_table.state.selected() {
+        let i = match self.key_table.selected() {
             Some(i) => i,
             None => return,
         };
@@ -312,12 +654,12 @@ impl AppContext {
         };
 
         let entry = key_state.cached_values.entry(key_name.clone()).or_insert_with(|| {
-            let key = match registry::read_key(&key_state.key, key_name.as_str()) {
+            let key = match registry::read_key(key_state.key.as_ref(), key_name.as_str()) {
                 Ok(key) => key,
                 Err(_) => return Vec::new(),
             };
 
-            let values = registry::read_values(&key);
+            let values = registry::read_values(key.as_ref());
             
             match values {
                 Ok(values) => values.into_iter().map(|(name, value)| NamedValue::new(name, value)).collect(),
@@ -329,16 +671,10 @@ impl AppContext {
     }
 
     pub fn get_values(&self) -> Option<&Vec<NamedValue>> {
-        let i = match self.key_table.state.selected() {
-            Some(i) => i,
-            None => return None,
-        };
+        let i = self.key_table.selected()?;
 
         let key_name = &self.get_subkeys()[i];
-        let key_state = match self.key_states.last() {
-            Some(key_state) => key_state,
-            None => return None,
-        };
+        let key_state = self.key_states.last()?;
 
         key_state.cached_values.get(key_name)
     }
@@ -347,6 +683,9 @@ impl AppContext {
         let len = match self.view_state {
             ViewState::Keys => self.get_subkeys().len(),
             ViewState::Values => self.get_values().map_or(0, |values| values.len()),
+            ViewState::Results => self.search.as_ref().map_or(0, |search| search.hits.len()),
+            ViewState::Diff => self.diff.as_ref().map_or(0, |diff| diff.differences.len()),
+            ViewState::Bookmarks => self.bookmarks.items.len(),
             _ => 0,
         };
 
@@ -356,8 +695,7 @@ impl AppContext {
     fn select_row_in(&mut self, view: ViewState, i: usize) {
         let Some(table) = self.get_table_by_view(view) else { return; };
 
-        table.state.select(Some(i));
-        table.scroll = table.scroll.position(i * ITEM_HEIGHT);
+        table.select(i);
 
         if self.view_state == ViewState::Keys {
             self.update_values();
@@ -374,7 +712,10 @@ impl AppContext {
             Some(table) => table,
             None => return,
         };
-        let i = table.state.selected().map_or(0, |i| i.saturating_add(1).min(max));
+        // rows are counted within what the filter left
+        let max = table.visible.as_ref().map_or(max, |visible| visible.len().saturating_sub(1));
+        let row = table.state.selected().map_or(0, |row| row.saturating_add(1).min(max));
+        let Some(i) = table.row_index(row) else { return; };
 
         self.select_row_in_current(i);
     }
@@ -384,11 +725,97 @@ impl AppContext {
             Some(table) => table,
             None => return,
         };
-        let i = table.state.selected().map_or(0, |i| i.saturating_sub(1));
+        let row = table.state.selected().map_or(0, |row| row.saturating_sub(1));
+        let Some(i) = table.row_index(row) else { return; };
 
         self.select_row_in_current(i);
     }
 
+    /// The filter of the table that has the focus.
+    pub fn get_filter(&self) -> Option<&Filter> {
+        match self.view_state {
+            ViewState::Keys => self.key_table.filter.as_ref(),
+            ViewState::Values => self.value_table.filter.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn refresh_filter(&mut self) {
+        let view = self.view_state;
+        let (names, pinned) = match view {
+            // ".." stays so there's always a way back up
+            ViewState::Keys => (self.get_subkeys().clone(), (!self.key_states.is_empty()).then_some(0)),
+            ViewState::Values => (self.get_values().map(|values| values.iter().map(|value| value.name.clone()).collect()).unwrap_or_default(), None),
+            _ => return,
+        };
+
+        let selected = self.key_table.selected();
+        let Some(table) = self.get_table_by_view(view) else { return; };
+        table.apply_filter(names.iter().map(|name| name.as_str()), pinned);
+
+        if view == ViewState::Keys && self.key_table.selected() != selected {
+            self.update_values();
+        }
+    }
+
+    fn edit_filter(&mut self, edit: impl FnOnce(&mut Filter)) {
+        let view = self.view_state;
+        let Some(filter) = self.get_table_by_view(view).and_then(|table| table.filter.as_mut()) else { return; };
+
+        edit(filter);
+        self.filter_mode = filter.mode;
+        self.refresh_filter();
+    }
+
+    pub fn start_filter(&mut self) {
+        match self.view_state {
+            ViewState::Keys if self.tree.is_some() => {
+                self.set_message(AppMessage::info("The filter works on the key table, close the tree first."));
+                return;
+            }
+            ViewState::Keys | ViewState::Values => (),
+            _ => return,
+        };
+
+        let mode = self.filter_mode;
+        let Some(table) = self.get_selected_table() else { return; };
+
+        table.filter.get_or_insert(Filter::new(mode));
+        self.filter_editing = true;
+        self.refresh_filter();
+    }
+
+    pub fn filter_input(&mut self, c: char) {
+        self.edit_filter(|filter| filter.query.push(c));
+    }
+
+    pub fn filter_backspace(&mut self) {
+        self.edit_filter(|filter| { filter.query.pop(); });
+    }
+
+    pub fn toggle_filter_mode(&mut self) {
+        self.edit_filter(|filter| filter.mode = filter.mode.toggle());
+    }
+
+    /// Stops typing, the rows stay filtered unless the query is empty.
+    pub fn finish_filter(&mut self) {
+        self.filter_editing = false;
+
+        if self.get_filter().is_some_and(|filter| filter.query.is_empty()) {
+            self.clear_filter();
+        }
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter_editing = false;
+
+        let view = self.view_state;
+        let Some(table) = self.get_table_by_view(view) else { return; };
+
+        table.filter = None;
+        self.refresh_filter();
+    }
+
     fn get_key_view_state(&self) -> KeyViewState {
         match self.key_states.is_empty() {
             true => KeyViewState::Base,
@@ -396,29 +823,27 @@ impl AppContext {
         }
     }
 
-    fn create_subkeys(&self, key: &windows_registry::Key) -> Vec<String> {
-        let mut subkeys = registry::read_subkeys(key).unwrap();
+    fn create_subkeys(&self, key: &dyn RegistryKey) -> backend::Result<Vec<String>> {
+        let mut subkeys = registry::read_subkeys(key)?;
 
         // add subkey to go back
         subkeys.insert(0, "..".into());
 
-        subkeys
+        Ok(subkeys)
     }
 
-    fn select_base(&mut self, index: usize) {
-        let path = &self.base_subkeys[index];
+    fn select_base(&mut self, index: usize) -> backend::Result<()> {
+        let name = self.base_subkeys[index].clone();
 
-        let default = registry::DEFAULT_KEYS;
-        let (key, name) = default.iter().find(|(_, s)| *s == path).unwrap();
-
-        let subkeys = self.create_subkeys(key);
-        let key = key.open("").unwrap();
-        let new_state = KeyState::new(key, String::from(*name), subkeys, self.base_path.to_owned());
+        let key = self.backend.open_root(name.as_str())?;
+        let subkeys = self.create_subkeys(key.as_ref())?;
+        let new_state = KeyState::new(key, name, subkeys, self.base_path.clone(), None);
 
         self.key_states.push(new_state);
+        Ok(())
     }
 
-    fn select_key(&mut self, index: usize) {
+    fn select_key(&mut self, index: usize) -> backend::Result<()> {
         match index {
             0 => { // ".." subkey
                 let _ = self.key_states.pop();
@@ -427,32 +852,296 @@ impl AppContext {
                 let path = &self.get_subkeys()[index];
                 let current_state = self.key_states.last().unwrap();
 
-                let key = registry::read_key(&current_state.key, path).unwrap();
-                let subkeys = self.create_subkeys(&key);
-                let new_state = KeyState::new(key, path.to_owned(), subkeys, current_state.cached_path.clone());
+                let key = registry::read_key(current_state.key.as_ref(), path)?;
+                let subkeys = self.create_subkeys(key.as_ref())?;
+                let new_state = KeyState::new(key, path.to_owned(), subkeys, current_state.cached_path.clone(), Some(&current_state.key_path));
 
                 self.key_states.push(new_state);
             }
         };
+
+        Ok(())
     }
 
     pub fn select(&mut self) {
-        let i = match self.key_table.state.selected() {
+        let i = match self.key_table.selected() {
             Some(i) => i,
             None => return,
         };
+        let from = self.current_location();
 
-        match self.get_key_view_state() {
+        let result = match self.get_key_view_state() {
             KeyViewState::Base => self.select_base(i),
             KeyViewState::Subkey => self.select_key(i),
         };
 
+        // a key that can't be read, e.g. with a corrupt subkey list, stays unopened
+        if let Err(err) = result {
+            self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+            return;
+        }
+
+        self.key_table.resize(self.get_subkeys().len() * ITEM_HEIGHT);
+        self.record_visit(from);
+    }
+
+    fn current_location(&self) -> Location {
+        let path = self.key_states.last().map(|state| Self::key_path_components(&state.key_path)).unwrap_or_default();
+        let key = self.key_table.selected().and_then(|i| self.get_subkeys().get(i)).cloned();
+        let value = self.value_table.selected().and_then(|i| self.get_values()?.get(i)).map(|value| value.name.clone());
+
+        Location { path, key, value }
+    }
+
+    /// Adds `from` to the history when another key is listed now.
+    fn record_visit(&mut self, from: Location) {
+        if !from.same_key(&self.current_location()) {
+            self.history.visit(from);
+        }
+    }
+
+    fn restore_location(&mut self, location: Location) {
+        let result = self.navigate_to(&location.path);
+
+        // a key that's gone by now leaves us at its closest parent
+        self.key_table.resize(self.get_subkeys().len() * ITEM_HEIGHT);
+        let key = location.key.and_then(|name| self.get_subkeys().iter().position(|subkey| subkey.eq_ignore_ascii_case(&name)));
+        self.select_row_in(ViewState::Keys, key.unwrap_or(0));
+        self.update_values();
+
+        // syncing reselects the key, which resets the value table
+        self.sync_tree();
+
+        let value = location.value.and_then(|name| self.get_values()?.iter().position(|value| value.name.eq_ignore_ascii_case(&name)));
+        if let Some(index) = value {
+            self.select_row_in(ViewState::Values, index);
+        }
+
+        if let Err(err) = result {
+            self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+        }
+    }
+
+    pub fn go_back(&mut self) {
+        let current = self.current_location();
+
+        match self.history.back(current) {
+            Some(location) => self.restore_location(location),
+            None => self.set_message(AppMessage::info("Nothing to go back to.")),
+        }
+    }
+
+    pub fn go_forward(&mut self) {
+        let current = self.current_location();
+
+        match self.history.forward(current) {
+            Some(location) => self.restore_location(location),
+            None => self.set_message(AppMessage::info("Nothing to go forward to.")),
+        }
+    }
+
+    fn key_path_components(path: &str) -> Vec<String> {
+        path.split('\\').map(|name| name.to_owned()).collect()
+    }
+
+    /// Path of the key highlighted in the subkey table, or of the listed key
+    /// itself when ".." is highlighted.
+    fn selected_key_components(&self) -> Vec<String> {
+        let selected = self.key_table.selected().unwrap_or(0);
+
+        match self.key_states.last() {
+            None => self.base_subkeys.get(selected).into_iter().cloned().collect(),
+            Some(state) => {
+                let mut path = Self::key_path_components(&state.key_path);
+                if selected > 0 && let Some(name) = state.subkeys.get(selected) {
+                    path.push(name.clone());
+                }
+                path
+            }
+        }
+    }
+
+    /// Points `key_states` at the given key, reusing the states it shares with the current path.
+    fn navigate_to(&mut self, path: &[String]) -> backend::Result<()> {
+        let shared = self.key_states
+            .iter()
+            .zip(1..)
+            .take_while(|(state, len)| *len <= path.len() && state.key_path.eq_ignore_ascii_case(&path[..*len].join("\\")))
+            .count();
+        self.key_states.truncate(shared);
+
+        for name in &path[shared..] {
+            // use the stored spelling of the name rather than the typed one
+            let name = self.get_subkeys().iter().find(|subkey| subkey.eq_ignore_ascii_case(name)).unwrap_or(name).clone();
+            let name = &name;
+
+            let key = match self.key_states.last() {
+                Some(state) => registry::read_key(state.key.as_ref(), name)?,
+                None => self.backend.open_root(name)?,
+            };
+
+            let mut subkeys = registry::read_subkeys(key.as_ref())?;
+            subkeys.insert(0, "..".into());
+
+            let (last_path, parent_key_path) = match self.key_states.last() {
+                Some(state) => (state.cached_path.clone(), Some(state.key_path.as_str())),
+                None => (self.base_path.clone(), None),
+            };
+            let new_state = KeyState::new(key, name.clone(), subkeys, last_path, parent_key_path);
+
+            self.key_states.push(new_state);
+        }
+
+        Ok(())
+    }
+
+    fn rebuild_tree(&mut self) {
+        let backend = self.backend.clone();
+        let Some(tree) = self.tree.as_mut() else { return; };
+
+        tree.rebuild(&self.base_subkeys, |path| {
+            let key = backend::open_path(backend.as_ref(), &path.join("\\")).ok()?;
+            registry::read_subkeys(key.as_ref()).ok()
+        });
+
+        self.tree_table.scroll = self.tree_table.scroll.content_length(tree.rows.len() * ITEM_HEIGHT);
+        self.tree_table.content_length = tree.rows.len() * ITEM_HEIGHT;
+    }
+
+    /// Makes the subkey table and values follow the key selected in the tree.
+    fn select_tree_row(&mut self, i: usize) {
+        let Some(path) = self.tree.as_ref().and_then(|tree| tree.rows.get(i)).map(|row| row.path.clone()) else { return; };
+
+        self.tree_table.state.select(Some(i));
+        self.tree_table.scroll = self.tree_table.scroll.position(i * ITEM_HEIGHT);
+
+        if let Err(err) = self.select_key_path(&path) {
+            self.set_message_with_state(AppMessage::error(format!("Error when opening the key: {}", err.message())), LastSelected::Keys);
+        }
+    }
+
+    /// Lists the parent of the key and highlights the key in it, remembering
+    /// where we came from.
+    fn select_key_path(&mut self, path: &[String]) -> backend::Result<()> {
+        let from = self.current_location();
+        let result = self.open_key_path(path);
+        self.record_visit(from);
+
+        result
+    }
+
+    fn open_key_path(&mut self, path: &[String]) -> backend::Result<()> {
+        let Some((name, parent)) = path.split_last() else { return Ok(()); };
+
+        self.navigate_to(parent)?;
+
+        let index = self.get_subkeys()
+            .iter()
+            .position(|subkey| subkey.eq_ignore_ascii_case(name))
+            .ok_or_else(|| backend::Error::not_found(format!("The key {name} doesn't exist")))?;
+
         self.key_table.resize(self.get_subkeys().len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Keys, index);
+        self.update_values();
+
+        Ok(())
+    }
+
+    /// Refreshes the tree after the keys have been changed through the subkey table.
+    fn sync_tree(&mut self) {
+        let Some(tree) = self.tree.as_mut() else { return; };
+
+        for state in &self.key_states {
+            let path = Self::key_path_components(&state.key_path);
+            let children = state.subkeys.iter().skip(1).cloned().collect();
+
+            tree.set_children(&path, children);
+            tree.set_expanded(&path, true);
+        }
+
+        self.rebuild_tree();
+
+        let selected = self.selected_key_components();
+        let Some(tree) = self.tree.as_ref() else { return; };
+        let i = tree.position(&selected).unwrap_or(0);
+
+        self.select_tree_row(i);
+    }
+
+    pub fn toggle_tree(&mut self) {
+        if self.tree.take().is_some() {
+            return;
+        }
+
+        // syncing expands the current location
+        self.tree = Some(KeyTree::default());
+        self.sync_tree();
+    }
+
+    pub fn next_tree_row(&mut self) {
+        let Some(tree) = self.tree.as_ref() else { return; };
+        let max = tree.rows.len().saturating_sub(1);
+        let i = self.tree_table.state.selected().map_or(0, |i| i.saturating_add(1).min(max));
+
+        self.select_tree_row(i);
+    }
+
+    pub fn prev_tree_row(&mut self) {
+        let i = self.tree_table.state.selected().map_or(0, |i| i.saturating_sub(1));
+
+        self.select_tree_row(i);
+    }
+
+    /// Expands the selected key, or moves to its first child when it's already expanded.
+    pub fn expand_tree_row(&mut self) {
+        let Some(i) = self.tree_table.state.selected() else { return; };
+        let Some(tree) = self.tree.as_mut() else { return; };
+        let Some(row) = tree.rows.get(i) else { return; };
+
+        match row.expanded {
+            true if row.has_children == Some(true) => self.select_tree_row(i + 1),
+            true => (),
+            false => {
+                let path = row.path.clone();
+                tree.set_expanded(&path, true);
+                self.rebuild_tree();
+            }
+        }
+    }
+
+    /// Collapses the selected key, or moves to its parent when it's already collapsed.
+    pub fn collapse_tree_row(&mut self) {
+        let Some(i) = self.tree_table.state.selected() else { return; };
+        let Some(tree) = self.tree.as_mut() else { return; };
+        let Some(row) = tree.rows.get(i) else { return; };
+        let path = row.path.clone();
+
+        match row.expanded {
+            true => {
+                tree.set_expanded(&path, false);
+                self.rebuild_tree();
+            }
+            false if path.len() > 1 => {
+                let parent = tree.position(&path[..path.len() - 1]).unwrap_or(0);
+                self.select_tree_row(parent);
+            }
+            false => (),
+        }
+    }
+
+    pub fn toggle_tree_row(&mut self) {
+        let Some(i) = self.tree_table.state.selected() else { return; };
+        let Some(row) = self.tree.as_ref().and_then(|tree| tree.rows.get(i)) else { return; };
+
+        match row.expanded {
+            true => self.collapse_tree_row(),
+            false => self.expand_tree_row(),
+        }
     }
 
     pub fn get_path(&self) -> &str {
         match self.get_key_view_state() {
-            KeyViewState::Base => self.base_path,
+            KeyViewState::Base => self.base_path.as_str(),
             KeyViewState::Subkey => self.key_states.last().unwrap().cached_path.as_str(),
         }
     }
@@ -488,6 +1177,16 @@ impl AppContext {
         self.set_input_state(InputType::Choice(InputChoices::new(choices)), None, Some(confirm));
     }
 
+    /// The confirm function gets the bytes as hex, the way `ValueBytesParser` reads them.
+    pub fn set_hex_input(&mut self, data: Vec<u8>, confirm: Box<InputConfirmFn>) {
+        self.set_input_state(InputType::HexEditor(HexEditor::new(data)), None, Some(confirm));
+    }
+
+    /// The confirm function gets the entries one per line.
+    pub fn set_list_input(&mut self, items: Vec<String>, confirm: Box<InputConfirmFn>) {
+        self.set_input_state(InputType::List(ListEditor::new(items)), None, Some(confirm));
+    }
+
     pub fn next_input_choice(&mut self) {
         let InputType::Choice(ref mut choices) = self.input.ty else { return; };
         let len = choices.items.len();
@@ -502,28 +1201,72 @@ impl AppContext {
         choices.selected = (choices.selected + len - 1) % len;
     }
 
-    pub fn reset_input(&mut self) {
-        self.view_state = match self.view_state {
-            ViewState::Input(last_selected) => last_selected.into(),
-            _ => self.view_state,
-        };
+    pub fn next_preview_row(&mut self) {
+        let Some(preview) = self.preview.as_mut() else { return; };
+        let i = preview.table.state.selected().map_or(0, |i| i.saturating_add(1).min(preview.changes.len().saturating_sub(1)));
+
+        preview.table.state.select(Some(i));
+        preview.table.scroll = preview.table.scroll.position(i * ITEM_HEIGHT);
+    }
+
+    pub fn prev_preview_row(&mut self) {
+        let Some(preview) = self.preview.as_mut() else { return; };
+        let i = preview.table.state.selected().map_or(0, |i| i.saturating_sub(1));
+
+        preview.table.state.select(Some(i));
+        preview.table.scroll = preview.table.scroll.position(i * ITEM_HEIGHT);
+    }
+
+    pub fn reset_input(&mut self) {
+        self.view_state = match self.view_state {
+            ViewState::Input(last_selected) => last_selected.into(),
+            _ => self.view_state,
+        };
+
+        self.preview = None;
 
         self.input.validate_fn = None;
         self.input.confirm_fn = None;
+        self.input.complete_fn = None;
+        self.input.toggle_fn = None;
+        self.input.completions = None;
+        self.input.ty = InputType::TextArea;
 
-        if self.input.ty.is_textarea() {
-            self.input.textarea.select_all();
-            self.input.textarea.cut();
+        // a staged input may have ended on a choice, the text is still there
+        self.input.textarea.select_all();
+        self.input.textarea.cut();
 
-            self.input.textarea.set_placeholder_text("");
-        }
+        self.input.textarea.set_placeholder_text("");
 
         self.input.label = "No Input Required".into();
     }
 
+    /// Full path of a key below the one that's open.
+    fn child_path(&self, name: &str) -> String {
+        match self.key_states.last() {
+            Some(state) => format!("{}\\{name}", state.key_path),
+            None => name.to_owned(),
+        }
+    }
+
+    fn record(&mut self, operation: Operation) {
+        if let Err(err) = self.journal.record(operation) {
+            let last_selected = match self.view_state {
+                ViewState::Input(last_selected) | ViewState::Message(last_selected) => last_selected,
+                view => view.into(),
+            };
+
+            self.set_message_with_state(AppMessage::error(format!("Error when saving the undo journal: {}", err.message())), last_selected);
+        }
+    }
+
     fn post_action_add_subkey(&mut self, action: ActionAddSubkey) {
+        self.record(Operation::CreateKey { path: self.child_path(&action.name) });
+
         let Some(last) = self.key_states.last_mut() else { unreachable!() };
-        let Err(index) = last.subkeys.binary_search_by(|s| s.cmp(&action.name)) else { unreachable!() };
+        // listed the way the backends enumerate them, by uppercase name after ".."
+        let upper = action.name.to_uppercase();
+        let index = last.subkeys.iter().skip(1).position(|s| s.to_uppercase() > upper).map_or(last.subkeys.len(), |i| i + 1);
 
         last.subkeys.insert(index, action.name);
 
@@ -532,6 +1275,9 @@ impl AppContext {
     }
 
     fn post_action_rename_subkey(&mut self, action: ActionRenameSubkey) {
+        let Some(path) = self.key_states.last().map(|state| state.key_path.clone()) else { unreachable!() };
+        self.record(Operation::RenameKey { path, from: action.original.clone(), to: action.new.clone() });
+
         let Some(last) = self.key_states.last_mut() else { unreachable!() };
         let Some(subkey_index) = last.subkeys.iter().position(|a| *a == action.original) else { unreachable!() };
 
@@ -539,8 +1285,10 @@ impl AppContext {
     }
 
     fn post_action_delete_subkey(&mut self, action: ActionDeleteSubkey) {
+        self.record(Operation::DeleteKey { path: self.child_path(&action.name), tree: action.tree });
+
         let Some(last) = self.key_states.last_mut() else { unreachable!() };
-        let Ok(index) = last.subkeys.binary_search_by(|s| s.cmp(&action.name)) else { unreachable!() };
+        let Some(index) = last.subkeys.iter().skip(1).position(|s| memory::names_equal(s, &action.name)).map(|i| i + 1) else { unreachable!() };
 
         last.subkeys.remove(index);
 
@@ -548,6 +1296,55 @@ impl AppContext {
         self.select_row_in(ViewState::Keys, index - 1);
     }
 
+    fn post_action_add_value(&mut self, action: ActionAddValue) {
+        self.record(Operation::SetValue {
+            path: self.child_path(&action.subkey),
+            name: action.value.name.clone(),
+            old: action.previous,
+            new: action.value.value.clone(),
+        });
+
+        let Some(last) = self.key_states.last_mut() else { unreachable!() };
+        let values = last.cached_values.entry(action.subkey).or_default();
+
+        let index = match values.iter().position(|v| v.name.eq_ignore_ascii_case(&action.value.name)) {
+            Some(index) => {
+                values[index] = action.value;
+                index
+            }
+            None => {
+                values.push(action.value);
+                values.len() - 1
+            }
+        };
+
+        self.value_table.resize(values.len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Values, index);
+    }
+
+    fn post_action_rename_value(&mut self, action: ActionRenameValue) {
+        self.record(Operation::RenameValue { path: self.child_path(&action.subkey), from: action.original.clone(), to: action.new.clone() });
+
+        let Some(last) = self.key_states.last_mut() else { unreachable!() };
+        let Some(values) = last.cached_values.get_mut(&action.subkey) else { return; };
+        let Some(value) = values.iter_mut().find(|v| v.name == action.original) else { return; };
+
+        value.name = action.new;
+    }
+
+    fn post_action_delete_value(&mut self, action: ActionDeleteValue) {
+        self.record(Operation::DeleteValue { path: self.child_path(&action.subkey), name: action.name.clone(), value: action.value });
+
+        let Some(last) = self.key_states.last_mut() else { unreachable!() };
+        let Some(values) = last.cached_values.get_mut(&action.subkey) else { return; };
+        let Some(index) = values.iter().position(|v| v.name == action.name) else { return; };
+
+        values.remove(index);
+
+        self.value_table.resize(values.len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Values, index.saturating_sub(1));
+    }
+
     fn input_stage_new_value_type(&mut self, stage: StageNewValueType) {
         let confirm = move |input: String| {
             let ty = registry::str_to_type(input.as_ref());
@@ -555,7 +1352,12 @@ impl AppContext {
             (
                 None,
                 PostAction::Stage(ActionStage {
-                    ty: InputStageType::NewValueData(StageNewValueData { name: stage.name.clone(), ty }) 
+                    ty: InputStageType::NewValueData(StageNewValueData {
+                        key: registry::clone_key(stage.key.as_ref()),
+                        subkey: stage.subkey.clone(),
+                        name: stage.name.clone(),
+                        ty,
+                    })
                 })
             )
         };
@@ -568,28 +1370,285 @@ impl AppContext {
         let validator = registry::get_value_validator(stage.ty);
 
         let validate = move |input: &str| {
-            match (*validator).validate(input) {
-                Ok(()) => Ok(()),
-                Err(err) => match err {
-                    ValueParserError::U32Error(err) => Err(format!("{}", err)),
-                    ValueParserError::U64Error(err) => Err(format!("{}", err)),
+            (*validator).validate(input).map_err(registry::parser_error_message)
+        };
+
+        let confirm = move |input: String| {
+            match registry::set_value(stage.key.as_ref(), stage.name.as_str(), stage.ty, input.as_str()) {
+                Ok(value) => {
+                    let value = NamedValue::new(stage.name.clone(), value);
+                    (Some(AppMessage::info("New value successfully created.")), PostAction::AddValue(ActionAddValue { subkey: stage.subkey.clone(), value, previous: None }))
+                }
+                Err(err) => {
+                    (Some(AppMessage::error(format!("Error when creating a new value: {}", err.message()))), PostAction::None)
                 }
             }
         };
 
+        self.input.label = "Enter Value:".into();
+        match stage.ty {
+            registry::Type::MultiString => self.set_list_input(Vec::new(), Box::new(confirm)),
+            ty if ty.is_raw() => self.set_hex_input(Vec::new(), Box::new(confirm)),
+            _ => self.set_textarea_input(Box::new(validate), Box::new(confirm)),
+        };
+    }
+
+    fn input_stage_confirm_conversion(&mut self, stage: StageConfirmConversion) {
+        let confirm = move |text: String| {
+            if text == "No" {
+                return (None, PostAction::None);
+            }
+
+            Self::write_value(stage.key.as_ref(), &stage.path, &stage.subkey, &stage.name, &stage.value, "The type has been successfully changed.")
+        };
+
+        self.input.label = format!("{}. Change Anyway:", stage.warning);
+        self.set_choice_input(vec!["No", "Yes"], Box::new(confirm));
+    }
+
+    fn input_stage_import_preview(&mut self, stage: StageImportPreview) {
+        let backend = self.backend.clone();
+        let file = stage.file;
+        let path = stage.path.clone();
+
+        let confirm = move |text: String| {
+            if text == "No" {
+                return (None, PostAction::None);
+            }
+
+            let note = match backup::save_changes(backend.as_ref(), &file) {
+                Ok(backups) => backups.iter().map(|backup| format!(" Backup saved to {}.", backup.display())).collect::<String>(),
+                Err(err) => return (Some(AppMessage::error(format!("Error when backing up before the import: {}", err.message()))), PostAction::None),
+            };
+
+            // a failed import may still have changed part of it
+            let (operation, result) = journal::apply(backend.as_ref(), &file, format!("import {path}"));
+            let message = match result {
+                Ok(()) => AppMessage::info(format!("The file has been successfully imported.{note}")),
+                Err(err) => AppMessage::error(format!("Error when importing the file: {}", err.message())),
+            };
+
+            (Some(message), PostAction::Import(ActionImport { operation }))
+        };
+
+        let title = format!("Changes from {}", stage.path);
+        self.preview = Some(ChangePreview::new(title, stage.changes));
+
+        self.input.label = "Apply Changes:".into();
+        self.set_choice_input(vec!["No", "Yes"], Box::new(confirm));
+    }
+
+    fn input_stage_export_scope(&mut self, stage: StageExportScope) {
+        let confirm = move |text: String| {
+            (
+                None,
+                PostAction::Stage(ActionStage {
+                    ty: InputStageType::ExportFile(StageExportFile {
+                        key: registry::clone_key(stage.key.as_ref()),
+                        path: stage.path.clone(),
+                        recursive: text == "Subtree",
+                    })
+                })
+            )
+        };
+
+        self.input.label = "Export:".into();
+        self.set_choice_input(vec!["Subtree", "Key Only"], Box::new(confirm));
+    }
+
+    fn input_stage_export_file(&mut self, stage: StageExportFile) {
         let confirm = move |input: String| {
-            (None, PostAction::None)
+            // JSON and YAML can also carry the timestamps
+            if let Some(format) = TreeFormat::from_path(&input) {
+                return (
+                    None,
+                    PostAction::Stage(ActionStage {
+                        ty: InputStageType::ExportTimestamps(StageExportTimestamps {
+                            key: registry::clone_key(stage.key.as_ref()),
+                            path: stage.path.clone(),
+                            recursive: stage.recursive,
+                            file: input.trim().to_owned(),
+                            format,
+                        })
+                    })
+                );
+            }
+
+            let result = regfile::export(stage.key.as_ref(), &stage.path, stage.recursive)
+                .and_then(|(text, count)| {
+                    std::fs::write(input.trim(), regfile::encode_text(&text))?;
+                    Ok(count)
+                });
+
+            (Some(Self::export_message(result, input.trim())), PostAction::None)
         };
 
-        self.input.label = "Enter Value:".into();
-        self.set_textarea_input(Box::new(validate), Box::new(confirm));
+        self.input.label = "Export To:".into();
+        self.set_textarea_input(Box::new(Self::file_validator), Box::new(confirm));
+    }
+
+    fn export_message(result: backend::Result<usize>, file: &str) -> AppMessage {
+        match result {
+            Ok(1) => AppMessage::info(format!("Exported 1 key to {file}.")),
+            Ok(count) => AppMessage::info(format!("Exported {count} keys to {file}.")),
+            Err(err) => AppMessage::error(format!("Error when exporting the key: {}", err.message())),
+        }
+    }
+
+    fn input_stage_export_timestamps(&mut self, stage: StageExportTimestamps) {
+        let confirm = move |text: String| {
+            let result = treefile::export(stage.key.as_ref(), &stage.path, stage.recursive, text == "Yes", stage.format)
+                .and_then(|(text, count)| {
+                    std::fs::write(&stage.file, text)?;
+                    Ok(count)
+                });
+
+            (Some(Self::export_message(result, &stage.file)), PostAction::None)
+        };
+
+        self.input.label = "Include Timestamps:".into();
+        self.set_choice_input(vec!["No", "Yes"], Box::new(confirm));
+    }
+
+    /// Shows what importing a file would change before anything is applied.
+    fn preview_import(backend: &dyn RegistryBackend, path: String, file: RegFile) -> (Option<AppMessage>, PostAction) {
+        match regfile::preview(backend, &file) {
+            Ok(changes) if changes.is_empty() => (Some(AppMessage::info("The file doesn't change anything.")), PostAction::None),
+            Ok(changes) => (
+                None,
+                PostAction::Stage(ActionStage {
+                    ty: InputStageType::ImportPreview(StageImportPreview { path, file, changes })
+                })
+            ),
+            Err(err) => (Some(AppMessage::error(format!("Error when importing the file: {}", err.message()))), PostAction::None),
+        }
+    }
+
+    fn input_stage_import_mode(&mut self, stage: StageImportMode) {
+        let backend = self.backend.clone();
+        self.input.label = format!("Import Into {}:", stage.tree.path);
+
+        let confirm = move |text: String| {
+            let mode = match text.as_str() {
+                "Replace" => ImportMode::Replace,
+                _ => ImportMode::Merge,
+            };
+
+            match treefile::operations(backend.as_ref(), &stage.tree, mode) {
+                Ok(file) => Self::preview_import(backend.as_ref(), stage.path.clone(), file),
+                Err(err) => (Some(AppMessage::error(format!("Error when importing the file: {}", err.message()))), PostAction::None),
+            }
+        };
+
+        self.set_choice_input(vec!["Merge", "Replace"], Box::new(confirm));
     }
 
     fn post_action_stage(&mut self, action: ActionStage) {
         match action.ty {
             InputStageType::NewValueType(stage) => self.input_stage_new_value_type(stage),
             InputStageType::NewValueData(stage) => self.input_stage_new_value_data(stage),
+            InputStageType::ConfirmConversion(stage) => self.input_stage_confirm_conversion(stage),
+            InputStageType::ImportPreview(stage) => self.input_stage_import_preview(stage),
+            InputStageType::ExportScope(stage) => self.input_stage_export_scope(stage),
+            InputStageType::ExportFile(stage) => self.input_stage_export_file(stage),
+            InputStageType::ImportMode(stage) => self.input_stage_import_mode(stage),
+            InputStageType::ExportTimestamps(stage) => self.input_stage_export_timestamps(stage),
+        }
+    }
+
+    fn post_action_go_to(&mut self, action: ActionGoTo) {
+        // still called from within the input, so set_message can't tell where to return to
+        if let Err(err) = self.select_key_path(&action.path) {
+            self.set_message_with_state(AppMessage::error(format!("Error when opening the key: {}", err.message())), LastSelected::Keys);
+        }
+    }
+
+    fn post_action_find(&mut self, action: ActionFind) {
+        let path = self.selected_key_components();
+
+        match Search::start(self.backend.clone(), path, &action.pattern, self.search_options.get()) {
+            Ok(search) => {
+                self.search = Some(search);
+                self.search_table.resize(0);
+
+                // the input returns to the result list once it's reset
+                self.view_state = ViewState::Input(LastSelected::Results);
+            }
+            Err(err) => self.set_message_with_state(AppMessage::error(format!("Error when starting the search: {err}")), LastSelected::Keys),
+        }
+    }
+
+    fn post_action_show_diff(&mut self, action: ActionShowDiff) {
+        self.diff_table.resize(action.diff.differences.len() * ITEM_HEIGHT);
+        self.diff = Some(action.diff);
+
+        // the input returns to the diff once it's reset
+        self.view_state = ViewState::Input(LastSelected::Diff);
+    }
+
+    fn save_bookmarks(&mut self) {
+        if let Err(err) = self.bookmarks.save() {
+            self.set_message_with_state(AppMessage::error(format!("Error when saving the bookmarks: {}", err.message())), LastSelected::Bookmarks);
+        }
+    }
+
+    fn post_action_add_bookmark(&mut self, action: ActionAddBookmark) {
+        let index = self.bookmarks.add(action.bookmark);
+
+        self.bookmark_table.resize(self.bookmarks.items.len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Bookmarks, index);
+        self.save_bookmarks();
+    }
+
+    fn post_action_rename_bookmark(&mut self, action: ActionRenameBookmark) {
+        let Some(bookmark) = self.bookmarks.items.get_mut(action.index) else { return; };
+
+        bookmark.name = action.name;
+        self.save_bookmarks();
+    }
+
+    fn post_action_delete_bookmark(&mut self, action: ActionDeleteBookmark) {
+        if action.index >= self.bookmarks.items.len() {
+            return;
+        }
+
+        self.bookmarks.items.remove(action.index);
+
+        self.bookmark_table.resize(self.bookmarks.items.len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Bookmarks, action.index.saturating_sub(1));
+        self.save_bookmarks();
+    }
+
+    fn post_action_import(&mut self, action: ActionImport) {
+        if let Some(operation) = action.operation {
+            self.record(operation);
+        }
+
+        self.post_action_refresh();
+    }
+
+    fn post_action_refresh(&mut self) {
+        // keys that no longer exist are dropped along with everything below them
+        let mut keep = 0;
+        for state in self.key_states.iter_mut() {
+            let Ok(mut subkeys) = registry::read_subkeys(state.key.as_ref()) else { break; };
+            subkeys.insert(0, "..".into());
+
+            state.subkeys = subkeys;
+            state.cached_values.clear();
+            keep += 1;
+        }
+        self.key_states.truncate(keep);
+
+        if let Some(tree) = self.tree.as_mut() {
+            tree.clear_children();
         }
+
+        let selected = self.key_table.selected().unwrap_or(0).min(self.get_subkeys().len().saturating_sub(1));
+
+        self.key_table.resize(self.get_subkeys().len() * ITEM_HEIGHT);
+        self.select_row_in(ViewState::Keys, selected);
+        self.update_values();
     }
 
     pub fn confirm_input(&mut self) {
@@ -600,39 +1659,54 @@ impl AppContext {
         let text = match self.input.ty {
             InputType::TextArea => self.input.text(),
             InputType::Choice(ref choices) => choices.items[choices.selected].clone(),
+            InputType::HexEditor(ref editor) => editor.to_hex_string(),
+            InputType::List(ref list) => list.to_text(),
         };
 
         let mut should_reset_input = true;
 
-        match self.input.confirm_fn.as_ref() {
-            Some(confirm_fn) => {
-                let (message, action) = (confirm_fn)(text);
-                let last_selected = match self.view_state {
-                    ViewState::Input(last_selected) => last_selected,
-                    _ => LastSelected::None,
-                };
+        if let Some(confirm_fn) = self.input.confirm_fn.as_ref() {
+            let (message, action) = (confirm_fn)(text);
+            let last_selected = match self.view_state {
+                ViewState::Input(last_selected) => last_selected,
+                _ => LastSelected::None,
+            };
 
-                message.map(|result| self.set_message_with_state(result, last_selected));
+            if let Some(result) = message {
+                self.set_message_with_state(result, last_selected);
+            }
 
-                match action {
-                    PostAction::AddSubkey(action) => self.post_action_add_subkey(action),
-                    PostAction::RenameSubkey(action) => self.post_action_rename_subkey(action),
-                    PostAction::DeleteSubkey(action) => self.post_action_delete_subkey(action),
+            match action {
+                PostAction::AddSubkey(action) => self.post_action_add_subkey(action),
+                PostAction::RenameSubkey(action) => self.post_action_rename_subkey(action),
+                PostAction::DeleteSubkey(action) => self.post_action_delete_subkey(action),
+                PostAction::AddValue(action) => self.post_action_add_value(action),
+                PostAction::RenameValue(action) => self.post_action_rename_value(action),
+                PostAction::DeleteValue(action) => self.post_action_delete_value(action),
+
+                PostAction::Stage(action) => {
+                    should_reset_input = false;
+                    self.post_action_stage(action);
+                }
 
-                    PostAction::Stage(action) => {
-                        should_reset_input = false;
-                        self.post_action_stage(action);
-                    }
+                PostAction::GoTo(action) => self.post_action_go_to(action),
+                PostAction::Find(action) => self.post_action_find(action),
+                PostAction::ShowDiff(action) => self.post_action_show_diff(action),
+                PostAction::AddBookmark(action) => self.post_action_add_bookmark(action),
+                PostAction::RenameBookmark(action) => self.post_action_rename_bookmark(action),
+                PostAction::DeleteBookmark(action) => self.post_action_delete_bookmark(action),
+                PostAction::Import(action) => self.post_action_import(action),
+                PostAction::Refresh => self.post_action_refresh(),
 
-                    PostAction::None => (),
-                };
-            }
-            None => (),
-        };
+                PostAction::None => (),
+            };
+        }
 
         if should_reset_input {
             self.reset_input();
         }
+
+        self.sync_tree();
     }
 
     pub fn set_message_with_state(&mut self, message: AppMessage, last_selected: LastSelected) {
@@ -653,7 +1727,7 @@ impl AppContext {
         self.message = None;
     }
 
-    fn key_name_validator(input: &str, subkeys: &Vec<String>, exclude_keys: &Vec<String>) -> Result<(), String> {
+    fn key_name_validator(input: &str, subkeys: &[String], exclude_keys: &[String]) -> Result<(), String> {
         if input.trim().is_empty() {
             return Err("Can't be empty".into());
         }
@@ -673,7 +1747,7 @@ impl AppContext {
         Ok(())
     }
 
-    fn value_name_validator(input: &str, values: &Vec<NamedValue>, exclude_values: &Vec<NamedValue>) -> Result<(), String> {
+    fn value_name_validator(input: &str, values: &[NamedValue], exclude_values: &[NamedValue]) -> Result<(), String> {
         if input.trim().is_empty() {
             return Err("Can't be empty".into());
         }
@@ -694,7 +1768,7 @@ impl AppContext {
     }
 
     fn get_selected_subkey(&self) -> Option<(&KeyState, &String)> {
-        let selected = self.key_table.state.selected()?;
+        let selected = self.key_table.selected()?;
         let state = self.key_states.last()?;
 
         let subkey = state.subkeys.get(selected)?;
@@ -704,7 +1778,7 @@ impl AppContext {
 
     pub fn new_key(&mut self) {
         let Some((key, subkeys)) = self.key_states.last()
-            .map(|s| (registry::clone_key(&s.key), s.subkeys.clone()))
+            .map(|s| (registry::clone_key(s.key.as_ref()), s.subkeys.clone()))
             else {
                 self.set_message(AppMessage::error("Can't create a key here."));
                 return;
@@ -714,7 +1788,7 @@ impl AppContext {
         let validate = move |input: &str| { Self::key_name_validator(input, &subkeys, &exclude) };
 
         let confirm = move |input: String| {
-            match registry::new_key(&key, input.as_str()) {
+            match registry::new_key(key.as_ref(), input.as_str()) {
                 Ok(()) => {
                     (Some(AppMessage::info("New key successfully created.")), PostAction::AddSubkey(ActionAddSubkey { name: input }))
                 }
@@ -729,6 +1803,12 @@ impl AppContext {
     }
 
     pub fn new_value(&mut self) {
+        // ".." doesn't have any values
+        if self.key_table.selected() == Some(0) {
+            self.set_message(AppMessage::info("No key selected."));
+            return;
+        }
+
         let (state, key) = match self.get_selected_subkey() {
             Some((state, key)) => (state, key),
             None => {
@@ -739,6 +1819,15 @@ impl AppContext {
 
         let Some(values) = state.cached_values.get(key).cloned() else { unreachable!() };
 
+        let subkey = key.clone();
+        let key = match registry::read_key(state.key.as_ref(), subkey.as_str()) {
+            Ok(key) => key,
+            Err(err) => {
+                self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+                return;
+            }
+        };
+
         let exclude = Vec::new();
         let validate = move |input: &str| { Self::value_name_validator(input, &values, &exclude) };
 
@@ -746,7 +1835,7 @@ impl AppContext {
             (
                 None,
                 PostAction::Stage(ActionStage {
-                    ty: InputStageType::NewValueType(StageNewValueType { name: input }) 
+                    ty: InputStageType::NewValueType(StageNewValueType { key: registry::clone_key(key.as_ref()), subkey: subkey.clone(), name: input })
                 })
             )
         };
@@ -773,19 +1862,19 @@ impl AppContext {
 
     pub fn rename_key(&mut self) {
         let Some((key, subkeys)) = self.key_states.last()
-        .map(|s| (registry::clone_key(&s.key), s.subkeys.clone()))
+        .map(|s| (registry::clone_key(s.key.as_ref()), s.subkeys.clone()))
         else {
             self.set_message(AppMessage::error("Can't rename a key here."));
             return;
         };
 
-        let selection = self.key_table.state.selected().unwrap_or(0);
+        let selection = self.key_table.selected().unwrap_or(0);
         if selection == 0 {
             self.set_message(AppMessage::error("No key selected."));
             return;
         }
 
-        let current_name = (&subkeys[selection]).to_owned();
+        let current_name = subkeys[selection].to_owned();
         let short_name = Self::truncate_name(current_name.as_str(), 10, 3);
 
         let exclude = vec![current_name.clone()];
@@ -797,7 +1886,7 @@ impl AppContext {
         };
 
         let confirm = move |input: String| {
-            match registry::rename_key(&key, current_name.as_str(), input.as_str()) {
+            match registry::rename_key(key.as_ref(), current_name.as_str(), input.as_str()) {
                 Ok(()) => {
                     (Some(AppMessage::info("The key has been successfully renamed.")), PostAction::RenameSubkey(ActionRenameSubkey { original: current_name.clone(), new: input }))
                 }
@@ -811,34 +1900,123 @@ impl AppContext {
         self.set_textarea_input(Box::new(validate), Box::new(confirm));
     }
 
+    /// Opens the key the value table belongs to and returns it along with the selected value.
+    fn get_selected_value(&mut self) -> Option<(Box<dyn RegistryKey>, String, NamedValue)> {
+        let value = self.value_table.selected().and_then(|i| self.get_values()?.get(i).cloned());
+
+        let (Some(value), Some((state, subkey))) = (value, self.get_selected_subkey()) else {
+            self.set_message(AppMessage::info("No value selected."));
+            return None;
+        };
+
+        let subkey = subkey.clone();
+        let result = registry::read_key(state.key.as_ref(), subkey.as_str());
+
+        match result {
+            Ok(key) => Some((key, subkey, value)),
+            Err(err) => {
+                self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+                None
+            }
+        }
+    }
+
+    /// Backs up a value that's about to be overwritten, returning the note for the success message.
+    fn backup_value(path: &str, name: &str, previous: Option<&registry::Value>) -> Result<String, AppMessage> {
+        let Some(previous) = previous else { return Ok(String::new()); };
+
+        match backup::save_value(path, name, previous) {
+            Ok(file) => Ok(format!(" Backup saved to {}.", file.display())),
+            Err(err) => Err(AppMessage::error(format!("Error when backing up the value: {}", err.message()))),
+        }
+    }
+
+    fn write_value(key: &dyn RegistryKey, path: &str, subkey: &str, name: &str, value: &registry::Value, success: &str) -> (Option<AppMessage>, PostAction) {
+        let previous = key.get_value(name).ok();
+        let note = match Self::backup_value(path, name, previous.as_ref()) {
+            Ok(note) => note,
+            Err(message) => return (Some(message), PostAction::None),
+        };
+
+        match key.set_value(name, value) {
+            Ok(()) => {
+                let value = NamedValue::new(name.to_owned(), value.clone());
+                (Some(AppMessage::info(format!("{success}{note}"))), PostAction::AddValue(ActionAddValue { subkey: subkey.to_owned(), value, previous }))
+            }
+            Err(err) => {
+                (Some(AppMessage::error(format!("Error when writing the value: {}", err.message()))), PostAction::None)
+            }
+        }
+    }
+
     pub fn rename_value(&mut self) {
-        todo!()
+        let Some((key, subkey, value)) = self.get_selected_value() else { return; };
+        let Some(values) = self.get_values().cloned() else { unreachable!() };
+
+        let current_name = value.name.clone();
+        let short_name = Self::truncate_name(current_name.as_str(), 10, 3);
+
+        let exclude = vec![value];
+        let validate = move |input: &str| {
+            if input == exclude[0].name {
+                return Err("The name of the value must be new".into());
+            }
+            Self::value_name_validator(input, &values, &exclude)
+        };
+
+        let confirm = move |input: String| {
+            match registry::rename_value(key.as_ref(), current_name.as_str(), input.as_str()) {
+                Ok(()) => {
+                    (Some(AppMessage::info("The value has been successfully renamed.")), PostAction::RenameValue(ActionRenameValue { subkey: subkey.clone(), original: current_name.clone(), new: input }))
+                }
+                Err(err) => {
+                    (Some(AppMessage::error(format!("Error when renaming the value: {}", err.message()))), PostAction::None)
+                }
+            }
+        };
+
+        self.input.label = format!("Enter New Name ({}):", short_name);
+        self.set_textarea_input(Box::new(validate), Box::new(confirm));
     }
 
     pub fn delete_key(&mut self) {
         let Some((key, subkeys)) = self.key_states.last()
-            .map(|s| (registry::clone_key(&s.key), s.subkeys.clone()))
+            .map(|s| (registry::clone_key(s.key.as_ref()), s.subkeys.clone()))
             else {
                 self.set_message(AppMessage::error("Can't delete a key here."));
                 return;
             };
 
-        let selection = self.key_table.state.selected().unwrap_or(0);
+        let selection = self.key_table.selected().unwrap_or(0);
         if selection == 0 {
             self.set_message(AppMessage::error("No key selected."));
             return;
         }
 
-        let current_name = (&subkeys[selection]).to_owned();
+        let current_name = subkeys[selection].to_owned();
+        let path = self.child_path(&current_name);
 
         let confirm = move |text: String| {
             if text == "No" {
                 return (None, PostAction::None);
             }
 
-            match registry::delete_key(&key, current_name.as_str()) {
+            // kept for undoing and backed up, a key that can't be read isn't deleted
+            let tree = registry::read_key(key.as_ref(), current_name.as_str())
+                .and_then(|subkey| treefile::read_tree(subkey.as_ref(), &current_name, true));
+            let tree = match tree {
+                Ok(tree) => tree,
+                Err(err) => return (Some(AppMessage::error(format!("Error when deleting the key: {}", err.message()))), PostAction::None),
+            };
+            let backup = match backup::save_key(&path, &tree) {
+                Ok(backup) => backup,
+                Err(err) => return (Some(AppMessage::error(format!("Error when backing up the key: {}", err.message()))), PostAction::None),
+            };
+
+            match registry::delete_key(key.as_ref(), current_name.as_str()) {
                 Ok(()) => {
-                    (Some(AppMessage::info("The key has been successfully deleted.")), PostAction::DeleteSubkey(ActionDeleteSubkey { name: current_name.clone() }))
+                    let message = format!("The key has been successfully deleted. Backup saved to {}.", backup.display());
+                    (Some(AppMessage::info(message)), PostAction::DeleteSubkey(ActionDeleteSubkey { name: current_name.clone(), tree }))
                 }
                 Err(err) => {
                     (Some(AppMessage::error(format!("Error when deleting the key: {}", err.message()))), PostAction::None)
@@ -851,38 +2029,791 @@ impl AppContext {
     }
 
     pub fn change_type(&mut self) {
-        todo!()
-    }
+        let Some((key, subkey, value)) = self.get_selected_value() else { return; };
 
-    pub fn change_data(&mut self) {
-        todo!()
-    }
+        let path = self.child_path(&subkey);
+        let choices = registry::get_type_choices_vec();
+        let current = choices.iter().position(|ty| ty == registry::type_to_str(value.value.ty()));
 
-    pub fn delete_value(&mut self) {
-        todo!()
-    }
+        let confirm = move |input: String| {
+            let conversion = registry::convert_value(&value.value, registry::str_to_type(input.as_ref()));
+
+            match conversion.warning {
+                Some(warning) => (
+                    None,
+                    PostAction::Stage(ActionStage {
+                        ty: InputStageType::ConfirmConversion(StageConfirmConversion {
+                            key: registry::clone_key(key.as_ref()),
+                            path: path.clone(),
+                            subkey: subkey.clone(),
+                            name: value.name.clone(),
+                            value: conversion.value,
+                            warning,
+                        })
+                    })
+                ),
+                None => Self::write_value(key.as_ref(), &path, &subkey, &value.name, &conversion.value, "The type has been successfully changed."),
+            }
+        };
 
-    fn dispatch_by_view<F, G>(&mut self, on_keys: F, on_values: G)
-    where
-        F: FnOnce(&mut Self),
-        G: FnOnce(&mut Self),
-    {
-        match self.view_state {
-            ViewState::Keys => on_keys(self),
-            ViewState::Values => on_values(self),
-            _ => unreachable!(),
+        self.input.label = "Choose Type:".into();
+        self.set_choice_input(choices, Box::new(confirm));
+
+        if let InputType::Choice(ref mut choices) = self.input.ty {
+            choices.selected = current.unwrap_or(0);
         }
     }
 
-    pub fn create(&mut self) {
-        self.dispatch_by_view(Self::new_key, Self::new_value);
-    }
+    pub fn change_data(&mut self) {
+        let Some((key, subkey, value)) = self.get_selected_value() else { return; };
 
-    pub fn rename(&mut self) {
-        self.dispatch_by_view(Self::rename_key, Self::rename_value);
-    }
+        let ty = value.value.ty();
+        let validator = registry::get_value_validator(ty);
 
-    pub fn delete(&mut self) {
-        self.dispatch_by_view(Self::delete_key, Self::delete_value);
+        let validate = move |input: &str| {
+            (*validator).validate(input).map_err(registry::parser_error_message)
+        };
+
+        let path = self.child_path(&subkey);
+        let current = registry::get_editable_value(&value.value);
+        let data = value.value.to_vec();
+        let entries = registry::decode_multi_sz(&value.value);
+        let confirm = move |input: String| {
+            // checked first so that a backup is only made when something gets written
+            if let Err(err) = registry::encode_value(ty, &input) {
+                return (Some(AppMessage::error(format!("Error when changing the data: {}", registry::parser_error_message(err)))), PostAction::None);
+            }
+            let note = match Self::backup_value(&path, &value.name, Some(&value.value)) {
+                Ok(note) => note,
+                Err(message) => return (Some(message), PostAction::None),
+            };
+
+            match registry::set_value(key.as_ref(), value.name.as_str(), ty, input.as_str()) {
+                Ok(data) => {
+                    let previous = Some(value.value.clone());
+                    let value = NamedValue::new(value.name.clone(), data);
+                    (Some(AppMessage::info(format!("The data has been successfully changed.{note}"))), PostAction::AddValue(ActionAddValue { subkey: subkey.clone(), value, previous }))
+                }
+                Err(err) => {
+                    (Some(AppMessage::error(format!("Error when changing the data: {}", err.message()))), PostAction::None)
+                }
+            }
+        };
+
+        self.input.label = "Enter Value:".into();
+        match ty {
+            registry::Type::MultiString => self.set_list_input(entries, Box::new(confirm)),
+            ty if ty.is_raw() => self.set_hex_input(data, Box::new(confirm)),
+            _ => {
+                self.set_textarea_input(Box::new(validate), Box::new(confirm));
+                self.input.textarea.insert_str(current);
+            }
+        };
+    }
+
+    pub fn delete_value(&mut self) {
+        let Some((key, subkey, value)) = self.get_selected_value() else { return; };
+
+        let path = self.child_path(&subkey);
+        let confirm = move |text: String| {
+            if text == "No" {
+                return (None, PostAction::None);
+            }
+
+            let note = match Self::backup_value(&path, &value.name, Some(&value.value)) {
+                Ok(note) => note,
+                Err(message) => return (Some(message), PostAction::None),
+            };
+
+            match registry::delete_value(key.as_ref(), value.name.as_str()) {
+                Ok(()) => {
+                    (Some(AppMessage::info(format!("The value has been successfully deleted.{note}"))), PostAction::DeleteValue(ActionDeleteValue { subkey: subkey.clone(), name: value.name.clone(), value: value.value.clone() }))
+                }
+                Err(err) => {
+                    (Some(AppMessage::error(format!("Error when deleting the value: {}", err.message()))), PostAction::None)
+                }
+            }
+        };
+
+        self.input.label = "Confirm Delete:".into();
+        self.set_choice_input(vec!["No", "Yes"], Box::new(confirm));
+    }
+
+    fn journal_step(&mut self, result: backend::Result<Option<Operation>>, done: &str, doing: &str, nothing: &str) {
+        let message = match result {
+            Ok(Some(operation)) => AppMessage::info(format!("{done}: {}.", operation.describe())),
+            Ok(None) => {
+                self.set_message(AppMessage::info(nothing));
+                return;
+            }
+            Err(err) => AppMessage::error(format!("Error when {doing} the change: {}", err.message())),
+        };
+
+        // a failed step may still have changed part of a subtree
+        self.post_action_refresh();
+        self.sync_tree();
+        self.set_message(message);
+    }
+
+    pub fn undo(&mut self) {
+        let result = self.journal.undo(self.backend.as_ref());
+        self.journal_step(result, "Undone", "undoing", "Nothing to undo.");
+    }
+
+    pub fn redo(&mut self) {
+        let result = self.journal.redo(self.backend.as_ref());
+        self.journal_step(result, "Redone", "redoing", "Nothing to redo.");
+    }
+
+    /// Address bar paths may use forward slashes.
+    fn normalize_path(input: &str) -> String {
+        input.trim().replace('/', "\\")
+    }
+
+    fn complete_path(backend: &dyn RegistryBackend, input: &str) -> Vec<String> {
+        let path = Self::normalize_path(input);
+
+        let Some((parent, prefix)) = path.rsplit_once('\\') else {
+            return backend.roots()
+                .into_iter()
+                .filter(|root| root.to_uppercase().starts_with(&path.to_uppercase()) || root.eq_ignore_ascii_case(registry::expand_root_name(&path)))
+                .map(|root| format!("{root}\\"))
+                .collect();
+        };
+
+        let Ok(key) = backend::open_path(backend, parent) else { return Vec::new(); };
+        let Ok(subkeys) = registry::read_subkeys(key.as_ref()) else { return Vec::new(); };
+
+        subkeys.into_iter()
+            .filter(|subkey| subkey.to_uppercase().starts_with(&prefix.to_uppercase()))
+            .map(|subkey| format!("{parent}\\{subkey}\\"))
+            .collect()
+    }
+
+    pub fn go_to_path(&mut self) {
+        let validate_backend = self.backend.clone();
+        let validate = move |input: &str| {
+            backend::open_path(validate_backend.as_ref(), &Self::normalize_path(input))
+                .map(|_| ())
+                .map_err(|err| err.message().to_owned())
+        };
+
+        let confirm_backend = self.backend.clone();
+        let confirm = move |input: String| {
+            match backend::resolve_path(confirm_backend.as_ref(), &Self::normalize_path(&input)) {
+                Ok((root, components)) => {
+                    let path = std::iter::once(root).chain(components).collect();
+                    (None, PostAction::GoTo(ActionGoTo { path }))
+                }
+                Err(err) => (Some(AppMessage::error(err.message())), PostAction::None),
+            }
+        };
+
+        let complete_backend = self.backend.clone();
+        let complete = move |input: &str| Self::complete_path(complete_backend.as_ref(), input);
+
+        let current = self.selected_key_components().join("\\");
+
+        self.input.label = "Go To:".into();
+        self.set_textarea_input(Box::new(validate), Box::new(confirm));
+        self.input.complete_fn = Some(Box::new(complete));
+        self.input.textarea.insert_str(current);
+    }
+
+    fn find_label(options: SearchOptions) -> String {
+        format!("Find ({}):", options.describe())
+    }
+
+    pub fn find(&mut self) {
+        let validate_options = self.search_options.clone();
+        let validate = move |input: &str| {
+            validate_options.get().build_matcher(input).map(|_| ())
+        };
+
+        let confirm = |input: String| (None, PostAction::Find(ActionFind { pattern: input }));
+
+        let toggle_options = self.search_options.clone();
+        let toggle = move |c: char| {
+            let mut options = toggle_options.get();
+            if !options.toggle(c) {
+                return None;
+            }
+
+            toggle_options.set(options);
+            Some(Self::find_label(options))
+        };
+
+        let pattern = self.search.as_ref().map(|search| search.pattern.clone()).unwrap_or_default();
+
+        self.input.label = Self::find_label(self.search_options.get());
+        self.set_textarea_input(Box::new(validate), Box::new(confirm));
+        self.input.toggle_fn = Some(Box::new(toggle));
+        self.input.textarea.set_placeholder_text("<Alt-K/V/D> Keys/Values/Data, <Alt-C> Match Case, <Alt-W> Whole Word, <Alt-R> Regex");
+        self.input.textarea.insert_str(pattern);
+    }
+
+    /// Collects the hits the search found in the background.
+    pub fn poll_search(&mut self) {
+        let Some(search) = self.search.as_mut() else { return; };
+
+        if search.poll() {
+            let len = search.hits.len() * ITEM_HEIGHT;
+
+            self.search_table.scroll = self.search_table.scroll.content_length(len);
+            self.search_table.content_length = len;
+
+            // rendering the empty table clears the selection
+            if self.search_table.state.selected().is_none() {
+                self.search_table.state.select(Some(0));
+            }
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.as_ref().is_some_and(|search| !search.done)
+    }
+
+    pub fn close_search(&mut self) {
+        self.search = None;
+
+        if self.view_state == ViewState::Results {
+            self.view_state = ViewState::Keys;
+        }
+    }
+
+    /// Opens the key of the hit and highlights the matching key or value.
+    fn jump_to_hit(&mut self, i: usize) {
+        let Some(search) = self.search.as_mut() else { return; };
+        let Some(hit) = search.hits.get(i).cloned() else { return; };
+
+        search.current = Some(i);
+        self.search_table.state.select(Some(i));
+        self.search_table.scroll = self.search_table.scroll.position(i * ITEM_HEIGHT);
+
+        if let Err(err) = self.select_key_path(&hit.path) {
+            self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+            return;
+        }
+
+        // syncing reselects the key, which resets the value table
+        self.sync_tree();
+        self.view_state = ViewState::Keys;
+
+        let Some(name) = hit.value else { return; };
+        let index = self.get_values().and_then(|values| values.iter().position(|value| value.name.eq_ignore_ascii_case(&name)));
+
+        if let Some(index) = index {
+            self.view_state = ViewState::Values;
+            self.select_row_in(ViewState::Values, index);
+        }
+    }
+
+    pub fn jump_to_selected_hit(&mut self) {
+        let Some(i) = self.search_table.state.selected() else { return; };
+
+        self.jump_to_hit(i);
+    }
+
+    /// Jumps to the hit after the last one, or asks for a pattern when nothing was searched yet.
+    pub fn find_next(&mut self) {
+        let Some(search) = self.search.as_ref() else {
+            self.find();
+            return;
+        };
+
+        let next = search.current.map_or(0, |i| i + 1);
+        if next < search.hits.len() {
+            self.jump_to_hit(next);
+            return;
+        }
+
+        let message = match search.done {
+            true => "No more results.",
+            false => "No more results yet, the search is still running.",
+        };
+        self.set_message(AppMessage::info(message));
+    }
+
+    pub fn find_prev(&mut self) {
+        let Some(search) = self.search.as_ref() else { return; };
+
+        match search.current {
+            Some(i) if i > 0 => self.jump_to_hit(i - 1),
+            _ => self.set_message(AppMessage::info("No previous results.")),
+        }
+    }
+
+    fn file_validator(input: &str) -> Result<(), String> {
+        match input.trim().is_empty() {
+            true => Err("Can't be empty".into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Saves the key highlighted in the subkey table with everything below it.
+    pub fn take_snapshot(&mut self) {
+        let path = self.selected_key_components();
+        let Some(name) = path.last().cloned() else {
+            self.set_message(AppMessage::info("No key selected."));
+            return;
+        };
+
+        let backend = self.backend.clone();
+        let path = path.join("\\");
+        let confirm = move |input: String| {
+            let file = input.trim();
+
+            match Snapshot::take(backend.as_ref(), &path).and_then(|snapshot| snapshot.save(file)) {
+                Ok(()) => (Some(AppMessage::info(format!("Saved a snapshot of {path} to {file}."))), PostAction::None),
+                Err(err) => (Some(AppMessage::error(format!("Error when taking the snapshot: {}", err.message()))), PostAction::None),
+            }
+        };
+
+        self.input.label = "Save Snapshot To:".into();
+        self.set_textarea_input(Box::new(Self::file_validator), Box::new(confirm));
+        self.input.textarea.insert_str(format!("{name}.snap"));
+    }
+
+    /// Compares a snapshot file with the key as it is now.
+    pub fn compare_snapshot(&mut self) {
+        let backend = self.backend.clone();
+        let confirm = move |input: String| {
+            let file = input.trim();
+
+            let result = Snapshot::load(file).and_then(|old| Ok((Snapshot::take(backend.as_ref(), &old.path)?, old)));
+            match result {
+                Ok((new, old)) => {
+                    let differences = snapshot::diff(&old, &new);
+                    if differences.is_empty() {
+                        return (Some(AppMessage::info(format!("Nothing changed in {} since the snapshot.", new.path))), PostAction::None);
+                    }
+
+                    let title = format!("Changes in {} since {file}", new.path);
+                    (None, PostAction::ShowDiff(ActionShowDiff { diff: SnapshotDiff { title, differences } }))
+                }
+                Err(err) => (Some(AppMessage::error(format!("Error when comparing the snapshot: {}", err.message()))), PostAction::None),
+            }
+        };
+
+        self.input.label = "Compare With Snapshot:".into();
+        self.set_textarea_input(Box::new(Self::file_validator), Box::new(confirm));
+    }
+
+    pub fn close_diff(&mut self) {
+        self.diff = None;
+
+        if self.view_state == ViewState::Diff {
+            self.view_state = ViewState::Keys;
+        }
+    }
+
+    /// Opens the key of the selected difference and highlights the value.
+    /// A removed key can't be opened, so its parent is instead.
+    pub fn jump_to_selected_difference(&mut self) {
+        let Some(difference) = self.diff_table.selected().and_then(|i| self.diff.as_ref()?.differences.get(i)).cloned() else { return; };
+
+        let path = match difference.kind {
+            ChangeKind::DeleteKey => &difference.path[..difference.path.len() - 1],
+            _ => &difference.path[..],
+        };
+
+        if let Err(err) = self.select_key_path(path) {
+            self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message())));
+            return;
+        }
+
+        self.sync_tree();
+        self.view_state = ViewState::Keys;
+
+        let Some(name) = difference.value.filter(|_| difference.kind != ChangeKind::DeleteValue) else { return; };
+        let index = self.get_values().and_then(|values| values.iter().position(|value| value.name.eq_ignore_ascii_case(&name)));
+
+        if let Some(index) = index {
+            self.view_state = ViewState::Values;
+            self.select_row_in(ViewState::Values, index);
+        }
+    }
+
+    /// Writes a .reg file that makes the changes of the diff.
+    pub fn save_patch(&mut self) {
+        let Some(diff) = self.diff.as_ref() else { return; };
+
+        let text = snapshot::patch(&diff.differences);
+        let confirm = move |input: String| {
+            let file = input.trim();
+
+            match std::fs::write(file, regfile::encode_text(&text)) {
+                Ok(()) => (Some(AppMessage::info(format!("Saved the patch to {file}."))), PostAction::None),
+                Err(err) => (Some(AppMessage::error(format!("Error when saving the patch: {err}"))), PostAction::None),
+            }
+        };
+
+        self.input.label = "Save Patch To:".into();
+        self.set_textarea_input(Box::new(Self::file_validator), Box::new(confirm));
+    }
+
+    /// Whether the bookmark popup is shown, possibly behind an input or message.
+    pub fn is_bookmarks_open(&self) -> bool {
+        matches!(self.view_state, ViewState::Bookmarks | ViewState::Input(LastSelected::Bookmarks) | ViewState::Message(LastSelected::Bookmarks))
+    }
+
+    pub fn open_bookmarks(&mut self) {
+        self.view_state = ViewState::Bookmarks;
+        self.bookmark_table.resize(self.bookmarks.items.len() * ITEM_HEIGHT);
+    }
+
+    pub fn close_bookmarks(&mut self) {
+        if self.view_state == ViewState::Bookmarks {
+            self.view_state = ViewState::Keys;
+        }
+    }
+
+    fn bookmark_name_validator(input: &str, bookmarks: &[Bookmark], exclude: Option<usize>) -> Result<(), String> {
+        if input.trim().is_empty() {
+            return Err("Can't be empty".into());
+        }
+
+        match bookmarks.iter().position(|bookmark| bookmark.name.eq_ignore_ascii_case(input.trim())) {
+            Some(index) if Some(index) != exclude => Err("This bookmark already exists".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Bookmarks the key highlighted in the subkey table.
+    pub fn add_bookmark(&mut self) {
+        let path = self.selected_key_components();
+        let Some(name) = path.last().cloned() else {
+            self.set_message(AppMessage::info("No key selected."));
+            return;
+        };
+
+        let bookmarks = self.bookmarks.items.clone();
+        let validate = move |input: &str| Self::bookmark_name_validator(input, &bookmarks, None);
+
+        let path = path.join("\\");
+        let confirm = move |input: String| {
+            let bookmark = Bookmark { name: input.trim().to_owned(), path: path.clone() };
+            (None, PostAction::AddBookmark(ActionAddBookmark { bookmark }))
+        };
+
+        self.input.label = "Bookmark Name:".into();
+        self.set_textarea_input(Box::new(validate), Box::new(confirm));
+        self.input.textarea.insert_str(name);
+    }
+
+    pub fn rename_bookmark(&mut self) {
+        let Some(index) = self.bookmark_table.selected().filter(|&index| index < self.bookmarks.items.len()) else { return; };
+
+        let bookmarks = self.bookmarks.items.clone();
+        let current = bookmarks[index].name.clone();
+        let validate = move |input: &str| Self::bookmark_name_validator(input, &bookmarks, Some(index));
+
+        let confirm = move |input: String| (None, PostAction::RenameBookmark(ActionRenameBookmark { index, name: input.trim().to_owned() }));
+
+        self.input.label = format!("Enter New Name ({}):", Self::truncate_name(&current, 10, 3));
+        self.set_textarea_input(Box::new(validate), Box::new(confirm));
+        self.input.textarea.insert_str(current);
+    }
+
+    pub fn delete_bookmark(&mut self) {
+        let Some(index) = self.bookmark_table.selected().filter(|&index| index < self.bookmarks.items.len()) else { return; };
+
+        let confirm = move |text: String| {
+            match text.as_str() {
+                "Yes" => (None, PostAction::DeleteBookmark(ActionDeleteBookmark { index })),
+                _ => (None, PostAction::None),
+            }
+        };
+
+        self.input.label = "Confirm Delete:".into();
+        self.set_choice_input(vec!["No", "Yes"], Box::new(confirm));
+    }
+
+    pub fn jump_to_bookmark(&mut self) {
+        let Some(bookmark) = self.bookmark_table.selected().and_then(|index| self.bookmarks.items.get(index)).cloned() else { return; };
+
+        let result = backend::resolve_path(self.backend.as_ref(), &bookmark.path)
+            .and_then(|(root, components)| self.select_key_path(&std::iter::once(root).chain(components).collect::<Vec<_>>()));
+
+        if let Err(err) = result {
+            self.set_message(AppMessage::error(format!("Error when opening the bookmark {}: {}", bookmark.name, err.message())));
+            return;
+        }
+
+        self.view_state = ViewState::Keys;
+        self.sync_tree();
+    }
+
+    pub fn import_file(&mut self) {
+        let backend = self.backend.clone();
+        let confirm = move |input: String| {
+            let path = input.trim().to_owned();
+
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) => return (Some(AppMessage::error(format!("Error when reading the file: {err}"))), PostAction::None),
+            };
+
+            // JSON and YAML exports ask whether to merge or replace first
+            if let Some(format) = TreeFormat::from_path(&path) {
+                return match treefile::parse(&regfile::decode_text(&bytes), format) {
+                    Ok(tree) => (
+                        None,
+                        PostAction::Stage(ActionStage {
+                            ty: InputStageType::ImportMode(StageImportMode { path, tree })
+                        })
+                    ),
+                    Err(err) => (Some(AppMessage::error(format!("Error when parsing the file: {}", err.message()))), PostAction::None),
+                };
+            }
+
+            let file = match regfile::parse(&bytes) {
+                Ok(file) => file,
+                Err(err) => return (Some(AppMessage::error(format!("Error when parsing the file: {err}"))), PostAction::None),
+            };
+
+            Self::preview_import(backend.as_ref(), path, file)
+        };
+
+        self.input.label = "Import File:".into();
+        self.set_textarea_input(Box::new(Self::file_validator), Box::new(confirm));
+    }
+
+    fn backup_label(backup: &Backup) -> String {
+        format!("{}, {} ({})", backup.taken(), backup.describe(), backup.file_name())
+    }
+
+    /// Offers the backups, newest first, and previews putting the chosen one back.
+    pub fn restore_backup(&mut self) {
+        let backups = match backup::list() {
+            Ok(backups) if backups.is_empty() => {
+                self.set_message(AppMessage::info("There are no backups yet."));
+                return;
+            }
+            Ok(backups) => backups,
+            Err(err) => {
+                self.set_message(AppMessage::error(format!("Error when listing the backups: {}", err.message())));
+                return;
+            }
+        };
+
+        let choices = backups.iter().map(Self::backup_label).collect::<Vec<_>>();
+        let backend = self.backend.clone();
+        let confirm = move |text: String| {
+            let Some(backup) = backups.iter().find(|backup| Self::backup_label(backup) == text) else { return (None, PostAction::None); };
+
+            match backup.operations(backend.as_ref()) {
+                Ok(file) => Self::preview_import(backend.as_ref(), backup.file.display().to_string(), file),
+                Err(err) => (Some(AppMessage::error(format!("Error when restoring the backup: {}", err.message()))), PostAction::None),
+            }
+        };
+
+        self.input.label = "Restore Backup:".into();
+        self.set_choice_input(choices, Box::new(confirm));
+    }
+
+    pub fn export_key(&mut self) {
+        let Some(selection) = self.key_table.selected() else {
+            self.set_message(AppMessage::error("No key selected."));
+            return;
+        };
+
+        // ".." stands for the key that's currently open
+        let result = match self.key_states.last() {
+            None => {
+                let name = self.base_subkeys[selection].clone();
+                self.backend.open_root(&name).map(|key| (key, name))
+            }
+            Some(state) if selection == 0 => Ok((registry::clone_key(state.key.as_ref()), state.key_path.clone())),
+            Some(state) => {
+                let name = &state.subkeys[selection];
+                registry::read_key(state.key.as_ref(), name).map(|key| (key, format!("{}\\{name}", state.key_path)))
+            }
+        };
+
+        match result {
+            Ok((key, path)) => self.post_action_stage(ActionStage {
+                ty: InputStageType::ExportScope(StageExportScope { key, path })
+            }),
+            Err(err) => self.set_message(AppMessage::error(format!("Error when opening the key: {}", err.message()))),
+        };
+    }
+
+    fn dispatch_by_view<F, G>(&mut self, on_keys: F, on_values: G)
+    where
+        F: FnOnce(&mut Self),
+        G: FnOnce(&mut Self),
+    {
+        match self.view_state {
+            ViewState::Keys => on_keys(self),
+            ViewState::Values => on_values(self),
+            _ => (),
+        }
+    }
+
+    pub fn create(&mut self) {
+        self.dispatch_by_view(Self::new_key, Self::new_value);
+    }
+
+    pub fn rename(&mut self) {
+        self.dispatch_by_view(Self::rename_key, Self::rename_value);
+    }
+
+    pub fn delete(&mut self) {
+        self.dispatch_by_view(Self::delete_key, Self::delete_value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{backend::memory::MemoryBackend, config::TestConfigDir};
+
+    const FIXTURE: &str = r#"{
+        "HKEY_CURRENT_USER": {
+            "keys": {
+                "App": {
+                    "values": { "Name": "regcli" },
+                    "keys": { "Plugins": { "values": { "Count": 2 } } }
+                },
+                "Other": {}
+            }
+        }
+    }"#;
+
+    /// A context with HKEY_CURRENT_USER of `fixture` open, the config and
+    /// backups kept in a temporary directory for as long as the guard lives.
+    fn open_fixture(fixture: &str) -> (Arc<MemoryBackend>, AppContext, TestConfigDir) {
+        let config = TestConfigDir::create();
+        let backend = Arc::new(MemoryBackend::from_json(fixture).unwrap());
+        let mut context = AppContext::new(backend.clone());
+        assert_eq!(context.view_state, ViewState::Keys);
+
+        context.select();
+        (backend, context, config)
+    }
+
+    fn open_context() -> (Arc<MemoryBackend>, AppContext, TestConfigDir) {
+        let (backend, context, config) = open_fixture(FIXTURE);
+        assert_eq!(context.get_subkeys(), &["..", "App", "Other"]);
+        (backend, context, config)
+    }
+
+    fn type_and_confirm(context: &mut AppContext, text: &str) {
+        context.input.textarea.insert_str(text);
+        context.confirm_input();
+    }
+
+    fn choose_and_confirm(context: &mut AppContext, choice: &str) {
+        if let InputType::Choice(ref mut choices) = context.input.ty {
+            choices.selected = choices.items.iter().position(|item| item == choice).unwrap();
+        }
+        context.confirm_input();
+    }
+
+    fn message(context: &AppContext) -> &str {
+        context.message.as_ref().map(|message| message.message.as_str()).unwrap_or_default()
+    }
+
+    #[test]
+    fn update_values_reads_the_selected_key() {
+        let (_, mut context, _config) = open_context();
+
+        context.select_row_in(ViewState::Keys, 1);
+        let values = context.get_values().unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].name, "Name");
+
+        context.select_row_in(ViewState::Keys, 2);
+        assert!(context.get_values().unwrap().is_empty());
+    }
+
+    #[test]
+    fn new_key_is_created_and_recorded() {
+        let (backend, mut context, _config) = open_context();
+
+        context.new_key();
+        type_and_confirm(&mut context, "New");
+        assert_eq!(message(&context), "New key successfully created.");
+        assert_eq!(context.get_subkeys(), &["..", "App", "New", "Other"]);
+        assert!(backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\New").is_ok());
+
+        context.cancel_message();
+        context.undo();
+        assert_eq!(message(&context), "Undone: create the key HKEY_CURRENT_USER\\New.");
+        assert!(backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\New").is_err());
+    }
+
+    #[test]
+    fn new_key_names_are_validated() {
+        let (backend, mut context, _config) = open_context();
+
+        context.new_key();
+        type_and_confirm(&mut context, "app");
+        assert!(matches!(context.view_state, ViewState::Input(_)));
+        assert!(context.input.validate().unwrap().is_err());
+
+        context.input.textarea.select_all();
+        context.input.textarea.cut();
+        type_and_confirm(&mut context, "a/b");
+        assert!(context.input.validate().unwrap().is_err());
+        assert_eq!(registry::read_subkeys(backend.open_root("HKEY_CURRENT_USER").unwrap().as_ref()).unwrap(), ["App", "Other"]);
+    }
+
+    #[test]
+    fn rename_key_keeps_what_is_below() {
+        let (backend, mut context, _config) = open_context();
+
+        context.select_row_in(ViewState::Keys, 1);
+        context.rename_key();
+        type_and_confirm(&mut context, "Renamed");
+        assert_eq!(message(&context), "The key has been successfully renamed.");
+        assert_eq!(context.get_subkeys(), &["..", "Renamed", "Other"]);
+
+        let key = backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\Renamed\\Plugins").unwrap();
+        assert!(key.get_value("Count").is_ok());
+        assert!(backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\App").is_err());
+    }
+
+    #[test]
+    fn delete_key_backs_up_and_can_be_undone() {
+        let (backend, mut context, _config) = open_context();
+
+        context.select_row_in(ViewState::Keys, 1);
+        context.delete_key();
+        choose_and_confirm(&mut context, "No");
+        assert!(backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\App").is_ok());
+
+        context.delete_key();
+        choose_and_confirm(&mut context, "Yes");
+        assert!(message(&context).starts_with("The key has been successfully deleted. Backup saved to "));
+        assert_eq!(context.get_subkeys(), &["..", "Other"]);
+        assert!(backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\App").is_err());
+
+        context.cancel_message();
+        context.undo();
+        let key = backend::open_path(backend.as_ref(), "HKEY_CURRENT_USER\\App\\Plugins").unwrap();
+        assert!(key.get_value("Count").is_ok());
+        assert_eq!(context.get_subkeys(), &["..", "App", "Other"]);
+    }
+
+    #[test]
+    fn mixed_case_keys_keep_their_rows() {
+        let (backend, mut context, _config) = open_fixture(r#"{ "HKEY_CURRENT_USER": { "keys": { "a": {}, "B": {}, "d": {} } } }"#);
+        assert_eq!(context.get_subkeys(), &["..", "a", "B", "d"]);
+
+        context.new_key();
+        type_and_confirm(&mut context, "C");
+        assert_eq!(context.get_subkeys(), &["..", "a", "B", "C", "d"]);
+        assert_eq!(context.key_table.selected(), Some(3));
+
+        context.cancel_message();
+        context.select_row_in(ViewState::Keys, 2);
+        context.delete_key();
+        choose_and_confirm(&mut context, "Yes");
+        assert_eq!(context.get_subkeys(), &["..", "a", "C", "d"]);
+        assert_eq!(registry::read_subkeys(backend.open_root("HKEY_CURRENT_USER").unwrap().as_ref()).unwrap(), ["a", "C", "d"]);
     }
 }
@@ -0,0 +1,74 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Substring,
+    Fuzzy,
+}
+
+impl FilterMode {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Substring => "substring",
+            Self::Fuzzy => "fuzzy",
+        }
+    }
+
+    pub const fn toggle(self) -> Self {
+        match self {
+            Self::Substring => Self::Fuzzy,
+            Self::Fuzzy => Self::Substring,
+        }
+    }
+}
+
+/// Narrows a list of names down to the ones matching the query, ignoring case
+/// the way key and value names do.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub query: String,
+    pub mode: FilterMode,
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+impl Filter {
+    pub const fn new(mode: FilterMode) -> Self {
+        Self { query: String::new(), mode }
+    }
+
+    /// Positions of the characters in `text` matched by the query, or `None`
+    /// when it doesn't match.
+    pub fn find(&self, text: &str) -> Option<Vec<usize>> {
+        let query = self.query.chars().map(fold).collect::<Vec<_>>();
+        let text = text.chars().map(fold).collect::<Vec<_>>();
+
+        match self.mode {
+            FilterMode::Substring => Self::find_substring(&query, &text),
+            FilterMode::Fuzzy => Self::find_fuzzy(&query, &text),
+        }
+    }
+
+    fn find_substring(query: &[char], text: &[char]) -> Option<Vec<usize>> {
+        if query.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let start = text.windows(query.len()).position(|window| window == query)?;
+        Some((start..start + query.len()).collect())
+    }
+
+    /// Every character of the query has to appear in order, not necessarily
+    /// next to each other.
+    fn find_fuzzy(query: &[char], text: &[char]) -> Option<Vec<usize>> {
+        let mut positions = Vec::with_capacity(query.len());
+        let mut chars = text.iter().enumerate();
+
+        for c in query {
+            let (position, _) = chars.find(|(_, t)| *t == c)?;
+            positions.push(position);
+        }
+
+        Some(positions)
+    }
+}
@@ -0,0 +1,442 @@
+use std::ops::Range;
+
+pub const BYTES_PER_ROW: usize = 16;
+const MAX_UNDO: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexPane {
+    Hex,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Ascii,
+    Utf16,
+}
+
+struct Snapshot {
+    data: Vec<u8>,
+    cursor: usize,
+}
+
+/// State of the hex editor input. The cursor is a byte offset and may sit
+/// one past the end, where typing appends.
+pub struct HexEditor {
+    data: Vec<u8>,
+    cursor: usize,
+    low_nibble: bool,
+    anchor: Option<usize>,
+
+    pub insert: bool,
+    pub pane: HexPane,
+    pub encoding: TextEncoding,
+    pub scroll: usize,
+
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl HexEditor {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            low_nibble: false,
+            anchor: None,
+            insert: false,
+            pane: HexPane::Hex,
+            encoding: TextEncoding::Ascii,
+            scroll: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub const fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub const fn low_nibble(&self) -> bool {
+        self.low_nibble
+    }
+
+    /// The selected bytes, including the one under the cursor.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        let (start, end) = (anchor.min(self.cursor), anchor.max(self.cursor));
+
+        Some(start..(end + 1).min(self.data.len()))
+    }
+
+    /// Formats the data for `ValueBytesParser`.
+    pub fn to_hex_string(&self) -> String {
+        self.data.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Rows needed to show the data plus the append position.
+    pub fn rows(&self) -> usize {
+        self.data.len() / BYTES_PER_ROW + 1
+    }
+
+    pub fn scroll_to_cursor(&mut self, visible_rows: usize) {
+        let row = self.cursor / BYTES_PER_ROW;
+        let visible_rows = visible_rows.max(1);
+
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + visible_rows {
+            self.scroll = row + 1 - visible_rows;
+        }
+    }
+
+    fn step(&self) -> usize {
+        match (self.pane, self.encoding) {
+            (HexPane::Text, TextEncoding::Utf16) => 2,
+            _ => 1,
+        }
+    }
+
+    fn update_anchor(&mut self, select: bool) {
+        match (select, self.anchor) {
+            (true, None) => self.anchor = Some(self.cursor),
+            (false, _) => self.anchor = None,
+            _ => (),
+        }
+    }
+
+    fn move_to(&mut self, cursor: usize, select: bool) {
+        self.update_anchor(select);
+        self.cursor = cursor.min(self.data.len());
+        self.low_nibble = false;
+    }
+
+    pub fn move_left(&mut self, select: bool) {
+        // within the hex grid the cursor steps over nibbles
+        if self.pane == HexPane::Hex && !select && self.anchor.is_none() {
+            match (self.low_nibble, self.cursor) {
+                (true, _) => self.low_nibble = false,
+                (false, 0) => (),
+                (false, cursor) => {
+                    self.cursor = cursor - 1;
+                    self.low_nibble = true;
+                }
+            }
+            return;
+        }
+
+        self.move_to(self.cursor.saturating_sub(self.step()), select);
+    }
+
+    pub fn move_right(&mut self, select: bool) {
+        if self.pane == HexPane::Hex && !select && self.anchor.is_none() {
+            match self.low_nibble {
+                false if self.cursor < self.data.len() => self.low_nibble = true,
+                _ => self.move_to(self.cursor + 1, false),
+            }
+            return;
+        }
+
+        self.move_to(self.cursor + self.step(), select);
+    }
+
+    pub fn move_up(&mut self, rows: usize, select: bool) {
+        let low_nibble = self.low_nibble;
+        self.move_to(self.cursor.saturating_sub(rows * BYTES_PER_ROW), select);
+        self.low_nibble = low_nibble && !select && self.cursor < self.data.len();
+    }
+
+    pub fn move_down(&mut self, rows: usize, select: bool) {
+        let low_nibble = self.low_nibble;
+        let target = self.cursor + rows * BYTES_PER_ROW;
+
+        self.move_to(target, select);
+        self.low_nibble = low_nibble && !select && self.cursor < self.data.len();
+    }
+
+    pub fn move_row_start(&mut self, select: bool) {
+        self.move_to(self.cursor - self.cursor % BYTES_PER_ROW, select);
+    }
+
+    pub fn move_row_end(&mut self, select: bool) {
+        let row_end = self.cursor - self.cursor % BYTES_PER_ROW + BYTES_PER_ROW - 1;
+        self.move_to(row_end.min(self.data.len()), select);
+    }
+
+    pub fn move_start(&mut self, select: bool) {
+        self.move_to(0, select);
+    }
+
+    pub fn move_end(&mut self, select: bool) {
+        self.move_to(self.data.len(), select);
+    }
+
+    pub fn select_all(&mut self) {
+        if self.data.is_empty() {
+            return;
+        }
+
+        self.anchor = Some(0);
+        self.cursor = self.data.len() - 1;
+        self.low_nibble = false;
+    }
+
+    fn save_undo(&mut self) {
+        if self.undo.len() == MAX_UNDO {
+            self.undo.remove(0);
+        }
+
+        self.undo.push(Snapshot { data: self.data.clone(), cursor: self.cursor });
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self) {
+        let Some(snapshot) = self.undo.pop() else { return; };
+
+        self.redo.push(Snapshot { data: std::mem::replace(&mut self.data, snapshot.data), cursor: self.cursor });
+        self.move_to(snapshot.cursor, false);
+    }
+
+    pub fn redo(&mut self) {
+        let Some(snapshot) = self.redo.pop() else { return; };
+
+        self.undo.push(Snapshot { data: std::mem::replace(&mut self.data, snapshot.data), cursor: self.cursor });
+        self.move_to(snapshot.cursor, false);
+    }
+
+    /// Removes the selection, returning whether there was one.
+    fn remove_selection(&mut self) -> bool {
+        let Some(range) = self.selection() else { return false; };
+
+        self.data.drain(range.clone());
+        self.move_to(range.start, false);
+        true
+    }
+
+    pub fn input_hex_digit(&mut self, digit: u8) {
+        self.save_undo();
+        let replaced = self.remove_selection();
+
+        if self.cursor == self.data.len() || ((self.insert || replaced) && !self.low_nibble) {
+            self.data.insert(self.cursor, digit << 4);
+            self.low_nibble = true;
+            return;
+        }
+
+        let byte = &mut self.data[self.cursor];
+        match self.low_nibble {
+            true => {
+                *byte = (*byte & 0xf0) | digit;
+                self.cursor += 1;
+                self.low_nibble = false;
+            }
+            false => {
+                *byte = (*byte & 0x0f) | (digit << 4);
+                self.low_nibble = true;
+            }
+        }
+    }
+
+    pub fn input_char(&mut self, c: char) {
+        let bytes = match self.encoding {
+            TextEncoding::Ascii if c.is_ascii() => vec![c as u8],
+            TextEncoding::Ascii => return,
+            TextEncoding::Utf16 => c.encode_utf16(&mut [0; 2]).iter().flat_map(|u| u.to_le_bytes()).collect(),
+        };
+
+        self.save_undo();
+        let replaced = self.remove_selection();
+
+        let end = match self.insert || replaced {
+            true => self.cursor,
+            false => (self.cursor + bytes.len()).min(self.data.len()),
+        };
+
+        self.data.splice(self.cursor..end, bytes.iter().copied());
+        self.move_to(self.cursor + bytes.len(), false);
+    }
+
+    pub fn backspace(&mut self) {
+        if self.anchor.is_some() {
+            self.save_undo();
+            self.remove_selection();
+            return;
+        }
+        if self.cursor == 0 {
+            return;
+        }
+
+        self.save_undo();
+        let start = self.cursor.saturating_sub(self.step());
+        self.data.drain(start..self.cursor);
+        self.move_to(start, false);
+    }
+
+    pub fn delete(&mut self) {
+        if self.anchor.is_some() {
+            self.save_undo();
+            self.remove_selection();
+            return;
+        }
+        if self.cursor == self.data.len() {
+            return;
+        }
+
+        self.save_undo();
+        let end = (self.cursor + self.step()).min(self.data.len());
+        self.data.drain(self.cursor..end);
+        self.low_nibble = false;
+    }
+
+    pub fn toggle_pane(&mut self) {
+        self.pane = match self.pane {
+            HexPane::Hex => HexPane::Text,
+            HexPane::Text => HexPane::Hex,
+        };
+        self.low_nibble = false;
+    }
+
+    pub fn toggle_encoding(&mut self) {
+        self.encoding = match self.encoding {
+            TextEncoding::Ascii => TextEncoding::Utf16,
+            TextEncoding::Utf16 => TextEncoding::Ascii,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_hex(editor: &mut HexEditor, digits: &str) {
+        for digit in digits.chars() {
+            editor.input_hex_digit(digit.to_digit(16).unwrap() as u8);
+        }
+    }
+
+    #[test]
+    fn hex_digits_overwrite_by_default() {
+        let mut editor = HexEditor::new(vec![0x11, 0x22, 0x33]);
+
+        type_hex(&mut editor, "a");
+        assert_eq!(editor.data(), [0xa1, 0x22, 0x33]);
+        assert!(editor.low_nibble());
+
+        type_hex(&mut editor, "bc");
+        assert_eq!(editor.data(), [0xab, 0xc2, 0x33]);
+        assert_eq!((editor.cursor(), editor.low_nibble()), (1, true));
+    }
+
+    #[test]
+    fn hex_digits_insert_whole_bytes() {
+        let mut editor = HexEditor::new(vec![0x11, 0x22]);
+        editor.insert = true;
+
+        type_hex(&mut editor, "ab");
+        assert_eq!(editor.data(), [0xab, 0x11, 0x22]);
+        assert_eq!((editor.cursor(), editor.low_nibble()), (1, false));
+
+        type_hex(&mut editor, "c");
+        assert_eq!(editor.data(), [0xab, 0xc0, 0x11, 0x22]);
+    }
+
+    #[test]
+    fn hex_digits_append_at_the_end() {
+        let mut editor = HexEditor::new(vec![0x11]);
+        editor.move_end(false);
+
+        type_hex(&mut editor, "f");
+        assert_eq!(editor.data(), [0x11, 0xf0]);
+        type_hex(&mut editor, "e0");
+        assert_eq!(editor.data(), [0x11, 0xfe, 0x00]);
+        assert_eq!(editor.to_hex_string(), "11 fe 00");
+
+        // a selection is replaced by what's typed
+        let mut editor = HexEditor::new(vec![0x11, 0x22, 0x33]);
+        editor.move_right(true);
+        type_hex(&mut editor, "4");
+        assert_eq!(editor.data(), [0x40, 0x33]);
+    }
+
+    #[test]
+    fn text_is_written_as_utf16() {
+        let mut editor = HexEditor::new(vec![b'a', 0, b'b', 0]);
+        editor.toggle_pane();
+        editor.toggle_encoding();
+
+        editor.input_char('x');
+        assert_eq!(editor.data(), [b'x', 0, b'b', 0]);
+        assert_eq!(editor.cursor(), 2);
+
+        editor.input_char('\u{1f600}');
+        assert_eq!(editor.data(), [b'x', 0, 0x3d, 0xd8, 0x00, 0xde]);
+        assert_eq!(editor.cursor(), 6);
+
+        editor.move_left(false);
+        assert_eq!(editor.cursor(), 4);
+        editor.insert = true;
+        editor.input_char('y');
+        assert_eq!(editor.data(), [b'x', 0, 0x3d, 0xd8, b'y', 0, 0x00, 0xde]);
+
+        // ASCII mode ignores what it can't encode
+        editor.toggle_encoding();
+        editor.input_char('é');
+        assert_eq!(editor.data().len(), 8);
+    }
+
+    #[test]
+    fn removing_bytes_can_be_undone_and_redone() {
+        let mut editor = HexEditor::new(vec![1, 2, 3, 4, 5]);
+        editor.move_to(2, false);
+
+        editor.backspace();
+        assert_eq!((editor.data(), editor.cursor()), (&[1, 3, 4, 5][..], 1));
+        editor.delete();
+        assert_eq!((editor.data(), editor.cursor()), (&[1, 4, 5][..], 1));
+
+        editor.move_right(true);
+        editor.delete();
+        assert_eq!(editor.data(), [1]);
+
+        editor.undo();
+        assert_eq!(editor.data(), [1, 4, 5]);
+        editor.undo();
+        editor.undo();
+        assert_eq!((editor.data(), editor.cursor()), (&[1, 2, 3, 4, 5][..], 2));
+        editor.undo();
+        assert_eq!(editor.data(), [1, 2, 3, 4, 5]);
+
+        editor.redo();
+        editor.redo();
+        assert_eq!((editor.data(), editor.cursor()), (&[1, 4, 5][..], 2));
+
+        // a new change drops what could be redone
+        editor.backspace();
+        editor.redo();
+        assert_eq!(editor.data(), [1, 5]);
+    }
+
+    #[test]
+    fn utf16_text_removes_whole_units() {
+        let mut editor = HexEditor::new(vec![b'a', 0, b'b', 0]);
+        editor.toggle_pane();
+        editor.toggle_encoding();
+        editor.move_end(false);
+
+        editor.backspace();
+        assert_eq!(editor.data(), [b'a', 0]);
+        editor.move_start(false);
+        editor.delete();
+        assert!(editor.data().is_empty());
+
+        editor.backspace();
+        editor.delete();
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.data(), [b'a', 0, b'b', 0]);
+    }
+}
@@ -0,0 +1,55 @@
+const MAX_HISTORY: usize = 100;
+
+/// A place visited in the key browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// Path of the listed key, empty for the root keys.
+    pub path: Vec<String>,
+    /// Names of the rows selected in the subkey and value tables.
+    pub key: Option<String>,
+    pub value: Option<String>,
+}
+
+impl Location {
+    pub fn same_key(&self, other: &Location) -> bool {
+        self.path.len() == other.path.len() && self.path.iter().zip(&other.path).all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+/// Browser-style back and forward stacks. The current location isn't kept
+/// here, it's passed in whenever it's about to be left.
+#[derive(Debug, Default)]
+pub struct History {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl History {
+    fn push(stack: &mut Vec<Location>, location: Location) {
+        if stack.len() == MAX_HISTORY {
+            stack.remove(0);
+        }
+
+        stack.push(location);
+    }
+
+    /// Records leaving `from` for a new location, which drops the forward history.
+    pub fn visit(&mut self, from: Location) {
+        Self::push(&mut self.back, from);
+        self.forward.clear();
+    }
+
+    pub fn back(&mut self, current: Location) -> Option<Location> {
+        let location = self.back.pop()?;
+        Self::push(&mut self.forward, current);
+
+        Some(location)
+    }
+
+    pub fn forward(&mut self, current: Location) -> Option<Location> {
+        let location = self.forward.pop()?;
+        Self::push(&mut self.back, current);
+
+        Some(location)
+    }
+}
@@ -0,0 +1,642 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::{backend::{self, memory::{self, MemoryNode}, Error, ErrorKind, RegistryBackend, Result}, config, regfile::{self, ChangeKind, RegFile, RegOperation}, registry::{self, Value}, snapshot::{self, Snapshot}, treefile::{self, ImportMode, TreeFile}};
+
+pub const JOURNAL_VERSION: u64 = 1;
+/// Oldest operations are forgotten past this many.
+pub const MAX_JOURNAL: usize = 100;
+
+/// A change made to the registry, with what it takes to reverse it. Paths are
+/// full key paths, for values the path of the key holding them.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    CreateKey { path: String },
+    /// Keeps everything that was below the key so it can be put back.
+    DeleteKey { path: String, tree: MemoryNode },
+    RenameKey { path: String, from: String, to: String },
+    /// Also covers changing the type. `old` is `None` for a new value.
+    SetValue { path: String, name: String, old: Option<Value>, new: Value },
+    RenameValue { path: String, from: String, to: String },
+    DeleteValue { path: String, name: String, value: Value },
+    /// Several changes made at once, like importing a file, undone together.
+    Group { description: String, operations: Vec<Operation> },
+}
+
+fn invalid_file(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid journal file: {message}"))
+}
+
+/// FNV-1a, which unlike the std hashers is the same in every build.
+fn location_hash(location: &str) -> u64 {
+    location.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn restore_tree(backend: &dyn RegistryBackend, path: &str, tree: &MemoryNode) -> Result<()> {
+    let file = TreeFile { path: path.to_owned(), recursive: true, root: tree.clone() };
+
+    regfile::apply(backend, &treefile::operations(backend, &file, ImportMode::Merge)?)
+}
+
+fn set_value(backend: &dyn RegistryBackend, path: &str, name: &str, value: Option<&Value>) -> Result<()> {
+    let key = backend::open_path(backend, path)?;
+
+    match value {
+        Some(value) => key.set_value(name, value),
+        None => registry::delete_value(key.as_ref(), name),
+    }
+}
+
+fn rename_key(backend: &dyn RegistryBackend, path: &str, from: &str, to: &str) -> Result<()> {
+    registry::rename_key(backend::open_path(backend, path)?.as_ref(), from, to)
+}
+
+fn rename_value(backend: &dyn RegistryBackend, path: &str, from: &str, to: &str) -> Result<()> {
+    registry::rename_value(backend::open_path(backend, path)?.as_ref(), from, to)
+}
+
+fn key_exists(backend: &dyn RegistryBackend, path: &str) -> bool {
+    backend::open_path(backend, path).is_ok()
+}
+
+fn current_value(backend: &dyn RegistryBackend, path: &str, name: &str) -> Option<Value> {
+    backend::open_path(backend, path).and_then(|key| key.get_value(name)).ok()
+}
+
+fn key_moved(backend: &dyn RegistryBackend, path: &str, now: &str, was: &str) -> Option<String> {
+    let now_path = format!("{path}\\{now}");
+    let was_path = format!("{path}\\{was}");
+
+    match key_exists(backend, &now_path) {
+        false => Some(format!("The key {now_path} is gone")),
+        true if !memory::names_equal(now, was) && key_exists(backend, &was_path) => Some(format!("The key {was_path} exists again")),
+        true => None,
+    }
+}
+
+fn value_moved(backend: &dyn RegistryBackend, path: &str, now: &str, was: &str) -> Option<String> {
+    match current_value(backend, path, now) {
+        None => Some(format!("The value {} in {path} is gone", registry::display_name(now))),
+        Some(_) if !memory::names_equal(now, was) && current_value(backend, path, was).is_some() => Some(format!("The value {} in {path} exists again", registry::display_name(was))),
+        Some(_) => None,
+    }
+}
+
+fn value_changed(backend: &dyn RegistryBackend, path: &str, name: &str, expected: Option<&Value>) -> Option<String> {
+    match current_value(backend, path, name) {
+        current if current.as_ref() == expected => None,
+        None => Some(format!("The value {} in {path} is gone", registry::display_name(name))),
+        Some(_) if expected.is_none() => Some(format!("The value {} in {path} exists again", registry::display_name(name))),
+        Some(_) => Some(format!("The value {} in {path} has changed", registry::display_name(name))),
+    }
+}
+
+impl Operation {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::CreateKey { path } => format!("create the key {path}"),
+            Self::DeleteKey { path, .. } => format!("delete the key {path}"),
+            Self::RenameKey { path, from, to } => format!("rename the key {path}\\{from} to {to}"),
+            Self::SetValue { path, name, old: None, .. } => format!("create the value {} in {path}", registry::display_name(name)),
+            Self::SetValue { path, name, .. } => format!("change the value {} in {path}", registry::display_name(name)),
+            Self::RenameValue { path, from, to } => format!("rename the value {} in {path} to {}", registry::display_name(from), registry::display_name(to)),
+            Self::DeleteValue { path, name, .. } => format!("delete the value {} in {path}", registry::display_name(name)),
+            Self::Group { description, .. } => description.clone(),
+        }
+    }
+
+    /// What changed since the operation was done, or undone when `undone` is
+    /// set, by something other than the journal. Undoing or redoing over such
+    /// a change would throw it away.
+    pub fn conflict(&self, backend: &dyn RegistryBackend, undone: bool) -> Option<String> {
+        match (self, undone) {
+            (Self::CreateKey { path }, false) | (Self::DeleteKey { path, .. }, true) => match key_exists(backend, path) {
+                true => None,
+                false => Some(format!("The key {path} is gone")),
+            },
+            (Self::CreateKey { path }, true) | (Self::DeleteKey { path, .. }, false) => match key_exists(backend, path) {
+                true => Some(format!("The key {path} exists again")),
+                false => None,
+            },
+            (Self::RenameKey { path, from, to }, false) => key_moved(backend, path, to, from),
+            (Self::RenameKey { path, from, to }, true) => key_moved(backend, path, from, to),
+            (Self::SetValue { path, name, new, .. }, false) => value_changed(backend, path, name, Some(new)),
+            (Self::SetValue { path, name, old, .. }, true) => value_changed(backend, path, name, old.as_ref()),
+            (Self::RenameValue { path, from, to }, false) => value_moved(backend, path, to, from),
+            (Self::RenameValue { path, from, to }, true) => value_moved(backend, path, from, to),
+            (Self::DeleteValue { path, name, .. }, false) => value_changed(backend, path, name, None),
+            (Self::DeleteValue { path, name, value }, true) => value_changed(backend, path, name, Some(value)),
+            // the operations of a group never touch the same key or value twice
+            (Self::Group { operations, .. }, undone) => operations.iter().find_map(|operation| operation.conflict(backend, undone)),
+        }
+    }
+
+    pub fn undo(&self, backend: &dyn RegistryBackend) -> Result<()> {
+        match self {
+            Self::CreateKey { path } => backend::delete_path(backend, path),
+            Self::DeleteKey { path, tree } => restore_tree(backend, path, tree),
+            Self::RenameKey { path, from, to } => rename_key(backend, path, to, from),
+            Self::SetValue { path, name, old, .. } => set_value(backend, path, name, old.as_ref()),
+            Self::RenameValue { path, from, to } => rename_value(backend, path, to, from),
+            Self::DeleteValue { path, name, value } => set_value(backend, path, name, Some(value)),
+            Self::Group { operations, .. } => backend::batch(backend, || {
+                for (i, operation) in operations.iter().enumerate().rev() {
+                    if let Err(err) = operation.undo(backend) {
+                        // what was undone already is put back, so the group stays whole
+                        for operation in &operations[i + 1..] {
+                            let _ = operation.redo(backend);
+                        }
+                        return Err(err);
+                    }
+                }
+
+                Ok(())
+            }),
+        }
+    }
+
+    pub fn redo(&self, backend: &dyn RegistryBackend) -> Result<()> {
+        match self {
+            Self::CreateKey { path } => backend::create_path(backend, path),
+            Self::DeleteKey { path, .. } => backend::delete_path(backend, path),
+            Self::RenameKey { path, from, to } => rename_key(backend, path, from, to),
+            Self::SetValue { path, name, new, .. } => set_value(backend, path, name, Some(new)),
+            Self::RenameValue { path, from, to } => rename_value(backend, path, from, to),
+            Self::DeleteValue { path, name, .. } => set_value(backend, path, name, None),
+            Self::Group { operations, .. } => backend::batch(backend, || {
+                for (i, operation) in operations.iter().enumerate() {
+                    if let Err(err) = operation.redo(backend) {
+                        for operation in operations[..i].iter().rev() {
+                            let _ = operation.undo(backend);
+                        }
+                        return Err(err);
+                    }
+                }
+
+                Ok(())
+            }),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::CreateKey { path } => json!({ "op": "create_key", "path": path }),
+            Self::DeleteKey { path, tree } => json!({ "op": "delete_key", "path": path, "key": treefile::node_to_json(tree, false, &mut 0) }),
+            Self::RenameKey { path, from, to } => json!({ "op": "rename_key", "path": path, "from": from, "to": to }),
+            Self::SetValue { path, name, old, new } => json!({
+                "op": "set_value",
+                "path": path,
+                "name": name,
+                "old": old.as_ref().map(treefile::value_to_json),
+                "new": treefile::value_to_json(new),
+            }),
+            Self::RenameValue { path, from, to } => json!({ "op": "rename_value", "path": path, "from": from, "to": to }),
+            Self::DeleteValue { path, name, value } => json!({ "op": "delete_value", "path": path, "name": name, "value": treefile::value_to_json(value) }),
+            Self::Group { description, operations } => json!({
+                "op": "group",
+                "description": description,
+                "operations": operations.iter().map(Self::to_json).collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    fn from_json(json: &serde_json::Value) -> Result<Self> {
+        let string = |field: &str| {
+            json.get(field)
+                .and_then(|value| value.as_str())
+                .map(|value| value.to_owned())
+                .ok_or_else(|| invalid_file(&format!("expected \"{field}\" to be a string")))
+        };
+        let value = |field: &str, path: &str| match json.get(field) {
+            Some(value) => treefile::value_from_json(path, value),
+            None => Err(invalid_file(&format!("expected \"{field}\""))),
+        };
+
+        let op = string("op")?;
+        if op == "group" {
+            let Some(entries) = json.get("operations").and_then(|entries| entries.as_array()) else {
+                return Err(invalid_file("expected the \"operations\" of the group"));
+            };
+            let operations = entries.iter().map(Self::from_json).collect::<Result<Vec<_>>>()?;
+
+            return Ok(Self::Group { description: string("description")?, operations });
+        }
+
+        let path = string("path")?;
+        let operation = match op.as_str() {
+            "create_key" => Self::CreateKey { path },
+            "delete_key" => {
+                let Some(key) = json.get("key") else { return Err(invalid_file("expected the deleted \"key\"")); };
+                let name = backend::split_path(&path).pop().unwrap_or_default();
+
+                Self::DeleteKey { tree: treefile::node_from_json(&name, &path, key)?, path }
+            }
+            "rename_key" => Self::RenameKey { path, from: string("from")?, to: string("to")? },
+            "set_value" => {
+                let old = match json.get("old") {
+                    None | Some(serde_json::Value::Null) => None,
+                    Some(_) => Some(value("old", &path)?),
+                };
+
+                Self::SetValue { name: string("name")?, old, new: value("new", &path)?, path }
+            }
+            "rename_value" => Self::RenameValue { path, from: string("from")?, to: string("to")? },
+            "delete_value" => Self::DeleteValue { name: string("name")?, value: value("value", &path)?, path },
+            op => return Err(invalid_file(&format!("unknown operation {op}"))),
+        };
+
+        Ok(operation)
+    }
+}
+
+/// A part of the registry a file touches, read before and after applying it
+/// to tell what changed.
+struct Scope {
+    path: Vec<String>,
+    /// Whether the subkeys are read too, only the values are otherwise.
+    recursive: bool,
+}
+
+impl Scope {
+    fn read(&self, backend: &dyn RegistryBackend) -> Result<Option<MemoryNode>> {
+        match backend::open_path(backend, &self.path.join("\\")) {
+            Ok(key) => treefile::read_tree(key.as_ref(), &self.path[self.path.len() - 1], self.recursive).map(Some),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// The least of the registry that has to be read to see everything the
+/// operations change. A key that doesn't exist yet is read from the topmost
+/// key that's missing above it.
+fn scopes(backend: &dyn RegistryBackend, operations: &[RegOperation]) -> Result<Vec<Scope>> {
+    let mut scopes = Vec::new();
+
+    for operation in operations {
+        let (path, recursive) = match operation {
+            RegOperation::DeleteKey { path } => (path, true),
+            RegOperation::CreateKey { path } | RegOperation::SetValue { path, .. } | RegOperation::DeleteValue { path, .. } => (path, false),
+        };
+        let (root, components) = backend::resolve_path(backend, path)?;
+        let mut path = std::iter::once(root).chain(components).collect::<Vec<_>>();
+
+        match (2..=path.len()).find(|&len| !key_exists(backend, &path[..len].join("\\"))) {
+            Some(len) => {
+                path.truncate(len);
+                scopes.push(Scope { path, recursive: true });
+            }
+            None => scopes.push(Scope { path, recursive }),
+        }
+    }
+
+    // parents come first, and a recursive scope covers everything below it
+    scopes.sort_by_key(|scope| (scope.path.len(), !scope.recursive));
+
+    let mut kept: Vec<Scope> = Vec::new();
+    for scope in scopes {
+        let covered = kept.iter().any(|other| snapshot::is_below(&scope.path, &other.path) && (other.recursive || scope.path.len() == other.path.len()));
+        if !covered {
+            kept.push(scope);
+        }
+    }
+
+    Ok(kept)
+}
+
+/// The operations that turn `before` into `after`, both read from the key at
+/// `path`, `None` where it doesn't exist.
+fn push_changes(path: &str, before: Option<MemoryNode>, after: Option<MemoryNode>, operations: &mut Vec<Operation>) {
+    let (before, after) = match (before, after) {
+        (None, None) => return,
+        (Some(tree), None) => return operations.push(Operation::DeleteKey { path: path.to_owned(), tree }),
+        (None, Some(after)) => {
+            operations.push(Operation::CreateKey { path: path.to_owned() });
+
+            let mut before = MemoryNode::new(after.name.clone());
+            before.last_write = 0;
+            (before, after)
+        }
+        (Some(before), Some(after)) => (before, after),
+    };
+
+    let base = backend::split_path(path).len();
+    let old = Snapshot { path: path.to_owned(), taken: 0, root: before };
+    let new = Snapshot { path: path.to_owned(), taken: 0, root: after };
+
+    // what was below a deleted key goes with it
+    let mut deleted: Option<Vec<String>> = None;
+    for difference in snapshot::diff(&old, &new) {
+        if deleted.as_ref().is_some_and(|parent| snapshot::is_below(&difference.path, parent)) {
+            continue;
+        }
+
+        let path = difference.key_path();
+        let operation = match (difference.kind, difference.value, difference.old, difference.new) {
+            (ChangeKind::AddKey, ..) => Operation::CreateKey { path },
+            (ChangeKind::DeleteKey, ..) => {
+                let Some(tree) = old.root.find(&difference.path[base..]).cloned() else { continue; };
+                deleted = Some(difference.path);
+
+                Operation::DeleteKey { path, tree }
+            }
+            (_, Some(name), old, Some(new)) => Operation::SetValue { path, name, old, new },
+            (_, Some(name), Some(value), None) => Operation::DeleteValue { path, name, value },
+            _ => continue,
+        };
+        operations.push(operation);
+    }
+}
+
+/// Applies `file` and returns what it changed as one operation for the
+/// journal, `None` when nothing changed. What changed is returned even when
+/// applying fails partway, so that it can still be undone.
+pub fn apply(backend: &dyn RegistryBackend, file: &RegFile, description: impl Into<String>) -> (Option<Operation>, Result<()>) {
+    let scopes = match scopes(backend, &file.operations) {
+        Ok(scopes) => scopes,
+        Err(err) => return (None, Err(err)),
+    };
+    let before = match scopes.iter().map(|scope| scope.read(backend)).collect::<Result<Vec<_>>>() {
+        Ok(before) => before,
+        Err(err) => return (None, Err(err)),
+    };
+
+    let result = regfile::apply(backend, file);
+
+    let mut operations = Vec::new();
+    for (scope, before) in scopes.iter().zip(before) {
+        let Ok(after) = scope.read(backend) else { return (None, result); };
+        push_changes(&scope.path.join("\\"), before, after, &mut operations);
+    }
+
+    let operation = match operations.len() {
+        0 | 1 => operations.pop(),
+        _ => Some(Operation::Group { description: description.into(), operations }),
+    };
+    (operation, result)
+}
+
+/// The operations that can be undone and redone, kept in a JSON file so they
+/// outlive the session:
+///
+/// ```json
+/// { "version": 1, "undo": [ { "op": "rename_key", "path": "HKEY_CURRENT_USER\\Software", "from": "Old", "to": "New" } ], "redo": [] }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    /// `None` when there's no config directory, the journal then only lasts the session.
+    file: Option<PathBuf>,
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+impl Journal {
+    /// Every store gets its own journal, named after the kind of store and a
+    /// hash of where it lives, so undoing a change made to one Wine prefix or
+    /// hive never touches another. Stores that don't outlive the session,
+    /// like fixtures, only get a journal for the session.
+    pub fn default_file(backend: &dyn RegistryBackend) -> Option<PathBuf> {
+        let location = backend.location()?;
+        let name: String = backend.name().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+
+        config::config_dir().map(|dir| dir.join(format!("journal-{}-{:016x}.json", name.to_ascii_lowercase(), location_hash(&location))))
+    }
+
+    /// Reads the journal, a missing file meaning nothing was done yet.
+    pub fn load(file: Option<PathBuf>) -> Result<Self> {
+        let Some(path) = file.as_deref() else { return Ok(Self::default()); };
+
+        let (undo, redo) = match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (Vec::new(), Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self { file, undo, redo })
+    }
+
+    fn parse(text: &str) -> Result<(Vec<Operation>, Vec<Operation>)> {
+        let json: serde_json::Value = serde_json::from_str(text).map_err(|err| invalid_file(&err.to_string()))?;
+
+        let version = json.get("version").and_then(|version| version.as_u64());
+        if version.is_some_and(|version| version > JOURNAL_VERSION) {
+            return Err(invalid_file("it was written by a newer version of regcli"));
+        }
+
+        let operations = |field: &str| match json.get(field).and_then(|entries| entries.as_array()) {
+            Some(entries) => entries.iter().map(Operation::from_json).collect::<Result<Vec<_>>>(),
+            None => Err(invalid_file(&format!("expected an \"{field}\" array"))),
+        };
+
+        Ok((operations("undo")?, operations("redo")?))
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.file.as_deref() else { return Ok(()); };
+
+        let undo = self.undo.iter().map(Operation::to_json).collect::<Vec<_>>();
+        let redo = self.redo.iter().map(Operation::to_json).collect::<Vec<_>>();
+        let json = json!({ "version": JOURNAL_VERSION, "undo": undo, "redo": redo });
+        let text = serde_json::to_string_pretty(&json).map_err(|err| Error::other(err.to_string()))?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text + "\n")?;
+
+        Ok(())
+    }
+
+    /// Adds an operation that was just done, which makes the undone ones unreachable.
+    pub fn record(&mut self, operation: Operation) -> Result<()> {
+        self.undo.push(operation);
+        self.redo.clear();
+
+        if self.undo.len() > MAX_JOURNAL {
+            self.undo.remove(0);
+        }
+
+        self.save()
+    }
+
+    /// Drops an operation that can't be undone or redone anymore, because
+    /// something else changed what it touched.
+    fn drop_conflicting(&mut self, conflict: String, undo: bool) -> Error {
+        match undo {
+            true => self.undo.pop(),
+            false => self.redo.pop(),
+        };
+
+        match self.save() {
+            Ok(()) => Error::other(format!("{conflict}, the change was dropped from the journal")),
+            Err(err) => err,
+        }
+    }
+
+    /// Reverses the last operation, returning it, or `None` when there's nothing
+    /// to undo. An operation that fails stays where it was, unless what it
+    /// touched was changed since by something else.
+    pub fn undo(&mut self, backend: &dyn RegistryBackend) -> Result<Option<Operation>> {
+        let Some(operation) = self.undo.last() else { return Ok(None); };
+        if let Some(conflict) = operation.conflict(backend, false) {
+            return Err(self.drop_conflicting(conflict, true));
+        }
+        operation.undo(backend)?;
+
+        let operation = self.undo.pop().unwrap();
+        self.redo.push(operation.clone());
+        self.save()?;
+
+        Ok(Some(operation))
+    }
+
+    pub fn redo(&mut self, backend: &dyn RegistryBackend) -> Result<Option<Operation>> {
+        let Some(operation) = self.redo.last() else { return Ok(None); };
+        if let Some(conflict) = operation.conflict(backend, true) {
+            return Err(self.drop_conflicting(conflict, false));
+        }
+        operation.redo(backend)?;
+
+        let operation = self.redo.pop().unwrap();
+        self.undo.push(operation.clone());
+        self.save()?;
+
+        Ok(Some(operation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    const FIXTURE: &str = r#"{
+        "HKEY_CURRENT_USER": {
+            "keys": {
+                "App": {
+                    "values": { "Name": "regcli", "Size": 1 },
+                    "keys": { "Plugins": { "values": { "Count": 2 } } }
+                }
+            }
+        }
+    }"#;
+
+    fn export(backend: &dyn RegistryBackend) -> String {
+        regfile::export(backend.open_root("HKEY_CURRENT_USER").unwrap().as_ref(), "HKEY_CURRENT_USER", true).unwrap().0
+    }
+
+    fn string(s: &str) -> Value {
+        Value::new(registry::Type::String, registry::encode_sz(s))
+    }
+
+    #[test]
+    fn operations_are_undone_and_redone() {
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+        let original = export(&backend);
+        let mut journal = Journal::load(None).unwrap();
+
+        let app = backend::open_path(&backend, "HKEY_CURRENT_USER\\App").unwrap();
+        let tree = treefile::read_tree(app.open("Plugins").unwrap().as_ref(), "Plugins", true).unwrap();
+        app.remove_tree("Plugins").unwrap();
+        journal.record(Operation::DeleteKey { path: "HKEY_CURRENT_USER\\App\\Plugins".into(), tree }).unwrap();
+
+        app.set_value("Name", &string("changed")).unwrap();
+        journal.record(Operation::SetValue { path: "HKEY_CURRENT_USER\\App".into(), name: "Name".into(), old: Some(string("regcli")), new: string("changed") }).unwrap();
+
+        registry::rename_value(app.as_ref(), "Size", "Length").unwrap();
+        journal.record(Operation::RenameValue { path: "HKEY_CURRENT_USER\\App".into(), from: "Size".into(), to: "Length".into() }).unwrap();
+        let changed = export(&backend);
+
+        while journal.undo(&backend).unwrap().is_some() {}
+        assert_eq!(export(&backend), original);
+
+        while journal.redo(&backend).unwrap().is_some() {}
+        assert_eq!(export(&backend), changed);
+    }
+
+    #[test]
+    fn changes_made_since_are_not_undone() {
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+        let mut journal = Journal::load(None).unwrap();
+
+        let app = backend::open_path(&backend, "HKEY_CURRENT_USER\\App").unwrap();
+        app.set_value("Name", &string("ours")).unwrap();
+        journal.record(Operation::SetValue { path: "HKEY_CURRENT_USER\\App".into(), name: "Name".into(), old: Some(string("regcli")), new: string("ours") }).unwrap();
+
+        app.set_value("Name", &string("theirs")).unwrap();
+        let err = journal.undo(&backend).err().unwrap();
+        assert_eq!(err.message(), "The value Name in HKEY_CURRENT_USER\\App has changed, the change was dropped from the journal");
+        assert_eq!(app.get_value("Name").unwrap(), string("theirs"));
+
+        // the stale operation is gone rather than stuck
+        assert!(journal.undo(&backend).unwrap().is_none());
+        assert!(journal.redo(&backend).unwrap().is_none());
+    }
+
+    #[test]
+    fn applied_files_are_undone_as_one() {
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+        let original = export(&backend);
+
+        let text = "Windows Registry Editor Version 5.00\r\n\r\n\
+            [-HKEY_CURRENT_USER\\App\\Plugins]\r\n\r\n\
+            [HKEY_CURRENT_USER\\App]\r\n\"Name\"=\"imported\"\r\n\"Size\"=-\r\n\r\n\
+            [HKEY_CURRENT_USER\\New\\Deep]\r\n\"Added\"=dword:00000001\r\n";
+        let file = regfile::parse(text.as_bytes()).unwrap();
+
+        let (operation, result) = apply(&backend, &file, "import test.reg");
+        result.unwrap();
+        let changed = export(&backend);
+        assert_ne!(changed, original);
+
+        let mut journal = Journal::load(None).unwrap();
+        journal.record(operation.unwrap()).unwrap();
+
+        assert_eq!(journal.undo(&backend).unwrap().unwrap().describe(), "import test.reg");
+        assert_eq!(export(&backend), original);
+        assert!(backend::open_path(&backend, "HKEY_CURRENT_USER\\New").is_err());
+
+        journal.redo(&backend).unwrap();
+        assert_eq!(export(&backend), changed);
+    }
+
+    #[test]
+    fn journal_files_read_back() {
+        let file = std::env::temp_dir().join(format!("regcli-{}-journal.json", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+
+        let mut journal = Journal::load(Some(file.clone())).unwrap();
+        journal.record(Operation::CreateKey { path: "HKEY_CURRENT_USER\\New".into() }).unwrap();
+        journal.record(Operation::Group {
+            description: "import test.reg".into(),
+            operations: vec![
+                Operation::RenameKey { path: "HKEY_CURRENT_USER".into(), from: "Old".into(), to: "New".into() },
+                Operation::DeleteValue { path: "HKEY_CURRENT_USER\\App".into(), name: String::new(), value: string("default") },
+            ],
+        }).unwrap();
+
+        let loaded = Journal::load(Some(file.clone())).unwrap();
+        let describe = |journal: &Journal| journal.undo.iter().map(Operation::describe).collect::<Vec<_>>();
+        assert_eq!(describe(&loaded), describe(&journal));
+
+        let Operation::Group { operations, .. } = &loaded.undo[1] else { panic!("expected a group") };
+        assert_eq!(operations[1].describe(), "delete the value (Default) in HKEY_CURRENT_USER\\App");
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn fixtures_only_get_a_session_journal() {
+        let backend = MemoryBackend::from_json(FIXTURE).unwrap();
+        assert!(Journal::default_file(&backend).is_none());
+
+        // FNV-1a test vectors, the file names can't change between builds
+        assert_eq!(location_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(location_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -1,3 +1,19 @@
 pub mod app;
+pub mod backend;
+pub mod backup;
+pub mod bookmarks;
+pub mod cli;
+pub mod config;
 pub mod context;
-pub mod registry;
\ No newline at end of file
+pub mod filter;
+pub mod hexedit;
+pub mod history;
+pub mod journal;
+pub mod listedit;
+pub mod regfile;
+pub mod registry;
+pub mod resources;
+pub mod search;
+pub mod snapshot;
+pub mod tree;
+pub mod treefile;
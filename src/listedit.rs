@@ -0,0 +1,132 @@
+use ratatui::crossterm::event::KeyEvent;
+use tui_textarea::TextArea;
+
+/// State of the string list input used for REG_MULTI_SZ data. One entry
+/// at a time can be edited in place.
+pub struct ListEditor {
+    items: Vec<String>,
+    selected: usize,
+    editing: Option<Box<TextArea<'static>>>,
+    // a freshly added entry is dropped again when its edit is cancelled
+    is_new: bool,
+
+    pub scroll: usize,
+}
+
+impl ListEditor {
+    pub fn new(items: Vec<String>) -> Self {
+        Self { items, selected: 0, editing: None, is_new: false, scroll: 0 }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub const fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub const fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    pub fn editing_mut(&mut self) -> Option<&mut TextArea<'static>> {
+        self.editing.as_deref_mut()
+    }
+
+    /// One string per line, the way `ValueMultistringParser` reads them.
+    pub fn to_text(&self) -> String {
+        self.items.join("\n")
+    }
+
+    pub fn scroll_to_selected(&mut self, visible_rows: usize) {
+        let visible_rows = visible_rows.max(1);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible_rows {
+            self.scroll = self.selected + 1 - visible_rows;
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.items.swap(self.selected, self.selected + 1);
+            self.selected += 1;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.selected > 0 {
+            self.items.swap(self.selected, self.selected - 1);
+            self.selected -= 1;
+        }
+    }
+
+    pub fn remove(&mut self) {
+        if self.selected < self.items.len() {
+            self.items.remove(self.selected);
+            self.selected = self.selected.min(self.items.len().saturating_sub(1));
+        }
+    }
+
+    fn start_edit(&mut self, text: &str) {
+        let mut textarea = TextArea::default();
+        textarea.insert_str(text);
+        self.editing = Some(Box::new(textarea));
+    }
+
+    /// Adds an empty entry below the selected one and starts editing it.
+    pub fn add(&mut self) {
+        let index = match self.items.is_empty() {
+            true => 0,
+            false => self.selected + 1,
+        };
+
+        self.items.insert(index, String::new());
+        self.selected = index;
+        self.is_new = true;
+        self.start_edit("");
+    }
+
+    pub fn edit(&mut self) {
+        let Some(item) = self.items.get(self.selected) else { return; };
+
+        self.is_new = false;
+        self.start_edit(&item.clone());
+    }
+
+    /// Stores the edited text. Empty entries can't be stored in REG_MULTI_SZ
+    /// data, so they're removed.
+    pub fn finish_edit(&mut self) {
+        let Some(textarea) = self.editing.take() else { return; };
+        let text = textarea.lines().join("");
+
+        match text.is_empty() {
+            true => self.remove(),
+            false => self.items[self.selected] = text,
+        };
+    }
+
+    pub fn cancel_edit(&mut self) {
+        if self.editing.take().is_some() && self.is_new {
+            self.remove();
+        }
+    }
+
+    pub fn input(&mut self, event: KeyEvent) {
+        if let Some(textarea) = self.editing.as_mut() {
+            textarea.input(event);
+        }
+    }
+}
@@ -1,7 +1,85 @@
-use regcli::app::App;
+use std::{path::PathBuf, sync::Arc};
+
+use regcli::{app::App, cli, backend::{self, hive::HiveBackend, memory::MemoryBackend, wine::WineBackend, RegistryBackend}};
+
+enum Source {
+    Default,
+    Memory(PathBuf),
+    Hives(Vec<PathBuf>),
+    Wine(PathBuf),
+}
+
+/// A reg.exe style command to run instead of the browser, with its arguments.
+struct Command {
+    name: String,
+    args: Vec<String>,
+}
+
+fn parse_args() -> Result<(Source, Option<Command>), String> {
+    let mut source = Source::Default;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if cli::is_command(&arg) {
+            return Ok((source, Some(Command { name: arg, args: args.collect() })));
+        }
+
+        match (arg.as_str(), &mut source) {
+            ("--memory", Source::Default) => {
+                let path = args.next().ok_or("--memory expects a fixture file")?;
+                source = Source::Memory(path.into());
+            }
+            ("--hive", Source::Default) => {
+                let path = args.next().ok_or("--hive expects a hive file")?;
+                source = Source::Hives(vec![path.into()]);
+            }
+            ("--hive", Source::Hives(paths)) => {
+                let path = args.next().ok_or("--hive expects a hive file")?;
+                paths.push(path.into());
+            }
+            ("--wine", Source::Default) => {
+                let path = args.next().ok_or("--wine expects a Wine prefix directory")?;
+                source = Source::Wine(path.into());
+            }
+            ("--memory" | "--hive" | "--wine", _) => return Err("only one kind of registry source can be opened at a time".into()),
+            _ => return Err(format!("unexpected argument '{arg}'")),
+        }
+    }
+
+    Ok((source, None))
+}
+
+fn open_backend(source: Source) -> Result<Arc<dyn RegistryBackend>, String> {
+    match source {
+        Source::Default => backend::default_backend()
+            .ok_or_else(|| "no registry backend is available on this platform, try --memory <fixture.json>".into()),
+        Source::Memory(path) => MemoryBackend::load(&path)
+            .map(|backend| Arc::new(backend) as Arc<dyn RegistryBackend>)
+            .map_err(|err| format!("can't load {}: {}", path.display(), err)),
+        Source::Hives(paths) => HiveBackend::open(&paths)
+            .map(|backend| Arc::new(backend) as Arc<dyn RegistryBackend>)
+            .map_err(|err| format!("can't open hive: {err}")),
+        Source::Wine(path) => WineBackend::open(&path)
+            .map(|backend| Arc::new(backend) as Arc<dyn RegistryBackend>)
+            .map_err(|err| format!("can't open Wine prefix: {err}")),
+    }
+}
 
 fn main() -> std::io::Result<()> {
-    let mut app = App::new();
+    let result = parse_args().and_then(|(source, command)| Ok((open_backend(source)?, command)));
+    let (backend, command) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("regcli: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(command) = command {
+        std::process::exit(cli::run(backend.as_ref(), &command.name, &command.args));
+    }
+
+    let mut app = App::new(backend);
     let mut terminal = ratatui::init();
 
     app.run(&mut terminal)?;
@@ -0,0 +1,596 @@
+use std::fmt;
+
+use crate::{backend::{self, ErrorKind, RegistryBackend, RegistryKey}, registry::{self, RegValue, Type, Value}};
+
+pub const REGEDIT5_HEADER: &str = "Windows Registry Editor Version 5.00";
+pub const REGEDIT4_HEADER: &str = "REGEDIT4";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegFileVersion {
+    Regedit4,
+    Regedit5,
+}
+
+#[derive(Debug, Clone)]
+pub enum RegOperation {
+    CreateKey { path: String },
+    DeleteKey { path: String },
+    SetValue { path: String, name: String, value: Value },
+    DeleteValue { path: String, name: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct RegFile {
+    pub version: RegFileVersion,
+    pub operations: Vec<RegOperation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self { line, message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+fn decode_ansi(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&b| match b {
+            0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect()
+}
+
+/// Decodes a .reg file: UTF-16LE when it starts with a BOM, otherwise UTF-8
+/// when valid and the ANSI code page as a last resort.
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some(wide) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = wide.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+        return String::from_utf16_lossy(&units);
+    }
+
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        Err(_) => decode_ansi(bytes),
+    }
+}
+
+/// Reads a quoted string starting at the opening quote, returning the
+/// unescaped contents and whatever follows the closing quote. Like regedit,
+/// only `\\` and `\"` are escapes, any other backslash is kept as is.
+fn parse_quoted(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut result = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((result, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                '\\' => result.push('\\'),
+                '"' => result.push('"'),
+                other => {
+                    result.push('\\');
+                    result.push(other);
+                }
+            },
+            c => result.push(c),
+        };
+    }
+
+    None
+}
+
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
+    s.split(',')
+        .map(|byte| byte.trim())
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| match byte.len() <= 2 {
+            true => u8::from_str_radix(byte, 16).map_err(|_| format!("'{byte}' isn't a hex byte")),
+            false => Err(format!("'{byte}' isn't a hex byte")),
+        })
+        .collect()
+}
+
+/// Strings stored as hex in REGEDIT4 files use the ANSI code page, the registry wants UTF-16.
+fn widen_ansi_strings(bytes: &[u8]) -> Vec<u8> {
+    decode_ansi(bytes).encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+}
+
+enum ValueData {
+    Set(Value),
+    Delete,
+}
+
+struct Parser<'a> {
+    lines: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
+    version: RegFileVersion,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_data(&mut self, line_number: usize, data: &str) -> Result<ValueData, ParseError> {
+        let data = data.trim();
+
+        if data == "-" {
+            return Ok(ValueData::Delete);
+        }
+
+        if data.starts_with('"') {
+            let (s, rest) = parse_quoted(data).ok_or_else(|| ParseError::new(line_number, "unterminated string"))?;
+            if !rest.trim().is_empty() && !rest.trim_start().starts_with(';') {
+                return Err(ParseError::new(line_number, "unexpected characters after the string"));
+            }
+
+            return Ok(ValueData::Set(Value::new(Type::String, registry::encode_sz(&s))));
+        }
+
+        let lower = data.to_ascii_lowercase();
+
+        if let Some(digits) = lower.strip_prefix("dword:") {
+            let digits = digits.trim();
+            if digits.is_empty() || digits.len() > 8 {
+                return Err(ParseError::new(line_number, "dword data must have 1 to 8 hex digits"));
+            }
+
+            let n = u32::from_str_radix(digits, 16).map_err(|_| ParseError::new(line_number, format!("'{digits}' isn't a hex dword")))?;
+            return Ok(ValueData::Set(Value::new(Type::U32, n.to_le_bytes().to_vec())));
+        }
+
+        if let Some(rest) = lower.strip_prefix("hex") {
+            let (ty, rest) = match rest.strip_prefix('(') {
+                Some(rest) => {
+                    let (ty, rest) = rest.split_once("):").ok_or_else(|| ParseError::new(line_number, "expected hex(<type>):"))?;
+                    let ty = u32::from_str_radix(ty.trim(), 16).map_err(|_| ParseError::new(line_number, format!("'{ty}' isn't a hex value type")))?;
+
+                    (Type::from(ty), rest)
+                }
+                None => (Type::Bytes, rest.strip_prefix(':').ok_or_else(|| ParseError::new(line_number, "expected hex:"))?),
+            };
+
+            let mut hex = rest.trim().to_owned();
+            while let Some(stripped) = hex.strip_suffix('\\') {
+                hex = stripped.to_owned();
+
+                let Some((_, next)) = self.lines.next() else {
+                    return Err(ParseError::new(line_number, "the file ends in the middle of hex data"));
+                };
+                hex.push_str(next.trim());
+            }
+
+            let mut bytes = parse_hex_bytes(&hex).map_err(|message| ParseError::new(line_number, message))?;
+            if self.version == RegFileVersion::Regedit4 && matches!(ty, Type::String | Type::ExpandString | Type::MultiString) {
+                bytes = widen_ansi_strings(&bytes);
+            }
+
+            return Ok(ValueData::Set(Value::new(ty, bytes)));
+        }
+
+        Err(ParseError::new(line_number, format!("unrecognized value data '{data}'")))
+    }
+
+    fn parse_value_line(&mut self, line_number: usize, line: &str) -> Result<(String, ValueData), ParseError> {
+        let (name, rest) = match line.strip_prefix('@') {
+            Some(rest) => (String::new(), rest),
+            None => parse_quoted(line).ok_or_else(|| ParseError::new(line_number, "unterminated value name"))?,
+        };
+
+        let Some(data) = rest.trim_start().strip_prefix('=') else {
+            return Err(ParseError::new(line_number, "expected '=' after the value name"));
+        };
+
+        Ok((name, self.parse_data(line_number, data)?))
+    }
+
+    fn parse(mut self) -> Result<RegFile, ParseError> {
+        let mut operations = Vec::new();
+        let mut current_key: Option<String> = None;
+        let mut in_deleted_key = false;
+
+        while let Some((index, line)) = self.lines.next() {
+            let line_number = index + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let Some(end) = section.rfind(']') else {
+                    return Err(ParseError::new(line_number, "unterminated key name"));
+                };
+
+                let section = &section[..end];
+                match section.strip_prefix('-') {
+                    Some(path) => {
+                        operations.push(RegOperation::DeleteKey { path: path.trim().to_owned() });
+                        current_key = None;
+                        in_deleted_key = true;
+                    }
+                    None => {
+                        operations.push(RegOperation::CreateKey { path: section.trim().to_owned() });
+                        current_key = Some(section.trim().to_owned());
+                        in_deleted_key = false;
+                    }
+                };
+
+                continue;
+            }
+
+            if !line.starts_with('"') && !line.starts_with('@') {
+                return Err(ParseError::new(line_number, format!("unexpected line '{line}'")));
+            }
+
+            let (name, data) = self.parse_value_line(line_number, line)?;
+
+            let Some(path) = current_key.clone() else {
+                // regedit ignores values listed under a deleted key
+                if in_deleted_key {
+                    continue;
+                }
+                return Err(ParseError::new(line_number, "value outside of a key"));
+            };
+
+            operations.push(match data {
+                ValueData::Set(value) => RegOperation::SetValue { path, name, value },
+                ValueData::Delete => RegOperation::DeleteValue { path, name },
+            });
+        }
+
+        Ok(RegFile { version: self.version, operations })
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<RegFile, ParseError> {
+    let text = decode_text(bytes);
+    let mut lines = text.lines().enumerate().peekable();
+
+    let version = loop {
+        let Some((index, line)) = lines.next() else {
+            return Err(ParseError::new(1, "the file is empty"));
+        };
+
+        match line.trim() {
+            "" => continue,
+            REGEDIT5_HEADER => break RegFileVersion::Regedit5,
+            REGEDIT4_HEADER => break RegFileVersion::Regedit4,
+            _ => return Err(ParseError::new(index + 1, "missing 'Windows Registry Editor Version 5.00' or 'REGEDIT4' header")),
+        }
+    };
+
+    Parser { lines, version }.parse()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    AddKey,
+    DeleteKey,
+    AddValue,
+    ModifyValue,
+    DeleteValue,
+}
+
+impl ChangeKind {
+    pub const fn symbol(&self) -> &'static str {
+        match self {
+            Self::AddKey | Self::AddValue => "+",
+            Self::DeleteKey | Self::DeleteValue => "-",
+            Self::ModifyValue => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: String,
+    pub value: Option<String>,
+    pub detail: String,
+}
+
+/// Type and data of a value as shown in the change lists.
+pub fn describe(value: &Value) -> String {
+    format!("{} {}", registry::type_to_str(value.ty()), registry::get_printable_value(value))
+}
+
+/// Lists what applying the file would change, leaving out operations that
+/// wouldn't have any effect.
+pub fn preview(backend: &dyn RegistryBackend, file: &RegFile) -> backend::Result<Vec<Change>> {
+    let mut changes = Vec::new();
+
+    for operation in &file.operations {
+        match operation {
+            RegOperation::CreateKey { path } => {
+                if backend::open_path(backend, path).is_err() {
+                    backend::resolve_path(backend, path)?;
+                    changes.push(Change { kind: ChangeKind::AddKey, path: path.clone(), value: None, detail: String::new() });
+                }
+            }
+            RegOperation::DeleteKey { path } => {
+                if let Ok(key) = backend::open_path(backend, path) {
+                    let subkeys = key.subkeys().map(|s| s.len()).unwrap_or(0);
+                    let values = key.values().map(|v| v.len()).unwrap_or(0);
+                    let detail = format!("{subkeys} subkeys, {values} values");
+
+                    changes.push(Change { kind: ChangeKind::DeleteKey, path: path.clone(), value: None, detail });
+                }
+            }
+            RegOperation::SetValue { path, name, value } => {
+                let existing = backend::open_path(backend, path).and_then(|key| key.get_value(name));
+
+                let (kind, detail) = match existing {
+                    Ok(existing) if existing == *value => continue,
+                    Ok(existing) => (ChangeKind::ModifyValue, format!("{} -> {}", describe(&existing), describe(value))),
+                    Err(_) => (ChangeKind::AddValue, describe(value)),
+                };
+
+                changes.push(Change { kind, path: path.clone(), value: Some(registry::display_name(name).to_owned()), detail });
+            }
+            RegOperation::DeleteValue { path, name } => {
+                if let Ok(existing) = backend::open_path(backend, path).and_then(|key| key.get_value(name)) {
+                    changes.push(Change { kind: ChangeKind::DeleteValue, path: path.clone(), value: Some(registry::display_name(name).to_owned()), detail: describe(&existing) });
+                }
+            }
+        };
+    }
+
+    Ok(changes)
+}
+
+fn ignore_missing(result: backend::Result<()>) -> backend::Result<()> {
+    match result {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// What copying the values of `source`, and its subkeys when `recursive`, to
+/// `target_path` takes. `target` is the key there when it exists already,
+/// `overwrite` is asked about each value it has with the path below
+/// `target_path` and the name of the value.
+pub fn copy_operations(source: &dyn RegistryKey, target: Option<&dyn RegistryKey>, target_path: &str, recursive: bool, overwrite: &mut dyn FnMut(&str, &str) -> bool) -> backend::Result<Vec<RegOperation>> {
+    let mut operations = Vec::new();
+    push_copy(source, target, target_path, "", recursive, overwrite, &mut operations)?;
+    Ok(operations)
+}
+
+fn push_copy(source: &dyn RegistryKey, target: Option<&dyn RegistryKey>, target_path: &str, path: &str, recursive: bool, overwrite: &mut dyn FnMut(&str, &str) -> bool, operations: &mut Vec<RegOperation>) -> backend::Result<()> {
+    let full = match path.is_empty() {
+        true => target_path.to_owned(),
+        false => format!("{target_path}\\{path}"),
+    };
+    operations.push(RegOperation::CreateKey { path: full.clone() });
+
+    for (name, value) in source.values()? {
+        if target.is_some_and(|target| target.get_value(&name).is_ok()) && !overwrite(path, &name) {
+            continue;
+        }
+
+        operations.push(RegOperation::SetValue { path: full.clone(), name, value });
+    }
+
+    if !recursive {
+        return Ok(());
+    }
+
+    for name in source.subkeys()? {
+        let subtarget = target.and_then(|target| target.open(&name).ok());
+        let subpath = match path.is_empty() {
+            true => name.clone(),
+            false => format!("{path}\\{name}"),
+        };
+        push_copy(source.open(&name)?.as_ref(), subtarget.as_deref(), target_path, &subpath, recursive, overwrite, operations)?;
+    }
+
+    Ok(())
+}
+
+/// Applies the operations in order as one batch, so a hive is only written once.
+pub fn apply(backend: &dyn RegistryBackend, file: &RegFile) -> backend::Result<()> {
+    backend::batch(backend, || {
+        for operation in &file.operations {
+            match operation {
+                RegOperation::CreateKey { path } => {
+                    backend::create_path(backend, path)?;
+                }
+                RegOperation::DeleteKey { path } => {
+                    ignore_missing(backend::delete_path(backend, path))?;
+                }
+                RegOperation::SetValue { path, name, value } => {
+                    backend::open_path(backend, path)?.set_value(name, value)?;
+                }
+                RegOperation::DeleteValue { path, name } => {
+                    let result = backend::open_path(backend, path).and_then(|key| key.remove_value(name));
+                    ignore_missing(result)?;
+                }
+            };
+        }
+
+        Ok(())
+    })
+}
+
+/// regedit wraps hex data once a line reaches this many characters, which
+/// keeps every line within 80 columns including the trailing backslash.
+const MAX_HEX_LINE: usize = 77;
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Writes `hex:` or `hex(<type>):` data, wrapping with a trailing backslash
+/// once the line reaches `MAX_HEX_LINE`. `prefix_len` is how much of the
+/// line the value name already takes.
+fn write_hex(out: &mut String, prefix_len: usize, ty: Option<u32>, data: &[u8]) {
+    let prefix = match ty {
+        Some(ty) => format!("hex({ty:x}):"),
+        None => "hex:".to_owned(),
+    };
+
+    let mut line_len = prefix_len + prefix.len();
+    out.push_str(&prefix);
+
+    for (i, byte) in data.iter().enumerate() {
+        out.push_str(&format!("{byte:02x}"));
+        if i + 1 == data.len() {
+            break;
+        }
+
+        out.push(',');
+        line_len += 3;
+
+        if line_len >= MAX_HEX_LINE {
+            out.push_str("\\\r\n  ");
+            line_len = 2;
+        }
+    }
+}
+
+/// Formats a single `"name"=data` line the way regedit does, including the CRLF.
+/// REG_SZ data can only be written as a quoted string when it's exactly one
+/// NUL-terminated string, anything else has to go out as hex(1).
+pub fn format_value(name: &str, value: &Value) -> String {
+    let mut out = match name.is_empty() {
+        true => "@=".to_owned(),
+        false => format!("\"{}\"=", escape(name)),
+    };
+    let prefix_len = utf16_len(&out);
+
+    match RegValue::from(value) {
+        // the parser reads a line at a time, so line breaks have to go out as hex
+        RegValue::Sz(s) if !s.contains(['\r', '\n']) => out.push_str(&format!("\"{}\"", escape(&s))),
+        // regedit writes empty data as an empty string too
+        RegValue::Unknown { ty: Type::String, bytes } if bytes.is_empty() => out.push_str("\"\""),
+        RegValue::Dword(n) => out.push_str(&format!("dword:{n:08x}")),
+        RegValue::Binary(_) => write_hex(&mut out, prefix_len, None, value),
+        other => write_hex(&mut out, prefix_len, Some(u32::from(other.ty())), value),
+    };
+
+    out.push_str("\r\n");
+    out
+}
+
+/// Formats the `"name"=-` line that deletes a value, including the CRLF.
+pub fn format_deletion(name: &str) -> String {
+    match name.is_empty() {
+        true => "@=-\r\n".to_owned(),
+        false => format!("\"{}\"=-\r\n", escape(name)),
+    }
+}
+
+fn write_key(out: &mut String, key: &dyn RegistryKey, path: &str, recursive: bool, count: &mut usize) -> backend::Result<()> {
+    out.push_str(&format!("\r\n[{path}]\r\n"));
+    *count += 1;
+
+    for (name, value) in key.values()? {
+        out.push_str(&format_value(&name, &value));
+    }
+
+    if recursive {
+        for name in key.subkeys()? {
+            let subkey = key.open(&name)?;
+            write_key(out, subkey.as_ref(), &format!("{path}\\{name}"), recursive, count)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Encodes text the way regedit saves it: UTF-16LE with a byte order mark.
+pub fn encode_text(text: &str) -> Vec<u8> {
+    [0xFF, 0xFE].into_iter()
+        .chain(text.encode_utf16().flat_map(|u| u.to_le_bytes()))
+        .collect()
+}
+
+/// Exports a key, and optionally everything below it, as the text of a
+/// REGEDIT5 file. `path` is the full path written in the section headers.
+/// Returns the text along with the number of keys written.
+pub fn export(key: &dyn RegistryKey, path: &str, recursive: bool) -> backend::Result<(String, usize)> {
+    let mut out = format!("{REGEDIT5_HEADER}\r\n");
+    let mut count = 0;
+
+    write_key(&mut out, key, path, recursive, &mut count)?;
+    out.push_str("\r\n");
+
+    Ok((out, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::memory::{MemoryBackend, MemoryNode};
+
+    use super::*;
+
+    fn parse_text(text: &str) -> RegFile {
+        parse(text.as_bytes()).unwrap()
+    }
+
+    fn set_value(operation: &RegOperation) -> (&str, &Value) {
+        match operation {
+            RegOperation::SetValue { name, value, .. } => (name, value),
+            other => panic!("expected a value, got {other:?}"),
+        }
+    }
+
+    fn sample_values() -> Vec<(String, Value)> {
+        vec![
+            (String::new(), Value::new(Type::String, registry::encode_sz("default"))),
+            ("Quote \"\\".to_owned(), Value::new(Type::String, registry::encode_sz("C:\\new \"dir\""))),
+            ("Lines".to_owned(), Value::new(Type::String, registry::encode_sz("first\r\nsecond\n"))),
+            ("Number".to_owned(), Value::new(Type::U32, 42u32.to_le_bytes().to_vec())),
+            ("Binary".to_owned(), Value::new(Type::Bytes, (0..=255).collect())),
+            ("Multi".to_owned(), Value::new(Type::MultiString, registry::encode_multi_sz(&["a".to_owned(), "b".to_owned()]))),
+        ]
+    }
+
+    #[test]
+    fn export_parses_back() {
+        let mut node = MemoryNode::new("Test");
+        node.values = sample_values();
+        let mut root = MemoryNode::new("HKEY_CURRENT_USER");
+        root.insert_subkey(node);
+
+        let backend = MemoryBackend::new("Memory", vec![root]);
+        let key = backend::open_path(&backend, "HKEY_CURRENT_USER\\Test").unwrap();
+        let (text, count) = export(key.as_ref(), "HKEY_CURRENT_USER\\Test", true).unwrap();
+        assert_eq!(count, 1);
+        assert!(text.lines().all(|line| line.len() <= 80));
+
+        let file = parse(&encode_text(&text)).unwrap();
+        let parsed = file.operations[1..].iter().map(set_value).map(|(name, value)| (name.to_owned(), value.clone())).collect::<Vec<_>>();
+        assert_eq!(parsed, sample_values());
+    }
+
+    #[test]
+    fn only_backslash_and_quote_are_escapes() {
+        let file = parse_text("Windows Registry Editor Version 5.00\r\n\r\n[HKEY_CURRENT_USER\\Test]\r\n\"Path\"=\"C:\\new\\r0\\\\x \\\"q\\\"\"\r\n");
+
+        let (name, value) = set_value(&file.operations[1]);
+        assert_eq!(name, "Path");
+        assert_eq!(registry::decode_sz(value), "C:\\new\\r0\\x \"q\"");
+    }
+}
@@ -1,25 +1,144 @@
-use std::num::ParseIntError;
+use std::{fmt, num::ParseIntError, ops::Deref, str::FromStr};
 
-use windows_registry::{Key, Type, Value};
+use crate::{backend::{self, ErrorKind, RegistryKey}, resources};
 
-pub const DEFAULT_KEYS: [(&'static Key, &'static str); 5] = [
-    (windows_registry::CLASSES_ROOT, "HKEY_CLASSES_ROOT"),
-    (windows_registry::CURRENT_USER, "HKEY_CURRENT_USER"),
-    (windows_registry::LOCAL_MACHINE, "HKEY_LOCAL_MACHINE"),
-    (windows_registry::USERS, "HKEY_USERS"),
-    (windows_registry::CURRENT_CONFIG, "HKEY_CURRENT_CONFIG")
-];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    None,
+    Bytes,
+    String,
+    ExpandString,
+    MultiString,
+    U32,
+    U32BigEndian,
+    U64,
+    Link,
+    ResourceList,
+    FullResourceDescriptor,
+    ResourceRequirementsList,
+    Other(u32),
+}
+
+impl Type {
+    /// Types whose data is edited and converted as plain bytes.
+    pub const fn is_raw(self) -> bool {
+        !matches!(self, Self::String | Self::ExpandString | Self::MultiString | Self::U32 | Self::U32BigEndian | Self::U64)
+    }
+}
+
+impl From<u32> for Type {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::String,
+            2 => Self::ExpandString,
+            3 => Self::Bytes,
+            4 => Self::U32,
+            5 => Self::U32BigEndian,
+            6 => Self::Link,
+            7 => Self::MultiString,
+            8 => Self::ResourceList,
+            9 => Self::FullResourceDescriptor,
+            10 => Self::ResourceRequirementsList,
+            11 => Self::U64,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<Type> for u32 {
+    fn from(value: Type) -> Self {
+        match value {
+            Type::None => 0,
+            Type::String => 1,
+            Type::ExpandString => 2,
+            Type::Bytes => 3,
+            Type::U32 => 4,
+            Type::U32BigEndian => 5,
+            Type::Link => 6,
+            Type::MultiString => 7,
+            Type::ResourceList => 8,
+            Type::FullResourceDescriptor => 9,
+            Type::ResourceRequirementsList => 10,
+            Type::U64 => 11,
+            Type::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Value {
+    ty: Type,
+    data: Vec<u8>,
+}
+
+impl Value {
+    pub const fn new(ty: Type, data: Vec<u8>) -> Self {
+        Self { ty, data }
+    }
+
+    pub const fn ty(&self) -> Type {
+        self.ty
+    }
+
+    pub const fn set_ty(&mut self, ty: Type) {
+        self.ty = ty;
+    }
+
+    pub fn as_wide(&self) -> Vec<u16> {
+        self.data
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+}
 
-pub const TYPE_STRINGS: [&str; 7] = [
+impl Deref for Value {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl AsRef<[u8]> for Value {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+pub const TYPE_STRINGS: [&str; 12] = [
     "REG_BINARY",
     "REG_SZ",
     "REG_EXPAND_SZ",
     "REG_MULTI_SZ",
     "REG_DWORD",
+    "REG_DWORD_BIG_ENDIAN",
     "REG_QWORD",
+    "REG_LINK",
+    "REG_RESOURCE_LIST",
+    "REG_FULL_RESOURCE_DESCRIPTOR",
+    "REG_RESOURCE_REQUIREMENTS_LIST",
     "REG_NONE",
 ];
 
+pub const ROOT_ABBREVIATIONS: [(&str, &str); 5] = [
+    ("HKLM", "HKEY_LOCAL_MACHINE"),
+    ("HKCU", "HKEY_CURRENT_USER"),
+    ("HKCR", "HKEY_CLASSES_ROOT"),
+    ("HKU", "HKEY_USERS"),
+    ("HKCC", "HKEY_CURRENT_CONFIG"),
+];
+
+/// Expands abbreviations such as HKLM to the full root key name.
+pub fn expand_root_name(name: &str) -> &str {
+    ROOT_ABBREVIATIONS
+        .iter()
+        .find(|(short, _)| short.eq_ignore_ascii_case(name))
+        .map(|&(_, long)| long)
+        .unwrap_or(name)
+}
+
 pub fn get_type_strings_vec() -> Vec<String> {
     TYPE_STRINGS.iter().map(|&s| s.to_string()).collect()
 }
@@ -31,37 +150,64 @@ pub fn get_type_choices_vec() -> Vec<String> {
         .collect()
 }
 
-pub fn read_key(key: &Key, path: &str) -> windows_registry::Result<Key> {
-    let key = key
-        .options()
-        .read()
-        .open(path);
+pub fn read_key(key: &dyn RegistryKey, path: &str) -> backend::Result<Box<dyn RegistryKey>> {
+    key.open(path)
+}
+
+pub fn read_subkeys(key: &dyn RegistryKey) -> backend::Result<Vec<String>> {
+    key.subkeys()
+}
+
+pub fn clone_key(key: &dyn RegistryKey) -> Box<dyn RegistryKey> {
+    key.try_clone().expect("Same key wasn't able to be cloned")
+}
 
-    key
+pub fn new_key(key: &dyn RegistryKey, name: impl AsRef<str>) -> backend::Result<()> {
+    key.create(name.as_ref())
 }
 
-pub fn read_subkeys(key: &Key) -> windows_registry::Result<Vec<String>> {
-    key.keys().map(|keys| keys.collect())
+pub fn rename_key(key: &dyn RegistryKey, original: impl AsRef<str>, new: impl AsRef<str>) -> backend::Result<()> {
+    key.rename(original.as_ref(), new.as_ref())
 }
 
-pub fn clone_key(key: &Key) -> Key {
-    key.open("").expect("Same key wasn't able to be cloned")
+pub fn delete_key(key: &dyn RegistryKey, name: impl AsRef<str>) -> backend::Result<()> {
+    key.remove_tree(name.as_ref())
 }
 
-pub fn new_key(key: &Key, name: impl AsRef<str>) -> windows_registry::Result<()> {
-    key.create(name).map(|_| ())
+pub fn read_values(key: &dyn RegistryKey) -> backend::Result<Vec<(String, Value)>> {
+    key.values()
 }
 
-pub fn rename_key(key: &Key, original: impl AsRef<str>, new: impl AsRef<str>) -> windows_registry::Result<()> {
-    key.rename(original, new).map(|_| ())
+pub fn rename_value(key: &dyn RegistryKey, original: impl AsRef<str>, new: impl AsRef<str>) -> backend::Result<()> {
+    let (original, new) = (original.as_ref(), new.as_ref());
+    let value = key.get_value(original)?;
+
+    // names are case-insensitive, so a change in case has to drop the old value first
+    if original.eq_ignore_ascii_case(new) {
+        key.remove_value(original)?;
+        return key.set_value(new, &value).inspect_err(|_| {
+            let _ = key.set_value(original, &value);
+        });
+    }
+
+    if key.get_value(new).is_ok() {
+        return Err(backend::Error::already_exists(format!("The value {new} already exists")));
+    }
+
+    key.set_value(new, &value)?;
+    key.remove_value(original)
 }
 
-pub fn delete_key(key: &Key, name: impl AsRef<str>) -> windows_registry::Result<()> {
-    key.remove_tree(name).map(|_| ())
+pub fn delete_value(key: &dyn RegistryKey, name: impl AsRef<str>) -> backend::Result<()> {
+    key.remove_value(name.as_ref())
 }
 
-pub fn read_values(key: &Key) -> windows_registry::Result<Vec<(String, Value)>> {
-    key.values().map(|values| values.collect())
+/// How a value name is shown, the default value having an empty name.
+pub fn display_name(name: &str) -> &str {
+    match name.is_empty() {
+        true => "(Default)",
+        false => name,
+    }
 }
 
 pub fn type_to_str(t: Type) -> &'static str {
@@ -71,43 +217,86 @@ pub fn type_to_str(t: Type) -> &'static str {
         Type::ExpandString => "REG_EXPAND_SZ",
         Type::MultiString => "REG_MULTI_SZ",
         Type::U32 => "REG_DWORD",
+        Type::U32BigEndian => "REG_DWORD_BIG_ENDIAN",
         Type::U64 => "REG_QWORD",
-        Type::Other(_) => "REG_NONE",
+        Type::Link => "REG_LINK",
+        Type::ResourceList => "REG_RESOURCE_LIST",
+        Type::FullResourceDescriptor => "REG_FULL_RESOURCE_DESCRIPTOR",
+        Type::ResourceRequirementsList => "REG_RESOURCE_REQUIREMENTS_LIST",
+        Type::None => "REG_NONE",
+        Type::Other(_) => "REG_UNKNOWN",
     }
 }
 
 pub fn str_to_type(s: &str) -> Type {
-    match s {
-        "REG_BINARY" => Type::Bytes,
-        "REG_SZ" => Type::String,
-        "REG_EXPAND_SZ" => Type::ExpandString,
-        "REG_MULTI_SZ" => Type::MultiString,
-        "REG_DWORD" => Type::U32,
-        "REG_QWORD" => Type::U64,
-        "REG_NONE" => Type::Other(0),
-        _ => Type::Other(0),
+    s.parse().unwrap_or(Type::None)
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", type_to_str(*self))
+    }
+}
+
+impl FromStr for Type {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "REG_BINARY" => Ok(Type::Bytes),
+            "REG_SZ" => Ok(Type::String),
+            "REG_EXPAND_SZ" => Ok(Type::ExpandString),
+            "REG_MULTI_SZ" => Ok(Type::MultiString),
+            "REG_DWORD" | "REG_DWORD_LITTLE_ENDIAN" => Ok(Type::U32),
+            "REG_DWORD_BIG_ENDIAN" => Ok(Type::U32BigEndian),
+            "REG_QWORD" | "REG_QWORD_LITTLE_ENDIAN" => Ok(Type::U64),
+            "REG_LINK" => Ok(Type::Link),
+            "REG_RESOURCE_LIST" => Ok(Type::ResourceList),
+            "REG_FULL_RESOURCE_DESCRIPTOR" => Ok(Type::FullResourceDescriptor),
+            "REG_RESOURCE_REQUIREMENTS_LIST" => Ok(Type::ResourceRequirementsList),
+            "REG_NONE" => Ok(Type::None),
+            _ => Err(()),
+        }
     }
 }
 
-fn get_printable_binary(value: &Value) -> String {
-    value.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().join(" ")
+pub fn encode_sz(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|u| u.to_le_bytes())
+        .collect()
+}
+
+pub fn encode_multi_sz(strings: &[String]) -> Vec<u8> {
+    strings
+        .iter()
+        .flat_map(|s| s.encode_utf16().chain(std::iter::once(0)))
+        .chain(std::iter::once(0))
+        .flat_map(|u| u.to_le_bytes())
+        .collect()
+}
+
+fn get_printable_binary(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().join(" ")
 }
 
-fn get_printable_sz(value: &Value) -> String {
+/// Reads string data up to the first NUL.
+pub fn decode_sz(value: &Value) -> String {
     let wide = value.as_wide();
     let wstr = match wide.iter().position(|&c| c == 0) {
         Some(pos) => &wide[..pos],
-        None => wide,
+        None => &wide,
     };
 
     String::from_utf16_lossy(wstr).to_string()
 }
 
-fn get_printable_multi_sz(value: &Value) -> String {
+/// Reads the strings of REG_MULTI_SZ data, stopping at the empty string that ends the list.
+pub fn decode_multi_sz(value: &Value) -> Vec<String> {
     let mut strs = Vec::new();
     let mut current = Vec::new();
 
-    for &u in value.as_wide() {
+    for u in value.as_wide() {
         match u {
             0 => {
                 if current.is_empty() {
@@ -121,33 +310,363 @@ fn get_printable_multi_sz(value: &Value) -> String {
         }
     }
 
-    strs.join(" ").trim_end().to_string()
+    // data that's missing its terminator
+    if !current.is_empty() {
+        strs.push(String::from_utf16_lossy(&current));
+    }
+
+    strs
 }
 
-fn get_printable_u32(value: &Value) -> String {
-    let num = u32::from_le_bytes(*&value[..4].try_into().unwrap());
+const MULTI_SZ_SEPARATOR: &str = " ¦ ";
 
-    format!("{:#010x} ({})", num, num)
+/// Shows the entry count up front, since long lists get cut off in the table.
+fn format_multi_sz(strings: &[String]) -> String {
+    format!("[{}] {}", strings.len(), strings.join(MULTI_SZ_SEPARATOR))
 }
 
-fn get_printable_u64(value: &Value) -> String {
-    let num = u64::from_le_bytes(*&value[..8].try_into().unwrap());
+/// Data that doesn't match the layout its type calls for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataError {
+    OddLength,
+    MissingTerminator,
+    /// Anything after the terminator, including extra NULs.
+    TrailingData,
+    EmptyString,
+    InvalidUtf16,
+    WrongSize { expected: usize, actual: usize },
+}
 
-    format!("{:#010x} ({})", num, num)
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OddLength => write!(f, "UTF-16 data with an odd number of bytes"),
+            Self::MissingTerminator => write!(f, "missing NUL terminator"),
+            Self::TrailingData => write!(f, "data after the NUL terminator"),
+            Self::EmptyString => write!(f, "empty string inside a string list"),
+            Self::InvalidUtf16 => write!(f, "invalid UTF-16"),
+            Self::WrongSize { expected, actual } => write!(f, "expected {expected} bytes, found {actual}"),
+        }
+    }
+}
+
+/// Decoded value data. Anything that can't be decoded exactly is kept as
+/// `Unknown`, so `RegValue::from(&value).encode() == value` always holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegValue {
+    Binary(Vec<u8>),
+    Sz(String),
+    ExpandSz(String),
+    MultiSz(Vec<String>),
+    Dword(u32),
+    DwordBE(u32),
+    Qword(u64),
+    Link(String),
+    None,
+    Unknown { ty: Type, bytes: Vec<u8> },
+}
+
+fn decode_utf16(data: &[u8]) -> Result<Vec<u16>, DataError> {
+    match data.len().is_multiple_of(2) {
+        true => Ok(data.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect()),
+        false => Err(DataError::OddLength),
+    }
+}
+
+fn decode_string(units: &[u16]) -> Result<String, DataError> {
+    String::from_utf16(units).map_err(|_| DataError::InvalidUtf16)
+}
+
+fn decode_terminated(data: &[u8]) -> Result<String, DataError> {
+    let units = decode_utf16(data)?;
+
+    match units.iter().position(|&u| u == 0) {
+        Some(pos) if pos + 1 == units.len() => decode_string(&units[..pos]),
+        Some(_) => Err(DataError::TrailingData),
+        None => Err(DataError::MissingTerminator),
+    }
+}
+
+fn decode_terminated_list(data: &[u8]) -> Result<Vec<String>, DataError> {
+    let units = decode_utf16(data)?;
+    let Some((&0, body)) = units.split_last() else {
+        return Err(DataError::MissingTerminator);
+    };
+
+    if body.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some((&0, body)) = body.split_last() else {
+        return Err(DataError::MissingTerminator);
+    };
+
+    body.split(|&u| u == 0)
+        .map(|units| match units.is_empty() {
+            true => Err(DataError::EmptyString),
+            false => decode_string(units),
+        })
+        .collect()
+}
+
+fn decode_number<const N: usize>(data: &[u8]) -> Result<[u8; N], DataError> {
+    data.try_into().map_err(|_| DataError::WrongSize { expected: N, actual: data.len() })
+}
+
+impl RegValue {
+    /// Decodes the data strictly, failing on anything that wouldn't encode
+    /// back to the same bytes.
+    pub fn decode(value: &Value) -> Result<Self, DataError> {
+        let data = value.as_ref();
+
+        Ok(match value.ty() {
+            Type::Bytes => Self::Binary(data.to_vec()),
+            Type::String => Self::Sz(decode_terminated(data)?),
+            Type::ExpandString => Self::ExpandSz(decode_terminated(data)?),
+            Type::MultiString => Self::MultiSz(decode_terminated_list(data)?),
+            Type::U32 => Self::Dword(u32::from_le_bytes(decode_number(data)?)),
+            Type::U32BigEndian => Self::DwordBE(u32::from_be_bytes(decode_number(data)?)),
+            Type::U64 => Self::Qword(u64::from_le_bytes(decode_number(data)?)),
+            Type::Link => Self::Link(decode_string(&decode_utf16(data)?)?),
+            Type::None if data.is_empty() => Self::None,
+            ty => Self::Unknown { ty, bytes: data.to_vec() },
+        })
+    }
+
+    pub fn ty(&self) -> Type {
+        match self {
+            Self::Binary(_) => Type::Bytes,
+            Self::Sz(_) => Type::String,
+            Self::ExpandSz(_) => Type::ExpandString,
+            Self::MultiSz(_) => Type::MultiString,
+            Self::Dword(_) => Type::U32,
+            Self::DwordBE(_) => Type::U32BigEndian,
+            Self::Qword(_) => Type::U64,
+            Self::Link(_) => Type::Link,
+            Self::None => Type::None,
+            Self::Unknown { ty, .. } => *ty,
+        }
+    }
+
+    pub fn encode(&self) -> Value {
+        let data = match self {
+            Self::Binary(bytes) | Self::Unknown { bytes, .. } => bytes.clone(),
+            Self::Sz(s) | Self::ExpandSz(s) => encode_sz(s),
+            Self::MultiSz(strings) => encode_multi_sz(strings),
+            Self::Dword(n) => n.to_le_bytes().to_vec(),
+            Self::DwordBE(n) => n.to_be_bytes().to_vec(),
+            Self::Qword(n) => n.to_le_bytes().to_vec(),
+            Self::Link(s) => s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect(),
+            Self::None => Vec::new(),
+        };
+
+        Value::new(self.ty(), data)
+    }
+
+    /// Parses the text typed into the value editor for the given type.
+    pub fn parse(ty: Type, s: &str) -> Result<Self, ValueParserError> {
+        Ok(match ty {
+            Type::String => Self::Sz(ValueStringParser::new().parse(s)?),
+            Type::ExpandString => Self::ExpandSz(ValueStringParser::new().parse(s)?),
+            Type::MultiString => Self::MultiSz(ValueMultistringParser::new().parse(s)?),
+            Type::U32 => Self::Dword(ValueU32Parser::new().parse(s)?),
+            Type::U32BigEndian => Self::DwordBE(ValueU32Parser::new().parse(s)?),
+            Type::U64 => Self::Qword(ValueU64Parser::new().parse(s)?),
+            ty => Self::from(&Value::new(ty, ValueBytesParser::new().parse(s)?)),
+        })
+    }
+
+    /// Formats the data the way it's typed into the value editor, so that
+    /// `parse` turns it back into the same value.
+    pub fn to_editable(&self) -> String {
+        match self {
+            Self::Sz(s) | Self::ExpandSz(s) => s.clone(),
+            Self::MultiSz(strings) => strings.join("\n"),
+            Self::Dword(n) | Self::DwordBE(n) => n.to_string(),
+            Self::Qword(n) => n.to_string(),
+            // malformed strings are edited as text and fixed up on save
+            Self::Unknown { ty: Type::String | Type::ExpandString, bytes } => decode_sz(&Value::new(Type::String, bytes.clone())),
+            Self::Unknown { ty: Type::MultiString, bytes } => decode_multi_sz(&Value::new(Type::MultiString, bytes.clone())).join("\n"),
+            _ => get_printable_binary(&self.encode()),
+        }
+    }
+
+    fn fmt_unknown(ty: Type, bytes: &[u8]) -> String {
+        let value = Value::new(ty, bytes.to_vec());
+
+        let decoded = match ty {
+            Type::String | Type::ExpandString => Some(decode_sz(&value)),
+            Type::MultiString => Some(format_multi_sz(&decode_multi_sz(&value))),
+            Type::ResourceList => resources::parse_resource_list(bytes)
+                .map(|list| list.iter().map(|descriptor| descriptor.to_string()).collect::<Vec<_>>().join("; ")),
+            Type::FullResourceDescriptor => resources::parse_full_resource_descriptor(bytes).map(|descriptor| descriptor.to_string()),
+            Type::ResourceRequirementsList => resources::parse_resource_requirements_list(bytes).map(|list| list.to_string()),
+            _ => None,
+        };
+
+        decoded.unwrap_or_else(|| get_printable_binary(bytes))
+    }
+}
+
+impl From<&Value> for RegValue {
+    fn from(value: &Value) -> Self {
+        Self::decode(value).unwrap_or_else(|_| Self::Unknown { ty: value.ty(), bytes: value.to_vec() })
+    }
+}
+
+impl fmt::Display for RegValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Binary(bytes) => write!(f, "{}", get_printable_binary(bytes)),
+            Self::Sz(s) | Self::ExpandSz(s) | Self::Link(s) => write!(f, "{s}"),
+            Self::MultiSz(strings) => write!(f, "{}", format_multi_sz(strings)),
+            Self::Dword(n) | Self::DwordBE(n) => write!(f, "{:#010x} ({})", n, n),
+            Self::Qword(n) => write!(f, "{:#010x} ({})", n, n),
+            Self::None => Ok(()),
+            Self::Unknown { ty, bytes } => write!(f, "{}", Self::fmt_unknown(*ty, bytes)),
+        }
+    }
+}
+
+/// Parses `TYPE:data`, e.g. `REG_DWORD:42`, with the data written the way
+/// it's typed into the value editor.
+impl FromStr for RegValue {
+    type Err = ValueParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ty, data) = s.split_once(':').unwrap_or((s, ""));
+        let ty = ty.parse::<Type>().map_err(|_| ValueParserError::TypeError(ty.to_owned()))?;
+
+        Self::parse(ty, data)
+    }
 }
 
 pub fn get_printable_value(value: &Value) -> String {
-    match value.ty() {
-        Type::Bytes => get_printable_binary(value),
-        Type::String | Type::ExpandString => get_printable_sz(value),
-        Type::MultiString => get_printable_multi_sz(value),
-        Type::U32 if value.len() >= 4 => get_printable_u32(value),
-        Type::U64 if value.len() >= 8 => get_printable_u64(value),
-        _ => "(unknown data)".into(),
+    RegValue::from(value).to_string()
+}
+
+pub fn get_editable_value(value: &Value) -> String {
+    RegValue::from(value).to_editable()
+}
+
+pub struct Conversion {
+    pub value: Value,
+    /// Set when some of the data can't be carried over to the new type.
+    pub warning: Option<String>,
+}
+
+impl Conversion {
+    fn lossless(ty: Type, data: Vec<u8>) -> Self {
+        Self { value: Value::new(ty, data), warning: None }
+    }
+
+    fn lossy(ty: Type, data: Vec<u8>, warning: impl Into<String>) -> Self {
+        Self { value: Value::new(ty, data), warning: Some(warning.into()) }
+    }
+}
+
+fn resize_number(value: &Value, ty: Type, size: usize) -> Conversion {
+    let mut data = value.to_vec();
+    data.resize(size, 0);
+
+    match value.len() == size {
+        true => Conversion::lossless(ty, data),
+        false if value.len() < size => Conversion::lossy(ty, data, format!("The data is {} bytes long and will be padded to {size}", value.len())),
+        false => Conversion::lossy(ty, data, format!("The data is {} bytes long and will be cut to {size}", value.len())),
+    }
+}
+
+fn number_to(n: u64, ty: Type) -> Conversion {
+    match ty {
+        Type::U32 => match u32::try_from(n) {
+            Ok(n) => Conversion::lossless(ty, n.to_le_bytes().to_vec()),
+            Err(_) => Conversion::lossy(ty, (n as u32).to_le_bytes().to_vec(), format!("{n} doesn't fit in a REG_DWORD and will be truncated")),
+        },
+        Type::U32BigEndian => match u32::try_from(n) {
+            Ok(n) => Conversion::lossless(ty, n.to_be_bytes().to_vec()),
+            Err(_) => Conversion::lossy(ty, (n as u32).to_be_bytes().to_vec(), format!("{n} doesn't fit in a REG_DWORD_BIG_ENDIAN and will be truncated")),
+        },
+        Type::U64 => Conversion::lossless(ty, n.to_le_bytes().to_vec()),
+        Type::String | Type::ExpandString => Conversion::lossless(ty, encode_sz(&n.to_string())),
+        Type::MultiString => Conversion::lossless(ty, encode_multi_sz(&[n.to_string()])),
+        _ => unreachable!(),
+    }
+}
+
+fn text_to(strings: Vec<String>, ty: Type) -> Conversion {
+    let first = strings.first().cloned().unwrap_or_default();
+
+    match ty {
+        Type::String | Type::ExpandString => match strings.len() {
+            0 | 1 => Conversion::lossless(ty, encode_sz(&first)),
+            n => Conversion::lossy(ty, encode_sz(&first), format!("Only the first of the {n} strings will be kept")),
+        },
+        Type::MultiString => Conversion::lossless(ty, encode_multi_sz(&strings)),
+        Type::U32 | Type::U32BigEndian | Type::U64 => {
+            let n = match ty {
+                Type::U32 | Type::U32BigEndian => ValueU32Parser::new().parse(&first).map(u64::from).ok(),
+                _ => ValueU64Parser::new().parse(&first).ok(),
+            };
+
+            match (n, strings.len()) {
+                (Some(n), 1) => number_to(n, ty),
+                (Some(n), count) => {
+                    let conversion = number_to(n, ty);
+                    Conversion::lossy(ty, conversion.value.to_vec(), format!("Only the first of the {count} strings will be kept"))
+                }
+                (None, _) => Conversion::lossy(ty, number_to(0, ty).value.to_vec(), format!("'{first}' isn't a number, the data will be set to 0")),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Converts data to another type, keeping as much of it as possible.
+pub fn convert_value(value: &Value, ty: Type) -> Conversion {
+    if value.ty() == ty {
+        return Conversion::lossless(ty, value.to_vec());
+    }
+
+    match (value.ty(), ty) {
+        // raw bytes can hold anything
+        (_, to) if to.is_raw() => Conversion::lossless(ty, value.to_vec()),
+
+        (from, Type::U32 | Type::U32BigEndian) if from.is_raw() => resize_number(value, ty, 4),
+        (from, Type::U64) if from.is_raw() => resize_number(value, ty, 8),
+        (from, _) if from.is_raw() => match value.len().is_multiple_of(2) {
+            true => Conversion::lossless(ty, value.to_vec()),
+            false => Conversion::lossy(ty, value[..value.len() - 1].to_vec(), "The data has an odd number of bytes, the last one will be dropped"),
+        },
+
+        (Type::U32, _) if value.len() == 4 => number_to(u32::from_le_bytes(value[..4].try_into().unwrap()).into(), ty),
+        (Type::U32BigEndian, _) if value.len() == 4 => number_to(u32::from_be_bytes(value[..4].try_into().unwrap()).into(), ty),
+        (Type::U64, _) if value.len() == 8 => number_to(u64::from_le_bytes(value[..8].try_into().unwrap()), ty),
+        (Type::U32 | Type::U32BigEndian | Type::U64, Type::U32 | Type::U32BigEndian) => resize_number(value, ty, 4),
+        (Type::U32 | Type::U32BigEndian | Type::U64, Type::U64) => resize_number(value, ty, 8),
+        (Type::U32 | Type::U32BigEndian | Type::U64, _) => Conversion::lossy(ty, encode_sz(""), "The number is malformed and will be dropped"),
+
+        // the same UTF-16 data either way
+        (Type::String | Type::ExpandString, Type::String | Type::ExpandString) => Conversion::lossless(ty, value.to_vec()),
+        (Type::String | Type::ExpandString, _) => {
+            let s = decode_sz(value);
+            let strings = match s.is_empty() {
+                true => Vec::new(),
+                false => vec![s],
+            };
+
+            text_to(strings, ty)
+        }
+        (Type::MultiString, _) => text_to(decode_multi_sz(value), ty),
+
+        // raw types are all handled above
+        _ => unreachable!(),
     }
 }
 
 pub enum ValueParserError {
+    BytesError(String),
+    MultiStringError(String),
+    TypeError(String),
     U32Error(ParseIntError),
     U64Error(ParseIntError),
 }
@@ -162,6 +681,7 @@ pub trait ValueParser {
     fn parse(&self, s: &str) -> Result<Self::ParserResult, ValueParserError>;
 }
 
+#[derive(Default)]
 pub struct ValueBytesParser { }
 
 impl ValueBytesParser {
@@ -171,19 +691,144 @@ impl ValueBytesParser {
 }
 
 impl ValueValidator for ValueBytesParser {
-    fn validate(&self, _: &str) -> Result<(), ValueParserError> {
-        todo!()
+    fn validate(&self, s: &str) -> Result<(), ValueParserError> {
+        self.parse(s).map(|_| ())
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn is_byte_separator(c: char) -> bool {
+    c.is_whitespace() || c == ',' || c == ':'
+}
+
+impl ValueBytesParser {
+    /// Splits the input into tokens along with the column each one starts at.
+    fn tokens(s: &str) -> Vec<(usize, &str)> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+
+        for (column, (i, c)) in s.char_indices().enumerate() {
+            match (is_byte_separator(c), start) {
+                (true, Some((start_column, start_index))) => {
+                    tokens.push((start_column, &s[start_index..i]));
+                    start = None;
+                }
+                (false, None) => start = Some((column + 1, i)),
+                _ => (),
+            };
+        }
+
+        if let Some((start_column, start_index)) = start {
+            tokens.push((start_column, &s[start_index..]));
+        }
+
+        tokens
+    }
+
+    fn parse_hex(s: &str) -> Result<Vec<u8>, ValueParserError> {
+        let mut bytes = Vec::new();
+
+        for (column, token) in Self::tokens(s) {
+            let (digits, digits_column) = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+                Some(digits) => (digits, column + 2),
+                None => (token, column),
+            };
+
+            if digits.is_empty() {
+                return Err(ValueParserError::BytesError(format!("'{token}' at column {column} has no hex digits")));
+            }
+            if let Some(offset) = digits.chars().position(|c| !c.is_ascii_hexdigit()) {
+                let c = digits.chars().nth(offset).unwrap();
+                return Err(ValueParserError::BytesError(format!("'{c}' at column {} isn't a hex digit", digits_column + offset)));
+            }
+
+            // a lone digit is a byte of its own, longer runs are read as pairs
+            match digits.len() {
+                1 => bytes.push(u8::from_str_radix(digits, 16).unwrap()),
+                len if len.is_multiple_of(2) => {
+                    for pair in digits.as_bytes().chunks(2) {
+                        bytes.push(u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap());
+                    }
+                }
+                _ => return Err(ValueParserError::BytesError(format!("'{token}' at column {column} has an odd number of hex digits"))),
+            };
+        }
+
+        Ok(bytes)
+    }
+
+    /// `column` is where `s` starts, columns count characters rather than bytes.
+    fn parse_base64(s: &str, column: usize) -> Result<Vec<u8>, ValueParserError> {
+        let chars: Vec<char> = s.chars().collect();
+        let data_len = chars.iter().position(|&c| c == '=').unwrap_or(chars.len());
+        let padding = chars.len() - data_len;
+
+        if let Some(offset) = chars[data_len..].iter().position(|&c| c != '=') {
+            let c = chars[data_len + offset];
+            return Err(ValueParserError::BytesError(format!("'{c}' at column {} comes after the base64 padding", column + data_len + offset)));
+        }
+        if padding > 2 {
+            return Err(ValueParserError::BytesError(format!("Too much base64 padding at column {}", column + data_len)));
+        }
+
+        let mut bytes = Vec::new();
+        let mut buffer = 0u32;
+        let mut bits = 0;
+
+        for (offset, &c) in chars[..data_len].iter().enumerate() {
+            let Some(sextet) = BASE64_ALPHABET.iter().position(|&b| b as char == c) else {
+                return Err(ValueParserError::BytesError(format!("'{c}' at column {} isn't valid base64", column + offset)));
+            };
+
+            buffer = (buffer << 6) | sextet as u32;
+            bits += 6;
+
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+
+        if data_len % 4 == 1 {
+            return Err(ValueParserError::BytesError(format!("Base64 data ends early at column {}", column + data_len)));
+        }
+        // padding, when there is any, has to fill up the last group of four
+        if padding > 0 && !chars.len().is_multiple_of(4) {
+            return Err(ValueParserError::BytesError(format!("Wrong amount of base64 padding at column {}", column + data_len)));
+        }
+
+        Ok(bytes)
     }
 }
 
 impl ValueParser for ValueBytesParser {
-    type ParserResult = String;
+    type ParserResult = Vec<u8>;
 
+    /// Accepts hex bytes separated by spaces, commas or colons, optionally
+    /// prefixed with `0x`, or a single run of base64. Base64 can be forced
+    /// with a `base64:` prefix.
     fn parse(&self, s: &str) -> Result<Self::ParserResult, ValueParserError> {
-        todo!()
+        if let Some(data) = s.trim_start().strip_prefix("base64:") {
+            let leading = s.chars().count() - s.trim_start().chars().count();
+            let trimmed = data.trim();
+            let column = leading + "base64:".len() + (data.chars().count() - data.trim_start().chars().count()) + 1;
+
+            return Self::parse_base64(trimmed, column);
+        }
+
+        match Self::parse_hex(s) {
+            Ok(bytes) => Ok(bytes),
+            // only padded base64 that can't be mistaken for hex is picked up without the prefix
+            Err(err) => match Self::tokens(s).as_slice() {
+                [(column, token)] if token.len().is_multiple_of(4) && !token.starts_with("0x") => Self::parse_base64(token, *column).map_err(|_| err),
+                _ => Err(err),
+            },
+        }
     }
 }
 
+#[derive(Default)]
 pub struct ValueStringParser { }
 
 impl ValueStringParser {
@@ -206,6 +851,7 @@ impl ValueParser for ValueStringParser {
     }
 }
 
+#[derive(Default)]
 pub struct ValueMultistringParser { }
 
 impl ValueMultistringParser {
@@ -215,19 +861,36 @@ impl ValueMultistringParser {
 }
 
 impl ValueValidator for ValueMultistringParser {
-    fn validate(&self, _: &str) -> Result<(), ValueParserError> {
-        todo!()
+    fn validate(&self, s: &str) -> Result<(), ValueParserError> {
+        self.parse(s).map(|_| ())
     }
 }
 
 impl ValueParser for ValueMultistringParser {
     type ParserResult = Vec<String>;
 
+    /// One string per line. Trailing empty lines are dropped, but Windows
+    /// doesn't allow empty strings in between the others.
     fn parse(&self, s: &str) -> Result<Self::ParserResult, ValueParserError> {
-        todo!()
+        let mut lines: Vec<&str> = s.lines().collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            if line.is_empty() {
+                return Err(ValueParserError::MultiStringError(format!("Line {} is empty, REG_MULTI_SZ can't contain empty strings", i + 1)));
+            }
+            if let Some(column) = line.chars().position(|c| c == '\0') {
+                return Err(ValueParserError::MultiStringError(format!("Line {} has a NUL character at column {}", i + 1, column + 1)));
+            }
+        }
+
+        Ok(lines.into_iter().map(|line| line.to_owned()).collect())
     }
 }
 
+#[derive(Default)]
 pub struct ValueU32Parser { }
 
 impl ValueU32Parser {
@@ -255,13 +918,14 @@ impl ValueParser for ValueU32Parser {
         } else if let Some(binary) = s.strip_prefix("0b") {
             u32::from_str_radix(binary, 2)
         } else {
-            u32::from_str_radix(&s, 10)
+            s.parse::<u32>()
         };
         
-        result.map_err(|err| ValueParserError::U32Error(err))
+        result.map_err(ValueParserError::U32Error)
     }
 }
 
+#[derive(Default)]
 pub struct ValueU64Parser { }
 
 impl ValueU64Parser {
@@ -289,33 +953,158 @@ impl ValueParser for ValueU64Parser {
         } else if let Some(binary) = s.strip_prefix("0b") {
             u64::from_str_radix(binary, 2)
         } else {
-            u64::from_str_radix(&s, 10)
+            s.parse::<u64>()
         };
         
-        result.map_err(|err| ValueParserError::U64Error(err))
+        result.map_err(ValueParserError::U64Error)
     }
 }
 
-pub fn get_value_validator(ty: windows_registry::Type) -> Box<dyn ValueValidator> {
+pub fn get_value_validator(ty: Type) -> Box<dyn ValueValidator> {
     match ty {
-        Type::Bytes => Box::new(ValueBytesParser::new()),
         Type::String | Type::ExpandString => Box::new(ValueStringParser::new()),
         Type::MultiString => Box::new(ValueMultistringParser::new()),
-        Type::U32 => Box::new(ValueU32Parser::new()),
+        Type::U32 | Type::U32BigEndian => Box::new(ValueU32Parser::new()),
         Type::U64 => Box::new(ValueU64Parser::new()),
-        _ => unreachable!(),
+        _ => Box::new(ValueBytesParser::new()),
     }
 }
 
-pub fn set_value(key: &Key, name: impl AsRef<str>, ty: windows_registry::Type, value: &str) -> Result<(), ()> {
-    match ty {
-        Type::Bytes => (),
-        Type::String | Type::ExpandString => (),
-        Type::MultiString => (),
-        Type::U32 => (),
-        Type::U64 => (),
-        _ => (),
-    };
+/// Parses the text entered for a value and encodes it the way the registry stores it.
+pub fn encode_value(ty: Type, s: &str) -> Result<Value, ValueParserError> {
+    RegValue::parse(ty, s).map(|value| value.encode())
+}
+
+pub fn parser_error_message(err: ValueParserError) -> String {
+    match err {
+        ValueParserError::BytesError(message) => message,
+        ValueParserError::MultiStringError(message) => message,
+        ValueParserError::TypeError(ty) => format!("{ty} isn't a registry value type"),
+        ValueParserError::U32Error(err) => format!("{}", err),
+        ValueParserError::U64Error(err) => format!("{}", err),
+    }
+}
 
-    Ok(())
+/// Writes the value and returns what was stored.
+pub fn set_value(key: &dyn RegistryKey, name: impl AsRef<str>, ty: Type, value: &str) -> backend::Result<Value> {
+    let value = encode_value(ty, value)
+        .map_err(|err| backend::Error::new(ErrorKind::InvalidInput, parser_error_message(err)))?;
+
+    key.set_value(name.as_ref(), &value)?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &str) -> Result<Vec<u8>, String> {
+        ValueBytesParser::new().parse(s).map_err(parser_error_message)
+    }
+
+    #[test]
+    fn bytes_parse_hex() {
+        assert_eq!(bytes("01 02,0a:FF"), Ok(vec![1, 2, 10, 255]));
+        assert_eq!(bytes("0x1234 f"), Ok(vec![0x12, 0x34, 0x0f]));
+        assert_eq!(bytes(""), Ok(vec![]));
+        assert_eq!(bytes("123"), Err("'123' at column 1 has an odd number of hex digits".to_owned()));
+        assert_eq!(bytes("00 0x"), Err("'0x' at column 4 has no hex digits".to_owned()));
+        assert_eq!(bytes("00 1g"), Err("'g' at column 5 isn't a hex digit".to_owned()));
+    }
+
+    #[test]
+    fn bytes_parse_base64() {
+        assert_eq!(bytes("base64:aGVsbG8="), Ok(b"hello".to_vec()));
+        assert_eq!(bytes("base64: aGk"), Ok(b"hi".to_vec()));
+        assert_eq!(bytes("aGVsbG8h"), Ok(b"hello!".to_vec()));
+        assert_eq!(bytes("aGk"), Err("'G' at column 2 isn't a hex digit".to_owned()));
+        assert_eq!(bytes("aGVsbA=="), Ok(b"hell".to_vec()));
+    }
+
+    #[test]
+    fn base64_errors_count_characters() {
+        assert_eq!(bytes("base64:é"), Err("'é' at column 8 isn't valid base64".to_owned()));
+        assert_eq!(bytes("ü base64:aGk*"), Err("'ü' at column 1 isn't a hex digit".to_owned()));
+        assert_eq!(bytes(" base64:ééaGk"), Err("'é' at column 9 isn't valid base64".to_owned()));
+    }
+
+    #[test]
+    fn base64_padding_is_checked() {
+        assert_eq!(bytes("base64:aG=k"), Err("'k' at column 11 comes after the base64 padding".to_owned()));
+        assert_eq!(bytes("base64:aGk==="), Err("Too much base64 padding at column 11".to_owned()));
+        assert_eq!(bytes("base64:aGk=="), Err("Wrong amount of base64 padding at column 11".to_owned()));
+        assert_eq!(bytes("base64:aGVs="), Err("Wrong amount of base64 padding at column 12".to_owned()));
+        assert_eq!(bytes("base64:aGVsb"), Err("Base64 data ends early at column 13".to_owned()));
+        assert_eq!(bytes("base64:aGk="), Ok(b"hi".to_vec()));
+    }
+
+    #[test]
+    fn multistring_lines() {
+        let parser = ValueMultistringParser::new();
+
+        assert_eq!(parser.parse("a\nb\n\n").map_err(parser_error_message), Ok(vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(parser.parse("").map_err(parser_error_message), Ok(vec![]));
+        assert_eq!(parser.parse("a\n\nb").map_err(parser_error_message), Err("Line 2 is empty, REG_MULTI_SZ can't contain empty strings".to_owned()));
+        assert_eq!(parser.parse("a\nxé\0").map_err(parser_error_message), Err("Line 2 has a NUL character at column 3".to_owned()));
+    }
+
+    fn sample_values() -> Vec<RegValue> {
+        vec![
+            RegValue::Binary(vec![0, 1, 0xfe, 0xff]),
+            RegValue::Sz("héllo wörld".to_owned()),
+            RegValue::Sz(String::new()),
+            RegValue::ExpandSz("%SystemRoot%\\system32".to_owned()),
+            RegValue::MultiSz(vec!["alpha".to_owned(), "beta".to_owned()]),
+            RegValue::MultiSz(vec![]),
+            RegValue::Dword(0xdead_beef),
+            RegValue::DwordBE(1),
+            RegValue::Qword(u64::MAX),
+            RegValue::Link("\\Registry\\Machine\\Software".to_owned()),
+            RegValue::None,
+        ]
+    }
+
+    #[test]
+    fn values_decode_to_what_was_encoded() {
+        for value in sample_values() {
+            assert_eq!(RegValue::decode(&value.encode()), Ok(value.clone()));
+        }
+
+        assert_eq!(RegValue::Dword(1).encode(), Value::new(Type::U32, vec![1, 0, 0, 0]));
+        assert_eq!(RegValue::DwordBE(1).encode(), Value::new(Type::U32BigEndian, vec![0, 0, 0, 1]));
+        assert_eq!(RegValue::Sz("a".to_owned()).encode(), Value::new(Type::String, vec![b'a', 0, 0, 0]));
+        assert_eq!(RegValue::MultiSz(vec!["a".to_owned()]).encode(), Value::new(Type::MultiString, vec![b'a', 0, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn malformed_data_is_kept_as_is() {
+        let malformed = [
+            (Value::new(Type::String, vec![b'a', 0]), DataError::MissingTerminator),
+            (Value::new(Type::String, vec![b'a', 0, 0]), DataError::OddLength),
+            (Value::new(Type::String, vec![b'a', 0, 0, 0, 0, 0]), DataError::TrailingData),
+            (Value::new(Type::MultiString, vec![b'a', 0, 0, 0, 0, 0, b'b', 0, 0, 0, 0, 0]), DataError::EmptyString),
+            (Value::new(Type::String, vec![0x00, 0xd8, 0, 0]), DataError::InvalidUtf16),
+            (Value::new(Type::U32, vec![1, 0, 0]), DataError::WrongSize { expected: 4, actual: 3 }),
+        ];
+
+        for (value, error) in malformed {
+            assert_eq!(RegValue::decode(&value), Err(error));
+            assert_eq!(RegValue::from(&value), RegValue::Unknown { ty: value.ty(), bytes: value.to_vec() });
+            assert_eq!(RegValue::from(&value).encode(), value);
+        }
+
+        let other = Value::new(Type::Other(0x1234), vec![1, 2]);
+        assert_eq!(RegValue::from(&other).encode(), other);
+    }
+
+    #[test]
+    fn editable_text_parses_back() {
+        for value in sample_values() {
+            assert_eq!(RegValue::parse(value.ty(), &value.to_editable()).map_err(parser_error_message), Ok(value.clone()));
+        }
+
+        assert_eq!("REG_DWORD:42".parse::<RegValue>().map_err(parser_error_message), Ok(RegValue::Dword(42)));
+        assert_eq!("REG_BINARY:01 ff".parse::<RegValue>().map_err(parser_error_message), Ok(RegValue::Binary(vec![1, 0xff])));
+        assert_eq!("REG_NOPE:1".parse::<RegValue>().map_err(parser_error_message), Err("REG_NOPE isn't a registry value type".to_owned()));
+    }
 }
@@ -0,0 +1,446 @@
+use std::fmt;
+
+// CM_PARTIAL_RESOURCE_DESCRIPTOR is 16 bytes on 32-bit Windows and 20 bytes
+// on 64-bit Windows, where the interrupt affinity is 8 bytes wide and the
+// interrupt level shares its 4 bytes with the processor group.
+const PARTIAL_DESCRIPTOR_SIZES: [usize; 2] = [20, 16];
+const FULL_DESCRIPTOR_HEADER_SIZE: usize = 16;
+
+const IO_DESCRIPTOR_SIZE: usize = 32;
+const IO_LIST_HEADER_SIZE: usize = 8;
+const REQUIREMENTS_HEADER_SIZE: usize = 32;
+
+const TYPE_PORT: u8 = 1;
+const TYPE_INTERRUPT: u8 = 2;
+const TYPE_MEMORY: u8 = 3;
+const TYPE_DMA: u8 = 4;
+const TYPE_DEVICE_SPECIFIC: u8 = 5;
+const TYPE_BUS_NUMBER: u8 = 6;
+const TYPE_MEMORY_LARGE: u8 = 7;
+
+const MEMORY_LARGE_40: u16 = 0x200;
+const MEMORY_LARGE_48: u16 = 0x400;
+const MEMORY_LARGE_64: u16 = 0x800;
+
+const INTERFACE_TYPES: [&str; 18] = [
+    "Internal", "Isa", "Eisa", "MicroChannel", "TurboChannel", "PCIBus", "VMEBus", "NuBus", "PCMCIABus",
+    "CBus", "MPIBus", "MPSABus", "ProcessorInternal", "InternalPowerBus", "PNPISABus", "PNPBus", "Vmcs", "ACPIBus",
+];
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+pub fn interface_type_name(interface_type: u32) -> String {
+    match INTERFACE_TYPES.get(interface_type as usize) {
+        Some(name) => (*name).to_owned(),
+        None if interface_type == u32::MAX => "Undefined".to_owned(),
+        None => format!("Bus {interface_type}"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartialResource {
+    Port { start: u64, length: u32 },
+    Interrupt { level: u32, vector: u32, affinity: u64 },
+    Memory { start: u64, length: u64 },
+    Dma { channel: u32, port: u32 },
+    DeviceSpecific { data: Vec<u8> },
+    BusNumber { start: u32, length: u32 },
+    Other { ty: u8, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialDescriptor {
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub resource: PartialResource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullResourceDescriptor {
+    pub interface_type: u32,
+    pub bus_number: u32,
+    pub version: u16,
+    pub revision: u16,
+    pub partial_descriptors: Vec<PartialDescriptor>,
+}
+
+fn parse_partial_descriptor(data: &[u8], offset: usize, size: usize) -> Option<(PartialDescriptor, usize)> {
+    let descriptor = data.get(offset..offset + size)?;
+    let (ty, share_disposition, flags) = (descriptor[0], descriptor[1], read_u16(descriptor, 2)?);
+    let mut end = offset + size;
+
+    let resource = match ty {
+        TYPE_PORT => PartialResource::Port { start: read_u64(descriptor, 4)?, length: read_u32(descriptor, 12)? },
+        TYPE_INTERRUPT => PartialResource::Interrupt {
+            level: match size {
+                20 => read_u16(descriptor, 4)?.into(),
+                _ => read_u32(descriptor, 4)?,
+            },
+            vector: read_u32(descriptor, 8)?,
+            affinity: match size {
+                20 => read_u64(descriptor, 12)?,
+                _ => read_u32(descriptor, 12)?.into(),
+            },
+        },
+        TYPE_MEMORY => PartialResource::Memory { start: read_u64(descriptor, 4)?, length: read_u32(descriptor, 12)?.into() },
+        TYPE_MEMORY_LARGE => {
+            let length = u64::from(read_u32(descriptor, 12)?);
+            let length = match flags {
+                f if f & MEMORY_LARGE_64 != 0 => length << 32,
+                f if f & MEMORY_LARGE_48 != 0 => length << 16,
+                f if f & MEMORY_LARGE_40 != 0 => length << 8,
+                _ => length,
+            };
+
+            PartialResource::Memory { start: read_u64(descriptor, 4)?, length }
+        }
+        TYPE_DMA => PartialResource::Dma { channel: read_u32(descriptor, 4)?, port: read_u32(descriptor, 8)? },
+        TYPE_DEVICE_SPECIFIC => {
+            // the data follows the descriptor itself
+            let data_size = read_u32(descriptor, 4)? as usize;
+            let extra = data.get(end..end + data_size)?.to_vec();
+            end += data_size;
+
+            PartialResource::DeviceSpecific { data: extra }
+        }
+        TYPE_BUS_NUMBER => PartialResource::BusNumber { start: read_u32(descriptor, 4)?, length: read_u32(descriptor, 8)? },
+        ty => PartialResource::Other { ty, data: descriptor[4..].to_vec() },
+    };
+
+    Some((PartialDescriptor { share_disposition, flags, resource }, end))
+}
+
+fn parse_full_descriptor_at(data: &[u8], offset: usize, size: usize) -> Option<(FullResourceDescriptor, usize)> {
+    let interface_type = read_u32(data, offset)?;
+    let bus_number = read_u32(data, offset + 4)?;
+    let version = read_u16(data, offset + 8)?;
+    let revision = read_u16(data, offset + 10)?;
+    let count = read_u32(data, offset + 12)?;
+
+    let mut end = offset + FULL_DESCRIPTOR_HEADER_SIZE;
+    let mut partial_descriptors = Vec::new();
+
+    for _ in 0..count {
+        let (descriptor, next) = parse_partial_descriptor(data, end, size)?;
+        partial_descriptors.push(descriptor);
+        end = next;
+    }
+
+    Some((FullResourceDescriptor { interface_type, bus_number, version, revision, partial_descriptors }, end))
+}
+
+/// Decodes REG_FULL_RESOURCE_DESCRIPTOR data. Returns `None` unless the data
+/// is exactly one well-formed descriptor.
+pub fn parse_full_resource_descriptor(data: &[u8]) -> Option<FullResourceDescriptor> {
+    PARTIAL_DESCRIPTOR_SIZES.iter().find_map(|&size| {
+        match parse_full_descriptor_at(data, 0, size) {
+            Some((descriptor, end)) if end == data.len() => Some(descriptor),
+            _ => None,
+        }
+    })
+}
+
+/// Decodes REG_RESOURCE_LIST data, a count followed by full resource descriptors.
+pub fn parse_resource_list(data: &[u8]) -> Option<Vec<FullResourceDescriptor>> {
+    let count = read_u32(data, 0)?;
+
+    PARTIAL_DESCRIPTOR_SIZES.iter().find_map(|&size| {
+        let mut end = 4;
+        let mut list = Vec::new();
+
+        for _ in 0..count {
+            let (descriptor, next) = parse_full_descriptor_at(data, end, size)?;
+            list.push(descriptor);
+            end = next;
+        }
+
+        (end == data.len()).then_some(list)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IoResource {
+    Port { length: u32, alignment: u32, minimum: u64, maximum: u64 },
+    Memory { length: u32, alignment: u32, minimum: u64, maximum: u64 },
+    Interrupt { minimum: u32, maximum: u32 },
+    Dma { minimum: u32, maximum: u32 },
+    BusNumber { length: u32, minimum: u32, maximum: u32 },
+    Other { ty: u8, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoResourceDescriptor {
+    pub option: u8,
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub resource: IoResource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRequirementsList {
+    pub interface_type: u32,
+    pub bus_number: u32,
+    pub slot_number: u32,
+    pub alternatives: Vec<Vec<IoResourceDescriptor>>,
+}
+
+fn parse_io_descriptor(data: &[u8]) -> Option<IoResourceDescriptor> {
+    let (option, ty, share_disposition, flags) = (data[0], data[1], data[2], read_u16(data, 4)?);
+
+    let resource = match ty {
+        TYPE_PORT | TYPE_MEMORY | TYPE_MEMORY_LARGE => {
+            let (length, alignment) = (read_u32(data, 8)?, read_u32(data, 12)?);
+            let (minimum, maximum) = (read_u64(data, 16)?, read_u64(data, 24)?);
+
+            match ty {
+                TYPE_PORT => IoResource::Port { length, alignment, minimum, maximum },
+                _ => IoResource::Memory { length, alignment, minimum, maximum },
+            }
+        }
+        TYPE_INTERRUPT => IoResource::Interrupt { minimum: read_u32(data, 8)?, maximum: read_u32(data, 12)? },
+        TYPE_DMA => IoResource::Dma { minimum: read_u32(data, 8)?, maximum: read_u32(data, 12)? },
+        TYPE_BUS_NUMBER => IoResource::BusNumber { length: read_u32(data, 8)?, minimum: read_u32(data, 12)?, maximum: read_u32(data, 16)? },
+        ty => IoResource::Other { ty, data: data[8..].to_vec() },
+    };
+
+    Some(IoResourceDescriptor { option, share_disposition, flags, resource })
+}
+
+/// Decodes REG_RESOURCE_REQUIREMENTS_LIST data.
+pub fn parse_resource_requirements_list(data: &[u8]) -> Option<ResourceRequirementsList> {
+    let list_size = read_u32(data, 0)? as usize;
+    if list_size != data.len() {
+        return None;
+    }
+
+    let interface_type = read_u32(data, 4)?;
+    let bus_number = read_u32(data, 8)?;
+    let slot_number = read_u32(data, 12)?;
+    let count = read_u32(data, 28)?;
+
+    let mut offset = REQUIREMENTS_HEADER_SIZE;
+    let mut alternatives = Vec::new();
+
+    for _ in 0..count {
+        let descriptors = read_u32(data, offset + 4)? as usize;
+        offset += IO_LIST_HEADER_SIZE;
+
+        let list = data.get(offset..offset + descriptors * IO_DESCRIPTOR_SIZE)?
+            .chunks_exact(IO_DESCRIPTOR_SIZE)
+            .map(parse_io_descriptor)
+            .collect::<Option<Vec<_>>>()?;
+
+        offset += descriptors * IO_DESCRIPTOR_SIZE;
+        alternatives.push(list);
+    }
+
+    (offset == data.len()).then_some(ResourceRequirementsList { interface_type, bus_number, slot_number, alternatives })
+}
+
+impl fmt::Display for PartialResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Port { start, length } => write!(f, "Port {start:#x}+{length:#x}"),
+            Self::Interrupt { level, vector, affinity } => write!(f, "Interrupt {level} (vector {vector}, affinity {affinity:#x})"),
+            Self::Memory { start, length } => write!(f, "Memory {start:#x}+{length:#x}"),
+            Self::Dma { channel, port } => write!(f, "DMA channel {channel} (port {port})"),
+            Self::DeviceSpecific { data } => write!(f, "Device specific ({} bytes)", data.len()),
+            Self::BusNumber { start, length } => write!(f, "Bus number {start}+{length}"),
+            Self::Other { ty, .. } => write!(f, "Resource type {ty}"),
+        }
+    }
+}
+
+impl fmt::Display for FullResourceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resources = self.partial_descriptors
+            .iter()
+            .map(|descriptor| descriptor.resource.to_string())
+            .collect::<Vec<_>>();
+
+        write!(f, "{} {}: {}", interface_type_name(self.interface_type), self.bus_number, resources.join(", "))
+    }
+}
+
+impl fmt::Display for IoResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Port { length, minimum, maximum, .. } => write!(f, "Port {length:#x} in {minimum:#x}-{maximum:#x}"),
+            Self::Memory { length, minimum, maximum, .. } => write!(f, "Memory {length:#x} in {minimum:#x}-{maximum:#x}"),
+            Self::Interrupt { minimum, maximum } => write!(f, "Interrupt {minimum}-{maximum}"),
+            Self::Dma { minimum, maximum } => write!(f, "DMA channel {minimum}-{maximum}"),
+            Self::BusNumber { length, minimum, maximum } => write!(f, "Bus number {length} in {minimum}-{maximum}"),
+            Self::Other { ty, .. } => write!(f, "Resource type {ty}"),
+        }
+    }
+}
+
+impl fmt::Display for ResourceRequirementsList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alternatives = self.alternatives
+            .iter()
+            .map(|list| list.iter().map(|descriptor| descriptor.resource.to_string()).collect::<Vec<_>>().join(", "))
+            .collect::<Vec<_>>();
+
+        write!(f, "{} {} slot {}: {}", interface_type_name(self.interface_type), self.bus_number, self.slot_number, alternatives.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_header(interface_type: u32, bus_number: u32, count: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&interface_type.to_le_bytes());
+        data.extend_from_slice(&bus_number.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data
+    }
+
+    fn descriptor_header(ty: u8, flags: u16) -> Vec<u8> {
+        let mut data = vec![ty, 3];
+        data.extend_from_slice(&flags.to_le_bytes());
+        data
+    }
+
+    /// An interrupt in the 16-byte layout of 32-bit Windows.
+    fn interrupt_x86(level: u32, vector: u32, affinity: u32) -> Vec<u8> {
+        let mut data = descriptor_header(TYPE_INTERRUPT, 1);
+        data.extend_from_slice(&level.to_le_bytes());
+        data.extend_from_slice(&vector.to_le_bytes());
+        data.extend_from_slice(&affinity.to_le_bytes());
+        data
+    }
+
+    /// An interrupt in the 20-byte layout of 64-bit Windows.
+    fn interrupt_x64(level: u16, group: u16, vector: u32, affinity: u64) -> Vec<u8> {
+        let mut data = descriptor_header(TYPE_INTERRUPT, 1);
+        data.extend_from_slice(&level.to_le_bytes());
+        data.extend_from_slice(&group.to_le_bytes());
+        data.extend_from_slice(&vector.to_le_bytes());
+        data.extend_from_slice(&affinity.to_le_bytes());
+        data
+    }
+
+    fn port(start: u64, length: u32, size: usize) -> Vec<u8> {
+        let mut data = descriptor_header(TYPE_PORT, 0x11);
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.resize(size, 0);
+        data
+    }
+
+    #[test]
+    fn full_descriptors_are_read_in_the_32_bit_layout() {
+        let mut data = full_header(5, 0, 2);
+        data.extend(port(0x3f8, 8, 16));
+        data.extend(interrupt_x86(4, 4, 0xffff_ffff));
+
+        let descriptor = parse_full_resource_descriptor(&data).unwrap();
+        assert_eq!((descriptor.interface_type, descriptor.version, descriptor.revision), (5, 1, 2));
+        assert_eq!(descriptor.partial_descriptors[0], PartialDescriptor { share_disposition: 3, flags: 0x11, resource: PartialResource::Port { start: 0x3f8, length: 8 } });
+        assert_eq!(descriptor.partial_descriptors[1].resource, PartialResource::Interrupt { level: 4, vector: 4, affinity: 0xffff_ffff });
+        assert_eq!(descriptor.to_string(), "PCIBus 0: Port 0x3f8+0x8, Interrupt 4 (vector 4, affinity 0xffffffff)");
+    }
+
+    #[test]
+    fn full_descriptors_are_read_in_the_64_bit_layout() {
+        let mut data = full_header(15, 1, 2);
+        data.extend(port(0x3f8, 8, 20));
+        data.extend(interrupt_x64(9, 1, 0x39, 0xffff_0000_ffff));
+
+        let descriptor = parse_full_resource_descriptor(&data).unwrap();
+        assert_eq!(descriptor.partial_descriptors[0].resource, PartialResource::Port { start: 0x3f8, length: 8 });
+        // the group next to the level isn't part of it
+        assert_eq!(descriptor.partial_descriptors[1].resource, PartialResource::Interrupt { level: 9, vector: 0x39, affinity: 0xffff_0000_ffff });
+        assert_eq!(descriptor.to_string(), "PNPBus 1: Port 0x3f8+0x8, Interrupt 9 (vector 57, affinity 0xffff0000ffff)");
+
+        assert_eq!(parse_full_resource_descriptor(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn resource_lists_hold_several_descriptors() {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend(full_header(1, 0, 1));
+        data.extend(interrupt_x64(3, 0, 3, 1));
+
+        // device specific data follows its descriptor
+        data.extend(full_header(0, 0, 1));
+        let mut specific = descriptor_header(TYPE_DEVICE_SPECIFIC, 0);
+        specific.extend_from_slice(&3u32.to_le_bytes());
+        specific.resize(20, 0);
+        data.extend(specific);
+        data.extend([0xaa, 0xbb, 0xcc]);
+
+        let list = parse_resource_list(&data).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].partial_descriptors[0].resource, PartialResource::Interrupt { level: 3, vector: 3, affinity: 1 });
+        assert_eq!(list[1].partial_descriptors[0].resource, PartialResource::DeviceSpecific { data: vec![0xaa, 0xbb, 0xcc] });
+
+        let mut data = 1u32.to_le_bytes().to_vec();
+        data.extend(full_header(1, 0, 1));
+        data.extend(interrupt_x86(0x10005, 5, 1));
+        assert_eq!(parse_resource_list(&data).unwrap()[0].partial_descriptors[0].resource, PartialResource::Interrupt { level: 0x10005, vector: 5, affinity: 1 });
+    }
+
+    #[test]
+    fn requirements_lists_hold_alternatives() {
+        let io_descriptor = |ty: u8, fields: &[u8]| {
+            let mut data = vec![0, ty, 1, 0, 0x11, 0, 0, 0];
+            data.extend_from_slice(fields);
+            data.resize(IO_DESCRIPTOR_SIZE, 0);
+            data
+        };
+        let io_list = |descriptors: &[Vec<u8>]| {
+            let mut data = vec![1, 0, 1, 0];
+            data.extend_from_slice(&(descriptors.len() as u32).to_le_bytes());
+            data.extend(descriptors.concat());
+            data
+        };
+
+        let mut port = [8u32.to_le_bytes(), 1u32.to_le_bytes()].concat();
+        port.extend_from_slice(&0x100u64.to_le_bytes());
+        port.extend_from_slice(&0x1ffu64.to_le_bytes());
+        let interrupt = [5u32.to_le_bytes(), 7u32.to_le_bytes()].concat();
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&5u32.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&2u32.to_le_bytes());
+        body.extend_from_slice(&[0; 12]);
+        body.extend_from_slice(&2u32.to_le_bytes());
+        body.extend(io_list(&[io_descriptor(TYPE_PORT, &port), io_descriptor(TYPE_INTERRUPT, &interrupt)]));
+        body.extend(io_list(&[io_descriptor(TYPE_DMA, &interrupt)]));
+
+        let mut data = ((body.len() + 4) as u32).to_le_bytes().to_vec();
+        data.extend(body);
+        assert_eq!(data.len(), REQUIREMENTS_HEADER_SIZE + 2 * IO_LIST_HEADER_SIZE + 3 * IO_DESCRIPTOR_SIZE);
+
+        let list = parse_resource_requirements_list(&data).unwrap();
+        assert_eq!(list.slot_number, 2);
+        assert_eq!(list.alternatives[0][0], IoResourceDescriptor {
+            option: 0,
+            share_disposition: 1,
+            flags: 0x11,
+            resource: IoResource::Port { length: 8, alignment: 1, minimum: 0x100, maximum: 0x1ff },
+        });
+        assert_eq!(list.to_string(), "PCIBus 0 slot 2: Port 0x8 in 0x100-0x1ff, Interrupt 5-7 | DMA channel 5-7");
+
+        // the size in the header has to match
+        data.push(0);
+        assert_eq!(parse_resource_requirements_list(&data), None);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, Sender}, Arc}, thread};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{backend::{self, RegistryBackend, RegistryKey}, registry::{self, RegValue}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub keys: bool,
+    pub values: bool,
+    pub data: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self { keys: true, values: true, data: true, case_sensitive: false, whole_word: false, regex: false }
+    }
+}
+
+impl SearchOptions {
+    /// Flips the option bound to `<Alt-c>`, returning false for unknown letters.
+    pub fn toggle(&mut self, c: char) -> bool {
+        let option = match c.to_ascii_lowercase() {
+            'k' => &mut self.keys,
+            'v' => &mut self.values,
+            'd' => &mut self.data,
+            'c' => &mut self.case_sensitive,
+            'w' => &mut self.whole_word,
+            'r' => &mut self.regex,
+            _ => return false,
+        };
+
+        *option = !*option;
+        true
+    }
+
+    /// Short description of the enabled options, e.g. "keys, data, regex".
+    pub fn describe(&self) -> String {
+        let names = [
+            (self.keys, "keys"),
+            (self.values, "values"),
+            (self.data, "data"),
+            (self.case_sensitive, "case"),
+            (self.whole_word, "word"),
+            (self.regex, "regex"),
+        ];
+
+        names.iter().filter(|(enabled, _)| *enabled).map(|(_, name)| *name).collect::<Vec<_>>().join(", ")
+    }
+
+    pub fn build_matcher(&self, pattern: &str) -> Result<Regex, String> {
+        if !self.keys && !self.values && !self.data {
+            return Err("Nothing to look at, enable keys, values or data".into());
+        }
+        if pattern.is_empty() {
+            return Err("Can't be empty".into());
+        }
+
+        let pattern = match self.regex {
+            true => pattern.to_owned(),
+            false => regex::escape(pattern),
+        };
+        let pattern = match self.whole_word {
+            true => format!(r"\b(?:{pattern})\b"),
+            false => pattern,
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|err| match err {
+                regex::Error::Syntax(_) => "Invalid regular expression".into(),
+                err => err.to_string(),
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitKind {
+    Key,
+    ValueName,
+    Data,
+}
+
+impl HitKind {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Key => "Key",
+            Self::ValueName => "Value",
+            Self::Data => "Data",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// Path of the matching key, or of the key holding the matching value.
+    pub path: Vec<String>,
+    pub value: Option<String>,
+    pub kind: HitKind,
+    /// The text that matched, shown in the result list.
+    pub text: String,
+}
+
+enum SearchEvent {
+    Hit(SearchHit),
+    Done,
+}
+
+/// A search running on a worker thread. Hits are collected by `poll`, the
+/// walk stops once the search is dropped.
+pub struct Search {
+    pub pattern: String,
+    pub hits: Vec<SearchHit>,
+    /// Index of the hit that was jumped to last.
+    pub current: Option<usize>,
+    pub done: bool,
+
+    receiver: Receiver<SearchEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Search {
+    /// Walks the key at `path` and everything below it, or all root keys
+    /// when `path` is empty.
+    pub fn start(backend: Arc<dyn RegistryBackend>, path: Vec<String>, pattern: &str, options: SearchOptions) -> Result<Self, String> {
+        let matcher = options.build_matcher(pattern)?;
+
+        let start = match path.is_empty() {
+            true => backend.roots()
+                .into_iter()
+                .filter_map(|root| backend.open_root(&root).ok().map(|key| (vec![root], key)))
+                .collect(),
+            false => {
+                let key = backend::open_path(backend.as_ref(), &path.join("\\")).map_err(|err| err.message().to_owned())?;
+                vec![(path, key)]
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+
+        thread::spawn(move || {
+            let matches_root = start.len() > 1;
+            let walker = Walker { matcher, options, sender, cancel: worker_cancel };
+
+            for (mut path, key) in start {
+                if !walker.walk(&mut path, key.as_ref(), matches_root) {
+                    return;
+                }
+            }
+
+            let _ = walker.sender.send(SearchEvent::Done);
+        });
+
+        Ok(Self { pattern: pattern.to_owned(), hits: Vec::new(), current: None, done: false, receiver, cancel })
+    }
+
+    /// Collects the hits found since the last call, returning whether there were any.
+    pub fn poll(&mut self) -> bool {
+        let count = self.hits.len();
+
+        for event in self.receiver.try_iter() {
+            match event {
+                SearchEvent::Hit(hit) => self.hits.push(hit),
+                SearchEvent::Done => self.done = true,
+            }
+        }
+
+        self.hits.len() != count
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Walker {
+    matcher: Regex,
+    options: SearchOptions,
+    sender: Sender<SearchEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Walker {
+    fn send(&self, path: &[String], value: Option<&str>, kind: HitKind, text: String) -> bool {
+        let hit = SearchHit { path: path.to_vec(), value: value.map(|value| value.to_owned()), kind, text };
+
+        self.sender.send(SearchEvent::Hit(hit)).is_ok()
+    }
+
+    /// Text the data of a value is matched against.
+    fn data_text(value: &registry::Value) -> String {
+        match RegValue::from(value) {
+            RegValue::MultiSz(strings) => strings.join("\n"),
+            value => value.to_string(),
+        }
+    }
+
+    fn search_key(&self, path: &[String], key: &dyn RegistryKey, match_name: bool) -> bool {
+        let name = path.last().map_or("", |name| name.as_str());
+        if match_name && self.options.keys && self.matcher.is_match(name) && !self.send(path, None, HitKind::Key, name.to_owned()) {
+            return false;
+        }
+
+        if !self.options.values && !self.options.data {
+            return true;
+        }
+
+        // keys that can't be read are skipped
+        let Ok(values) = registry::read_values(key) else { return true; };
+
+        for (name, value) in values {
+            if self.options.values && self.matcher.is_match(&name) {
+                if !self.send(path, Some(&name), HitKind::ValueName, name.clone()) {
+                    return false;
+                }
+                continue;
+            }
+
+            if !self.options.data {
+                continue;
+            }
+
+            let text = Self::data_text(&value);
+            if self.matcher.is_match(&text) && !self.send(path, Some(&name), HitKind::Data, text.replace('\n', " ¦ ")) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Searches the keys depth first in the order the tree shows them.
+    /// Returns false when the search was cancelled.
+    fn walk(&self, path: &mut Vec<String>, key: &dyn RegistryKey, match_name: bool) -> bool {
+        if self.cancel.load(Ordering::Relaxed) || !self.search_key(path, key, match_name) {
+            return false;
+        }
+
+        let Ok(subkeys) = registry::read_subkeys(key) else { return true; };
+
+        for name in subkeys {
+            let Ok(subkey) = registry::read_key(key, &name) else { continue; };
+
+            path.push(name);
+            if !self.walk(path, subkey.as_ref(), true) {
+                return false;
+            }
+            path.pop();
+        }
+
+        true
+    }
+}
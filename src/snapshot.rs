@@ -0,0 +1,408 @@
+use std::path::Path;
+
+use crate::{backend::{self, memory::{self, MemoryNode}, Error, ErrorKind, RegistryBackend}, regfile::{self, ChangeKind}, registry::Value, treefile};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RCSNAP\0\0";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A key subtree frozen at one point in time, to be compared later.
+///
+/// The file is little endian: the magic and version, then the key path and
+/// the time it was taken, then the nodes depth first. Strings are a u32 byte
+/// length followed by UTF-8, a node is its name, timestamp, values (name,
+/// type, data) and subkeys, each list prefixed with a u32 count.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Full path of the key.
+    pub path: String,
+    /// FILETIME of when the snapshot was taken.
+    pub taken: u64,
+    pub root: MemoryNode,
+}
+
+impl Snapshot {
+    pub fn take(backend: &dyn RegistryBackend, path: &str) -> backend::Result<Self> {
+        let (root, components) = backend::resolve_path(backend, path)?;
+        let key = backend::open_path(backend, path)?;
+        let name = components.last().unwrap_or(&root).clone();
+        let path = std::iter::once(root).chain(components).collect::<Vec<_>>().join("\\");
+
+        Ok(Self {
+            root: treefile::read_tree(key.as_ref(), &name, true)?,
+            path,
+            taken: memory::filetime_now(),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = SNAPSHOT_MAGIC.to_vec();
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        write_str(&mut out, &self.path);
+        out.extend_from_slice(&self.taken.to_le_bytes());
+        write_node(&mut out, &self.root);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> backend::Result<Self> {
+        if !bytes.starts_with(SNAPSHOT_MAGIC) {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a snapshot file"));
+        }
+
+        let mut reader = Reader { bytes: &bytes[SNAPSHOT_MAGIC.len()..] };
+        let version = reader.u32()?;
+        if version > SNAPSHOT_VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("The snapshot is version {version}, only up to {SNAPSHOT_VERSION} is supported")));
+        }
+
+        let path = reader.string()?;
+        let taken = reader.u64()?;
+        let root = reader.node()?;
+
+        match reader.bytes.is_empty() {
+            true => Ok(Self { path, taken, root }),
+            false => Err(Error::new(ErrorKind::InvalidData, "Unexpected data after the snapshot")),
+        }
+    }
+
+    pub fn save(&self, file: impl AsRef<Path>) -> backend::Result<()> {
+        std::fs::write(file, self.encode()).map_err(Error::from)
+    }
+
+    pub fn load(file: impl AsRef<Path>) -> backend::Result<Self> {
+        Self::decode(&std::fs::read(file)?)
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_u32(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_node(out: &mut Vec<u8>, node: &MemoryNode) {
+    write_str(out, &node.name);
+    out.extend_from_slice(&node.last_write.to_le_bytes());
+
+    write_u32(out, node.values.len());
+    for (name, value) in &node.values {
+        write_str(out, name);
+        out.extend_from_slice(&u32::from(value.ty()).to_le_bytes());
+        write_u32(out, value.len());
+        out.extend_from_slice(value);
+    }
+
+    write_u32(out, node.subkeys.len());
+    for subkey in &node.subkeys {
+        write_node(out, subkey);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> backend::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(Error::new(ErrorKind::InvalidData, "The snapshot is truncated"));
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> backend::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> backend::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> backend::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid name in the snapshot"))
+    }
+
+    fn node(&mut self) -> backend::Result<MemoryNode> {
+        let mut node = MemoryNode::new(self.string()?);
+        node.last_write = self.u64()?;
+
+        for _ in 0..self.u32()? {
+            let name = self.string()?;
+            let ty = self.u32()?.into();
+            let len = self.u32()? as usize;
+            node.values.push((name, Value::new(ty, self.take(len)?.to_vec())));
+        }
+
+        for _ in 0..self.u32()? {
+            let child = self.node()?;
+            node.subkeys.push(child);
+        }
+
+        Ok(node)
+    }
+}
+
+/// One added, removed or modified key or value.
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pub kind: ChangeKind,
+    /// Components of the key path, starting with the root.
+    pub path: Vec<String>,
+    /// Name of the value, `None` for a key.
+    pub value: Option<String>,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Difference {
+    pub fn key_path(&self) -> String {
+        self.path.join("\\")
+    }
+
+    /// The data involved, `old -> new` for a modified value.
+    pub fn detail(&self) -> String {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => format!("{} -> {}", regfile::describe(old), regfile::describe(new)),
+            (Some(value), None) | (None, Some(value)) => regfile::describe(value),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+fn key_difference(kind: ChangeKind, path: &[String]) -> Difference {
+    Difference { kind, path: path.to_vec(), value: None, old: None, new: None }
+}
+
+fn value_difference(kind: ChangeKind, path: &[String], name: &str, old: Option<&Value>, new: Option<&Value>) -> Difference {
+    Difference { kind, path: path.to_vec(), value: Some(name.to_owned()), old: old.cloned(), new: new.cloned() }
+}
+
+/// Reports a whole subtree as added or removed.
+fn push_subtree(node: &MemoryNode, path: &mut Vec<String>, added: bool, out: &mut Vec<Difference>) {
+    path.push(node.name.clone());
+
+    match added {
+        true => out.push(key_difference(ChangeKind::AddKey, path)),
+        false => out.push(key_difference(ChangeKind::DeleteKey, path)),
+    }
+
+    for (name, value) in &node.values {
+        match added {
+            true => out.push(value_difference(ChangeKind::AddValue, path, name, None, Some(value))),
+            false => out.push(value_difference(ChangeKind::DeleteValue, path, name, Some(value), None)),
+        }
+    }
+
+    for subkey in &node.subkeys {
+        push_subtree(subkey, path, added, out);
+    }
+
+    path.pop();
+}
+
+fn diff_node(old: &MemoryNode, new: &MemoryNode, path: &mut Vec<String>, out: &mut Vec<Difference>) {
+    for (name, old_value) in &old.values {
+        match new.value(name) {
+            None => out.push(value_difference(ChangeKind::DeleteValue, path, name, Some(old_value), None)),
+            Some(new_value) if new_value != old_value => out.push(value_difference(ChangeKind::ModifyValue, path, name, Some(old_value), Some(new_value))),
+            Some(_) => {},
+        }
+    }
+
+    for (name, new_value) in &new.values {
+        if old.value(name).is_none() {
+            out.push(value_difference(ChangeKind::AddValue, path, name, None, Some(new_value)));
+        }
+    }
+
+    for old_subkey in &old.subkeys {
+        match new.subkey(&old_subkey.name) {
+            Some(new_subkey) => {
+                path.push(new_subkey.name.clone());
+                diff_node(old_subkey, new_subkey, path, out);
+                path.pop();
+            },
+            None => push_subtree(old_subkey, path, false, out),
+        }
+    }
+
+    for new_subkey in &new.subkeys {
+        if old.subkey(&new_subkey.name).is_none() {
+            push_subtree(new_subkey, path, true, out);
+        }
+    }
+}
+
+/// Everything that changed from `old` to `new`, depth first with the values
+/// of a key before its subkeys. Names compare case-insensitively, as in the
+/// registry. Paths are reported under the path of `new`.
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<Difference> {
+    let mut path = backend::split_path(&new.path);
+    let mut out = Vec::new();
+    diff_node(&old.root, &new.root, &mut path, &mut out);
+    out
+}
+
+/// Whether `path` is `parent` or a key below it.
+pub fn is_below(path: &[String], parent: &[String]) -> bool {
+    path.len() >= parent.len() && path.iter().zip(parent).all(|(a, b)| memory::names_equal(a, b))
+}
+
+/// A .reg file that turns the old state into the new one. Whatever lies
+/// below a deleted key goes with it and isn't listed.
+pub fn patch(differences: &[Difference]) -> String {
+    let mut out = format!("{}\r\n", regfile::REGEDIT5_HEADER);
+    let mut current: Option<&[String]> = None;
+    let mut deleted: Vec<&[String]> = Vec::new();
+
+    for difference in differences {
+        if deleted.iter().any(|parent| is_below(&difference.path, parent)) {
+            continue;
+        }
+
+        let is_current = current.is_some_and(|path| path == difference.path.as_slice());
+        if difference.kind == ChangeKind::DeleteKey {
+            out.push_str(&format!("\r\n[-{}]\r\n", difference.key_path()));
+            deleted.push(&difference.path);
+            current = None;
+            continue;
+        } else if !is_current {
+            out.push_str(&format!("\r\n[{}]\r\n", difference.key_path()));
+            current = Some(&difference.path);
+        }
+
+        match (&difference.value, &difference.new) {
+            (Some(name), Some(value)) => out.push_str(&regfile::format_value(name, value)),
+            (Some(name), None) => out.push_str(&regfile::format_deletion(name)),
+            (None, _) => {},
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::memory::MemoryBackend, registry::{self, Type}};
+
+    fn string(s: &str) -> Value {
+        Value::new(Type::String, registry::encode_sz(s))
+    }
+
+    fn key(name: &str, values: &[(&str, &str)], subkeys: Vec<MemoryNode>) -> MemoryNode {
+        let mut node = MemoryNode::new(name);
+        for (name, data) in values {
+            node.set_value(name, string(data));
+        }
+        for subkey in subkeys {
+            node.insert_subkey(subkey);
+        }
+        node
+    }
+
+    fn snapshot(root: MemoryNode) -> Snapshot {
+        Snapshot { path: format!("HKEY_CURRENT_USER\\{}", root.name), taken: 0x01d9_0000_0000_0000, root }
+    }
+
+    fn summary(differences: &[Difference]) -> Vec<String> {
+        differences.iter()
+            .map(|difference| format!("{} {} {}", difference.kind.symbol(), difference.key_path(), difference.value.as_deref().unwrap_or("-")))
+            .collect()
+    }
+
+    fn before() -> Snapshot {
+        snapshot(key("App", &[("Name", "regcli"), ("Old", "x")], vec![
+            key("Plugins", &[("Path", "C:\\plugins")], vec![key("Deep", &[("Level", "2")], Vec::new())]),
+            key("Same", &[], Vec::new()),
+        ]))
+    }
+
+    fn after() -> Snapshot {
+        snapshot(key("App", &[("NAME", "changed"), ("New", "y")], vec![
+            key("same", &[], vec![key("Added", &[("Value", "z")], Vec::new())]),
+        ]))
+    }
+
+    #[test]
+    fn encode_and_decode_round_trip() {
+        let mut old = before();
+        old.root.values.push(("Odd".to_owned(), Value::new(Type::Other(0x1234), vec![1, 2, 3])));
+        old.root.last_write = 42;
+
+        let decoded = Snapshot::decode(&old.encode()).unwrap();
+        assert_eq!(decoded.path, old.path);
+        assert_eq!(decoded.taken, old.taken);
+        assert_eq!(decoded.root.last_write, 42);
+        assert_eq!(decoded.root.value("Odd"), Some(&Value::new(Type::Other(0x1234), vec![1, 2, 3])));
+        assert!(diff(&old, &decoded).is_empty());
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        let bytes = before().encode();
+
+        for len in [SNAPSHOT_MAGIC.len() + 2, bytes.len() / 2, bytes.len() - 1] {
+            assert_eq!(Snapshot::decode(&bytes[..len]).err().unwrap().message(), "The snapshot is truncated");
+        }
+
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(Snapshot::decode(&longer).err().unwrap().message(), "Unexpected data after the snapshot");
+        assert_eq!(Snapshot::decode(b"REGEDIT4").err().unwrap().message(), "Not a snapshot file");
+    }
+
+    #[test]
+    fn diff_matches_names_ignoring_case() {
+        assert!(diff(&before(), &snapshot(key("APP", &[("name", "regcli"), ("OLD", "x")], vec![
+            key("plugins", &[("path", "C:\\plugins")], vec![key("DEEP", &[("level", "2")], Vec::new())]),
+            key("SAME", &[], Vec::new()),
+        ]))).is_empty());
+    }
+
+    #[test]
+    fn diff_lists_values_before_subkeys() {
+        let differences = diff(&before(), &after());
+
+        assert_eq!(summary(&differences), [
+            "~ HKEY_CURRENT_USER\\App Name",
+            "- HKEY_CURRENT_USER\\App Old",
+            "+ HKEY_CURRENT_USER\\App New",
+            "- HKEY_CURRENT_USER\\App\\Plugins -",
+            "- HKEY_CURRENT_USER\\App\\Plugins Path",
+            "- HKEY_CURRENT_USER\\App\\Plugins\\Deep -",
+            "- HKEY_CURRENT_USER\\App\\Plugins\\Deep Level",
+            "+ HKEY_CURRENT_USER\\App\\same\\Added -",
+            "+ HKEY_CURRENT_USER\\App\\same\\Added Value",
+        ]);
+        assert_eq!(differences[0].old, Some(string("regcli")));
+        assert_eq!(differences[0].new, Some(string("changed")));
+    }
+
+    #[test]
+    fn patch_skips_what_is_below_deleted_keys() {
+        let patch = patch(&diff(&before(), &after()));
+
+        assert!(patch.starts_with(regfile::REGEDIT5_HEADER));
+        assert!(patch.contains("[-HKEY_CURRENT_USER\\App\\Plugins]"));
+        assert!(!patch.contains("Deep"));
+        assert!(!patch.contains("\"Path\""));
+    }
+
+    #[test]
+    fn patch_turns_the_old_tree_into_the_new_one() {
+        let backend = MemoryBackend::new("Memory", vec![key("HKEY_CURRENT_USER", &[], vec![before().root])]);
+        let file = regfile::parse(&regfile::encode_text(&patch(&diff(&before(), &after())))).unwrap();
+        regfile::apply(&backend, &file).unwrap();
+
+        let applied = Snapshot::take(&backend, "HKEY_CURRENT_USER\\App").unwrap();
+        assert!(diff(&applied, &after()).is_empty());
+        assert_eq!(applied.root.value("Name"), Some(&string("changed")));
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+pub struct TreeRow {
+    /// Key names from the root down, e.g. `["HKEY_CURRENT_USER", "Software"]`.
+    pub path: Vec<String>,
+    pub expanded: bool,
+    /// `None` until the children have been loaded.
+    pub has_children: Option<bool>,
+}
+
+impl TreeRow {
+    pub fn name(&self) -> &str {
+        self.path.last().map_or("", |name| name.as_str())
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+}
+
+fn path_id(path: &[String]) -> String {
+    path.join("\\").to_uppercase()
+}
+
+/// Expanded keys and their children, loaded lazily. The visible rows are
+/// rebuilt from these whenever either changes.
+#[derive(Default)]
+pub struct KeyTree {
+    expanded: HashSet<String>,
+    children: HashMap<String, Vec<String>>,
+
+    pub rows: Vec<TreeRow>,
+}
+
+impl KeyTree {
+    pub fn is_expanded(&self, path: &[String]) -> bool {
+        self.expanded.contains(&path_id(path))
+    }
+
+    pub fn set_expanded(&mut self, path: &[String], expanded: bool) {
+        match expanded {
+            true => self.expanded.insert(path_id(path)),
+            false => self.expanded.remove(&path_id(path)),
+        };
+    }
+
+    pub fn children(&self, path: &[String]) -> Option<&Vec<String>> {
+        self.children.get(&path_id(path))
+    }
+
+    pub fn set_children(&mut self, path: &[String], children: Vec<String>) {
+        self.children.insert(path_id(path), children);
+    }
+
+    pub fn clear_children(&mut self) {
+        self.children.clear();
+    }
+
+    pub fn position(&self, path: &[String]) -> Option<usize> {
+        let id = path_id(path);
+        self.rows.iter().position(|row| path_id(&row.path) == id)
+    }
+
+    /// Lays out the rows again. Expanded keys whose children aren't known
+    /// yet are loaded through `load`, and collapsed when that fails.
+    pub fn rebuild(&mut self, roots: &[String], mut load: impl FnMut(&[String]) -> Option<Vec<String>>) {
+        let mut rows = Vec::new();
+        let mut stack: Vec<Vec<String>> = roots.iter().rev().map(|root| vec![root.clone()]).collect();
+
+        while let Some(path) = stack.pop() {
+            let id = path_id(&path);
+            let mut expanded = self.expanded.contains(&id);
+
+            if expanded && !self.children.contains_key(&id) {
+                match load(&path) {
+                    Some(children) => {
+                        self.children.insert(id.clone(), children);
+                    }
+                    None => {
+                        self.expanded.remove(&id);
+                        expanded = false;
+                    }
+                }
+            }
+
+            let children = self.children.get(&id);
+            if expanded && let Some(children) = children {
+                for child in children.iter().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(child.clone());
+                    stack.push(child_path);
+                }
+            }
+
+            rows.push(TreeRow { has_children: children.map(|children| !children.is_empty()), path, expanded });
+        }
+
+        self.rows = rows;
+    }
+}
@@ -0,0 +1,441 @@
+use serde_json::{json, Map};
+
+use crate::{backend::{self, memory::MemoryNode, Error, ErrorKind, RegistryBackend, RegistryKey}, regfile::{RegFile, RegFileVersion, RegOperation}, registry::{self, RegValue, Type, Value}};
+
+pub const TREE_FILE_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    Json,
+    Yaml,
+}
+
+impl TreeFormat {
+    /// Picks the format from the file extension, `None` meaning a .reg file.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path.trim()).extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Adds and overwrites, leaving everything else alone.
+    Merge,
+    /// Also removes the values and subkeys that aren't in the file.
+    Replace,
+}
+
+/// A key subtree in the layout of the memory backend fixtures, with explicit
+/// types on every value:
+///
+/// ```yaml
+/// version: 1
+/// path: HKEY_CURRENT_USER\Software\Regcli
+/// recursive: true
+/// key:
+///   timestamp: 133485408000000000
+///   values:
+///     Enabled: { type: REG_DWORD, data: 1 }
+///     Blob: { type: REG_BINARY, hex: deadbeef }
+///   keys:
+///     Servers: {}
+/// ```
+///
+/// Data that doesn't decode cleanly is written as `hex`, so nothing is lost.
+#[derive(Debug, Clone)]
+pub struct TreeFile {
+    /// Full path of the exported key.
+    pub path: String,
+    /// Whether the subkeys were exported, replacing a key only touches its
+    /// subkeys when they were.
+    pub recursive: bool,
+    pub root: MemoryNode,
+}
+
+fn invalid_file(path: &str, message: &str) -> Error {
+    match path.is_empty() {
+        true => Error::new(ErrorKind::InvalidData, format!("Invalid file: {message}")),
+        false => Error::new(ErrorKind::InvalidData, format!("Invalid file at {path}: {message}")),
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits.chunks(2)
+        .map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+/// Reads a key and, when `recursive`, everything below it. The timestamp is
+/// left at 0 when the backend doesn't keep one.
+pub fn read_tree(key: &dyn RegistryKey, name: &str, recursive: bool) -> backend::Result<MemoryNode> {
+    let mut node = MemoryNode::new(name);
+    node.last_write = key.last_write()?.unwrap_or(0);
+    node.values = registry::read_values(key)?;
+
+    if recursive {
+        for subkey in registry::read_subkeys(key)? {
+            let child = read_tree(registry::read_key(key, &subkey)?.as_ref(), &subkey, true)?;
+            node.insert_subkey(child);
+        }
+    }
+
+    Ok(node)
+}
+
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    let ty = match value.ty() {
+        Type::Other(ty) => json!(ty),
+        ty => json!(registry::type_to_str(ty)),
+    };
+
+    let data = match RegValue::from(value) {
+        RegValue::Sz(s) | RegValue::ExpandSz(s) | RegValue::Link(s) => json!(s),
+        RegValue::MultiSz(strings) => json!(strings),
+        RegValue::Dword(n) | RegValue::DwordBE(n) => json!(n),
+        RegValue::Qword(n) => json!(n),
+        _ => return json!({ "type": ty, "hex": encode_hex(value) }),
+    };
+
+    json!({ "type": ty, "data": data })
+}
+
+/// Serializes the node with everything below it, counting the keys.
+pub fn node_to_json(node: &MemoryNode, timestamps: bool, count: &mut usize) -> serde_json::Value {
+    let mut object = Map::new();
+    *count += 1;
+
+    if timestamps && node.last_write != 0 {
+        object.insert("timestamp".into(), json!(node.last_write));
+    }
+    if !node.values.is_empty() {
+        let values = node.values.iter().map(|(name, value)| (name.clone(), value_to_json(value))).collect();
+        object.insert("values".into(), serde_json::Value::Object(values));
+    }
+    if !node.subkeys.is_empty() {
+        let keys = node.subkeys.iter().map(|subkey| (subkey.name.clone(), node_to_json(subkey, timestamps, count))).collect();
+        object.insert("keys".into(), serde_json::Value::Object(keys));
+    }
+
+    serde_json::Value::Object(object)
+}
+
+/// Formats the file, returning the text along with the number of keys in it.
+pub fn format(file: &TreeFile, timestamps: bool, format: TreeFormat) -> backend::Result<(String, usize)> {
+    let mut count = 0;
+    let json = json!({
+        "version": TREE_FILE_VERSION,
+        "path": file.path,
+        "recursive": file.recursive,
+        "key": node_to_json(&file.root, timestamps, &mut count),
+    });
+
+    let text = match format {
+        TreeFormat::Json => serde_json::to_string_pretty(&json).map(|text| text + "\n").map_err(|err| Error::other(err.to_string()))?,
+        TreeFormat::Yaml => serde_yaml::to_string(&json).map_err(|err| Error::other(err.to_string()))?,
+    };
+
+    Ok((text, count))
+}
+
+/// Exports a key, and optionally everything below it, as JSON or YAML.
+/// `path` is the full path stored in the file. Returns the text along with
+/// the number of keys written.
+pub fn export(key: &dyn RegistryKey, path: &str, recursive: bool, timestamps: bool, tree_format: TreeFormat) -> backend::Result<(String, usize)> {
+    let name = backend::split_path(path).pop().unwrap_or_default();
+    let file = TreeFile { path: path.to_owned(), recursive, root: read_tree(key, &name, recursive)? };
+
+    format(&file, timestamps, tree_format)
+}
+
+fn parse_type(path: &str, json: Option<&serde_json::Value>) -> backend::Result<Type> {
+    match json {
+        Some(serde_json::Value::String(ty)) => ty.parse().map_err(|_| invalid_file(path, &format!("unknown type {ty}"))),
+        Some(ty) => ty.as_u64()
+            .and_then(|ty| u32::try_from(ty).ok())
+            .map(Type::from)
+            .ok_or_else(|| invalid_file(path, "type must be a type name or a number")),
+        None => Err(invalid_file(path, "a value must have a type")),
+    }
+}
+
+/// Parses a value, `path` only being used in error messages.
+pub fn value_from_json(path: &str, json: &serde_json::Value) -> backend::Result<Value> {
+    let Some(object) = json.as_object() else {
+        return Err(invalid_file(path, "a value must be an object with a type"));
+    };
+
+    let ty = parse_type(path, object.get("type"))?;
+
+    if let Some(hex) = object.get("hex") {
+        let bytes = hex.as_str()
+            .and_then(decode_hex)
+            .ok_or_else(|| invalid_file(path, "hex must be a string of hex digit pairs"))?;
+
+        return Ok(Value::new(ty, bytes));
+    }
+
+    let Some(data) = object.get("data") else {
+        return Err(invalid_file(path, "a value must have data or hex"));
+    };
+
+    let string = || data.as_str().map(|s| s.to_owned()).ok_or_else(|| invalid_file(path, "data must be a string"));
+    let value = match ty {
+        Type::String => RegValue::Sz(string()?),
+        Type::ExpandString => RegValue::ExpandSz(string()?),
+        Type::Link => RegValue::Link(string()?),
+        Type::MultiString => {
+            let strings = data.as_array()
+                .and_then(|items| items.iter().map(|item| item.as_str().map(|s| s.to_owned())).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| invalid_file(path, "data must be an array of strings"))?;
+
+            RegValue::MultiSz(strings)
+        }
+        Type::U32 | Type::U32BigEndian => {
+            let n = data.as_u64().and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(|| invalid_file(path, "data must be a 32-bit unsigned number"))?;
+
+            match ty {
+                Type::U32BigEndian => RegValue::DwordBE(n),
+                _ => RegValue::Dword(n),
+            }
+        }
+        Type::U64 => RegValue::Qword(data.as_u64().ok_or_else(|| invalid_file(path, "data must be a 64-bit unsigned number"))?),
+        ty => return Err(invalid_file(path, &format!("{ty} data has to be given as hex"))),
+    };
+
+    Ok(value.encode())
+}
+
+pub fn node_from_json(name: &str, path: &str, json: &serde_json::Value) -> backend::Result<MemoryNode> {
+    let Some(object) = json.as_object() else {
+        return Err(invalid_file(path, "a key must be an object"));
+    };
+
+    let mut node = MemoryNode::new(name);
+    node.last_write = 0;
+
+    if let Some(timestamp) = object.get("timestamp") {
+        node.last_write = timestamp.as_u64().ok_or_else(|| invalid_file(path, "timestamp must be a FILETIME number"))?;
+    }
+
+    if let Some(values) = object.get("values") {
+        let values = values.as_object().ok_or_else(|| invalid_file(path, "values must be an object"))?;
+
+        for (value_name, value) in values {
+            if node.value(value_name).is_some() {
+                return Err(invalid_file(path, &format!("duplicate value {value_name}")));
+            }
+
+            node.values.push((value_name.clone(), value_from_json(&format!("{path}\\@{value_name}"), value)?));
+        }
+    }
+
+    if let Some(keys) = object.get("keys") {
+        let keys = keys.as_object().ok_or_else(|| invalid_file(path, "keys must be an object"))?;
+
+        for (subkey_name, subkey) in keys {
+            if subkey_name.is_empty() || subkey_name.contains('\\') {
+                return Err(invalid_file(path, "key names can't be empty or contain backslashes"));
+            }
+            if node.subkey(subkey_name).is_some() {
+                return Err(invalid_file(path, &format!("duplicate key {subkey_name}")));
+            }
+
+            node.insert_subkey(node_from_json(subkey_name, &format!("{path}\\{subkey_name}"), subkey)?);
+        }
+    }
+
+    Ok(node)
+}
+
+pub fn parse(text: &str, format: TreeFormat) -> backend::Result<TreeFile> {
+    let json: serde_json::Value = match format {
+        TreeFormat::Json => serde_json::from_str(text).map_err(|err| invalid_file("", &err.to_string()))?,
+        TreeFormat::Yaml => serde_yaml::from_str(text).map_err(|err| invalid_file("", &err.to_string()))?,
+    };
+
+    let version = json.get("version").and_then(|version| version.as_u64());
+    if version.is_some_and(|version| version > TREE_FILE_VERSION) {
+        return Err(invalid_file("", "the file was written by a newer version of regcli"));
+    }
+
+    let Some(path) = json.get("path").and_then(|path| path.as_str()) else {
+        return Err(invalid_file("", "expected the \"path\" of the key"));
+    };
+    let Some(key) = json.get("key") else {
+        return Err(invalid_file("", "expected the \"key\" itself"));
+    };
+
+    let recursive = json.get("recursive").and_then(|recursive| recursive.as_bool()).unwrap_or(true);
+    let name = backend::split_path(path).pop().unwrap_or_default();
+
+    Ok(TreeFile { path: path.to_owned(), recursive, root: node_from_json(&name, path, key)? })
+}
+
+fn push_key(backend: &dyn RegistryBackend, path: &str, node: &MemoryNode, recursive: bool, mode: ImportMode, operations: &mut Vec<RegOperation>) -> backend::Result<()> {
+    operations.push(RegOperation::CreateKey { path: path.to_owned() });
+
+    if mode == ImportMode::Replace
+        && let Ok(existing) = backend::open_path(backend, path)
+    {
+        for (name, _) in registry::read_values(existing.as_ref())? {
+            if node.value(&name).is_none() {
+                operations.push(RegOperation::DeleteValue { path: path.to_owned(), name });
+            }
+        }
+
+        if recursive {
+            for name in registry::read_subkeys(existing.as_ref())? {
+                if node.subkey(&name).is_none() {
+                    operations.push(RegOperation::DeleteKey { path: format!("{path}\\{name}") });
+                }
+            }
+        }
+    }
+
+    for (name, value) in &node.values {
+        operations.push(RegOperation::SetValue { path: path.to_owned(), name: name.clone(), value: value.clone() });
+    }
+
+    if recursive {
+        for subkey in &node.subkeys {
+            push_key(backend, &format!("{path}\\{}", subkey.name), subkey, recursive, mode, operations)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the file into the operations a .reg file would use to get the
+/// registry to the same state, so it can be previewed and applied the same way.
+pub fn operations(backend: &dyn RegistryBackend, file: &TreeFile, mode: ImportMode) -> backend::Result<RegFile> {
+    let mut operations = Vec::new();
+    push_key(backend, &file.path, &file.root, file.recursive, mode, &mut operations)?;
+
+    Ok(RegFile { version: RegFileVersion::Regedit5, operations })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn tree_file() -> TreeFile {
+        let mut root = MemoryNode::new("App");
+        root.last_write = 133485408000000000;
+        root.values = vec![
+            ("Unterminated".to_owned(), Value::new(Type::String, b"a\0b\0".to_vec())),
+            ("Odd".to_owned(), Value::new(Type::String, vec![b'a', 0, b'b'])),
+            ("Short".to_owned(), Value::new(Type::U32, vec![1, 2])),
+            ("Long".to_owned(), Value::new(Type::U32, vec![1, 2, 3, 4, 5])),
+            ("Other".to_owned(), Value::new(Type::Other(0x1234), vec![0xde, 0xad])),
+            ("None".to_owned(), Value::new(Type::None, Vec::new())),
+            ("Number".to_owned(), Value::new(Type::U32, 7u32.to_le_bytes().to_vec())),
+            ("Text".to_owned(), Value::new(Type::String, registry::encode_sz("text"))),
+            ("List".to_owned(), Value::new(Type::MultiString, registry::encode_multi_sz(&["a".to_owned(), "b".to_owned()]))),
+        ];
+        root.insert_subkey(MemoryNode::new("Empty"));
+
+        TreeFile { path: "HKEY_CURRENT_USER\\App".to_owned(), recursive: true, root }
+    }
+
+    fn describe(operations: &[RegOperation]) -> Vec<String> {
+        operations.iter()
+            .map(|operation| match operation {
+                RegOperation::CreateKey { path } => format!("create {path}"),
+                RegOperation::DeleteKey { path } => format!("delete {path}"),
+                RegOperation::SetValue { path, name, .. } => format!("set {path} {name}"),
+                RegOperation::DeleteValue { path, name } => format!("delete {path} {name}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn format_and_parse_keep_every_byte() {
+        let file = tree_file();
+
+        for tree_format in [TreeFormat::Json, TreeFormat::Yaml] {
+            let (text, count) = format(&file, true, tree_format).unwrap();
+            assert_eq!(count, 2);
+
+            let parsed = parse(&text, tree_format).unwrap();
+            assert_eq!(parsed.path, file.path);
+            assert_eq!(parsed.root.last_write, file.root.last_write);
+            assert_eq!(parsed.root.values, file.root.values, "{}", tree_format.name());
+            assert_eq!(parsed.root.subkeys.len(), 1);
+
+            assert_eq!(format(&parsed, true, tree_format).unwrap().0, text);
+        }
+    }
+
+    #[test]
+    fn bad_values_are_reported_with_their_path() {
+        let text = r#"{ "path": "HKEY_CURRENT_USER\\App", "key": { "values": { "Name": { "type": "REG_DWORD", "data": "x" } } } }"#;
+
+        assert_eq!(parse(text, TreeFormat::Json).err().unwrap().message(), "Invalid file at HKEY_CURRENT_USER\\App\\@Name: data must be a 32-bit unsigned number");
+    }
+
+    #[test]
+    fn replace_only_removes_what_is_missing() {
+        let backend = MemoryBackend::from_json(r#"{
+            "HKEY_CURRENT_USER": {
+                "keys": {
+                    "App": {
+                        "values": { "Name": "regcli", "Old": "x" },
+                        "keys": { "Keep": { "values": { "Extra": 1 } }, "Gone": {} }
+                    }
+                }
+            }
+        }"#).unwrap();
+
+        let text = r#"{ "path": "HKEY_CURRENT_USER\\App", "key": { "values": { "name": { "type": "REG_SZ", "data": "new" } }, "keys": { "KEEP": {}, "Added": {} } } }"#;
+        let mut file = parse(text, TreeFormat::Json).unwrap();
+
+        assert_eq!(describe(&operations(&backend, &file, ImportMode::Replace).unwrap().operations), [
+            "create HKEY_CURRENT_USER\\App",
+            "delete HKEY_CURRENT_USER\\App Old",
+            "delete HKEY_CURRENT_USER\\App\\Gone",
+            "set HKEY_CURRENT_USER\\App name",
+            "create HKEY_CURRENT_USER\\App\\Added",
+            "create HKEY_CURRENT_USER\\App\\KEEP",
+            "delete HKEY_CURRENT_USER\\App\\KEEP Extra",
+        ]);
+
+        assert_eq!(describe(&operations(&backend, &file, ImportMode::Merge).unwrap().operations), [
+            "create HKEY_CURRENT_USER\\App",
+            "set HKEY_CURRENT_USER\\App name",
+            "create HKEY_CURRENT_USER\\App\\Added",
+            "create HKEY_CURRENT_USER\\App\\KEEP",
+        ]);
+
+        // subkeys that weren't exported are left alone
+        file.recursive = false;
+        assert_eq!(describe(&operations(&backend, &file, ImportMode::Replace).unwrap().operations), [
+            "create HKEY_CURRENT_USER\\App",
+            "delete HKEY_CURRENT_USER\\App Old",
+            "set HKEY_CURRENT_USER\\App name",
+        ]);
+    }
+}